@@ -41,7 +41,9 @@ struct VertexCustom {
 #ifdef MORPH_TARGETS
     @builtin(vertex_index) index: u32,
 #endif
-    @location(8) texture_id: u32
+    @location(8) texture_id: u32,
+    @location(9) ambient_occlusion: f32,
+    @location(10) light: vec2<f32>,
 };
 
 struct CustomVertexOutput {
@@ -69,6 +71,8 @@ struct CustomVertexOutput {
     @location(7) @interpolate(flat) visibility_range_dither: i32,
 #endif
     @location(8) texture_index: u32,
+    @location(9) ambient_occlusion: f32,
+    @location(10) light: vec2<f32>,
 }
 
 @vertex
@@ -197,6 +201,8 @@ fn vertex(vertex_custom: VertexCustom) -> CustomVertexOutput {
 #endif
 
     custom_out.texture_index = vertex_custom.texture_id;
+    custom_out.ambient_occlusion = vertex_custom.ambient_occlusion;
+    custom_out.light = vertex_custom.light;
 
     return custom_out;
 }
@@ -242,6 +248,14 @@ fn fragment(
     pbr_input.material.base_color = pbr_input.material.base_color * in.color;
 #endif
 
+    // baked voxel ambient occlusion darkens corners and crevices for direct and indirect light
+    pbr_input.material.base_color = vec4<f32>(pbr_input.material.base_color.rgb * in_custom.ambient_occlusion, pbr_input.material.base_color.a);
+
+    // voxel light, the brighter of sky and block light, loses a fifth of its brightness per level
+    let voxel_light = max(in_custom.light.x, in_custom.light.y);
+    let light_brightness = pow(0.8, (1.0 - voxel_light) * 15.0);
+    pbr_input.material.base_color = vec4<f32>(pbr_input.material.base_color.rgb * light_brightness, pbr_input.material.base_color.a);
+
 
     // alpha discard
     pbr_input.material.base_color = alpha_discard(pbr_input.material, pbr_input.material.base_color);
@@ -0,0 +1,132 @@
+//! Compares the bitmask greedy mesher against the previous per voxel one on generated terrain.
+//!
+//! Run with `cargo bench --bench mesh_generation` from the crate root, so the assets are found.
+
+use bevy::math::IVec2;
+use brunch::Bench;
+use opentale::world_generation::chunk_generation::mesh_generation::{
+    generate_mesh_buffers, generate_mesh_buffers_per_voxel, ChunkMeshes,
+};
+use opentale::world_generation::voxel_world::{ChunkLod, NeighbourLods};
+use opentale::world_generation::world_generator::{GeneratedChunk, WorldGenerator};
+
+const SEED: u64 = 3;
+
+fn main() {
+    let world_generator = WorldGenerator::new(SEED);
+    let block_registry = world_generator.generation_options().block_registry.clone();
+
+    let full_lod = get_busiest_chunk(&world_generator, ChunkLod::Full);
+    let eighth_lod = get_busiest_chunk(&world_generator, ChunkLod::Eighth);
+    let stitched = NeighbourLods([Some(ChunkLod::Half); 4]);
+
+    let cases = [
+        ("full lod", &full_lod, NeighbourLods::default()),
+        ("full lod, stitched", &full_lod, stitched),
+        ("eighth lod", &eighth_lod, NeighbourLods::default()),
+    ];
+
+    println!("Triangles (per voxel / bitmask):");
+    for (name, chunk, neighbour_lods) in &cases {
+        let per_voxel = count_triangles(&generate_mesh_buffers_per_voxel(
+            &chunk.voxel_data,
+            chunk.min_height,
+            chunk.chunk_lod,
+            *neighbour_lods,
+            &block_registry,
+        ));
+        let bitmask = count_triangles(&generate_mesh_buffers(
+            &chunk.voxel_data,
+            chunk.min_height,
+            chunk.chunk_lod,
+            *neighbour_lods,
+            &block_registry,
+        ));
+        println!("  {name}: {per_voxel} / {bitmask}");
+    }
+
+    brunch::benches!(
+        inline:
+
+        Bench::new("generate_mesh_buffers_per_voxel(full lod)").run(|| {
+            generate_mesh_buffers_per_voxel(
+                &full_lod.voxel_data,
+                full_lod.min_height,
+                full_lod.chunk_lod,
+                NeighbourLods::default(),
+                &block_registry,
+            )
+        }),
+        Bench::new("generate_mesh_buffers(full lod)").run(|| {
+            generate_mesh_buffers(
+                &full_lod.voxel_data,
+                full_lod.min_height,
+                full_lod.chunk_lod,
+                NeighbourLods::default(),
+                &block_registry,
+            )
+        }),
+
+        Bench::spacer(),
+
+        Bench::new("generate_mesh_buffers_per_voxel(full lod, stitched)").run(|| {
+            generate_mesh_buffers_per_voxel(
+                &full_lod.voxel_data,
+                full_lod.min_height,
+                full_lod.chunk_lod,
+                stitched,
+                &block_registry,
+            )
+        }),
+        Bench::new("generate_mesh_buffers(full lod, stitched)").run(|| {
+            generate_mesh_buffers(
+                &full_lod.voxel_data,
+                full_lod.min_height,
+                full_lod.chunk_lod,
+                stitched,
+                &block_registry,
+            )
+        }),
+
+        Bench::spacer(),
+
+        Bench::new("generate_mesh_buffers_per_voxel(eighth lod)").run(|| {
+            generate_mesh_buffers_per_voxel(
+                &eighth_lod.voxel_data,
+                eighth_lod.min_height,
+                eighth_lod.chunk_lod,
+                NeighbourLods::default(),
+                &block_registry,
+            )
+        }),
+        Bench::new("generate_mesh_buffers(eighth lod)").run(|| {
+            generate_mesh_buffers(
+                &eighth_lod.voxel_data,
+                eighth_lod.min_height,
+                eighth_lod.chunk_lod,
+                NeighbourLods::default(),
+                &block_registry,
+            )
+        }),
+    );
+}
+
+/// The chunk of the column at the origin with the most triangles, usually the one with the
+/// surface in it.
+fn get_busiest_chunk(world_generator: &WorldGenerator, chunk_lod: ChunkLod) -> GeneratedChunk {
+    let block_registry = &world_generator.generation_options().block_registry;
+
+    world_generator
+        .generate_column(IVec2::ZERO, chunk_lod)
+        .into_iter()
+        .max_by_key(|chunk| count_triangles(&chunk.generate_mesh(block_registry)))
+        .expect("A column has at least one chunk")
+}
+
+fn count_triangles(meshes: &ChunkMeshes) -> usize {
+    [&meshes.solid, &meshes.transparent]
+        .into_iter()
+        .flatten()
+        .map(|buffers| buffers.indices.len() / 3)
+        .sum()
+}
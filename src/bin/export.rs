@@ -0,0 +1,421 @@
+use bevy::math::{IVec2, IVec3, Vec3};
+use image::{ImageBuffer, Luma, Rgb};
+use noise::NoiseFn;
+use opentale::world_generation::chunk_generation::block_registry::BlockRegistry;
+use opentale::world_generation::chunk_generation::mesh_generation::{
+    ChunkMeshBuffers, ChunkMeshes,
+};
+use opentale::world_generation::chunk_generation::voxel_generation::get_terrain_noise;
+use opentale::world_generation::chunk_generation::{chunk_size, BlockType, VOXEL_SIZE};
+use opentale::world_generation::chunk_settings::load_chunk_settings;
+use opentale::world_generation::voxel_world::ChunkLod;
+use opentale::world_generation::world_generator::{GeneratedChunk, WorldGenerator};
+use std::fmt::Write as _;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::process::ExitCode;
+
+const USAGE: &str = "Usage: export --seed <seed> --from <x> <z> --to <x> <z> [--lod <1-9>] \
+[--format <obj|gltf>] [--output <directory>]
+
+Chunk positions are given in chunks of the chosen lod, lod 1 being full detail.";
+
+const MTL_FILE_NAME: &str = "materials.mtl";
+
+#[derive(Copy, Clone, PartialEq)]
+enum MeshFormat {
+    Obj,
+    Gltf,
+}
+
+struct ExportOptions {
+    seed: u64,
+    from: IVec2,
+    to: IVec2,
+    chunk_lod: ChunkLod,
+    format: MeshFormat,
+    output: PathBuf,
+}
+
+fn main() -> ExitCode {
+    // Chunk positions depend on the chunk settings, so exports have to use the ones of the game.
+    load_chunk_settings();
+
+    let options = match parse_args(std::env::args().skip(1)) {
+        Ok(options) => options,
+        Err(error) => {
+            eprintln!("{error}\n\n{USAGE}");
+            return ExitCode::FAILURE;
+        }
+    };
+
+    match export(&options) {
+        Ok(()) => ExitCode::SUCCESS,
+        Err(error) => {
+            eprintln!("Export failed: {error}");
+            ExitCode::FAILURE
+        }
+    }
+}
+
+fn parse_args(mut args: impl Iterator<Item = String>) -> Result<ExportOptions, String> {
+    fn next_value<T: std::str::FromStr>(
+        args: &mut impl Iterator<Item = String>,
+        name: &str,
+    ) -> Result<T, String> {
+        let value = args.next().ok_or(format!("Missing value for {name}"))?;
+        value
+            .parse()
+            .map_err(|_| format!("Invalid value for {name}: {value}"))
+    }
+
+    let mut seed = None;
+    let mut from = None;
+    let mut to = None;
+    let mut chunk_lod = ChunkLod::Full;
+    let mut format = MeshFormat::Obj;
+    let mut output = PathBuf::from("export");
+
+    while let Some(arg) = args.next() {
+        match arg.as_str() {
+            "--seed" => seed = Some(next_value(&mut args, "--seed")?),
+            "--from" => {
+                from = Some(IVec2::new(
+                    next_value(&mut args, "--from")?,
+                    next_value(&mut args, "--from")?,
+                ))
+            }
+            "--to" => {
+                to = Some(IVec2::new(
+                    next_value(&mut args, "--to")?,
+                    next_value(&mut args, "--to")?,
+                ))
+            }
+            "--lod" => {
+                let lod: u8 = next_value(&mut args, "--lod")?;
+                chunk_lod = ChunkLod::from_u8(lod).ok_or(format!("Invalid lod: {lod}"))?;
+            }
+            "--format" => {
+                format = match args.next().as_deref() {
+                    Some("obj") => MeshFormat::Obj,
+                    Some("gltf") => MeshFormat::Gltf,
+                    other => return Err(format!("Unknown mesh format: {other:?}")),
+                }
+            }
+            "--output" => output = PathBuf::from(next_value::<String>(&mut args, "--output")?),
+            other => return Err(format!("Unknown argument: {other}")),
+        }
+    }
+
+    let from = from.ok_or("Missing --from")?;
+    let to = to.ok_or("Missing --to")?;
+
+    Ok(ExportOptions {
+        seed: seed.ok_or("Missing --seed")?,
+        from: from.min(to),
+        to: from.max(to),
+        chunk_lod,
+        format,
+        output,
+    })
+}
+
+fn export(options: &ExportOptions) -> Result<(), String> {
+    let mesh_directory = options.output.join("meshes");
+    fs::create_dir_all(&mesh_directory).map_err(|error| error.to_string())?;
+
+    let world_generator = WorldGenerator::new(options.seed);
+    let block_registry = &world_generator.generation_options().block_registry;
+    let terrain_noise = get_terrain_noise(world_generator.generation_options());
+    let multiplier = options.chunk_lod.multiplier_i32();
+
+    if options.format == MeshFormat::Obj {
+        write_mtl(&mesh_directory.join(MTL_FILE_NAME), block_registry)
+            .map_err(|error| format!("Failed to write {MTL_FILE_NAME}: {error}"))?;
+    }
+
+    let size = (options.to - options.from + IVec2::ONE) * chunk_size() as i32;
+    let mut heights = vec![0f64; (size.x * size.y) as usize];
+    let mut material_map = ImageBuffer::<Rgb<u8>, Vec<u8>>::new(size.x as u32, size.y as u32);
+
+    for chunk_x in options.from.x..=options.to.x {
+        for chunk_z in options.from.y..=options.to.y {
+            let chunk_pos = IVec2::new(chunk_x, chunk_z) * multiplier;
+            let column = world_generator.generate_column(chunk_pos, options.chunk_lod);
+            let pixel_offset = (IVec2::new(chunk_x, chunk_z) - options.from) * chunk_size() as i32;
+
+            for x in 0..chunk_size() as i32 {
+                for z in 0..chunk_size() as i32 {
+                    let pixel = (pixel_offset + IVec2::new(x, z)).as_uvec2();
+
+                    // Cell 0 of the voxel data is padding, so the chunk itself starts at 1.
+                    let total_x = chunk_pos.x * chunk_size() as i32 + (x + 1) * multiplier;
+                    let total_z = chunk_pos.y * chunk_size() as i32 + (z + 1) * multiplier;
+                    let height = terrain_noise.get([total_x as f64, total_z as f64]);
+                    heights[(pixel.y * size.x as u32 + pixel.x) as usize] = height;
+
+                    let top_block = get_top_block(&column, IVec2::new(x + 1, z + 1));
+                    material_map.put_pixel(
+                        pixel.x,
+                        pixel.y,
+                        Rgb(block_registry.get(top_block).map_color),
+                    );
+                }
+            }
+
+            let file_name = format!("chunk_{chunk_x}_{chunk_z}");
+            match options.format {
+                MeshFormat::Obj => write_obj(
+                    &mesh_directory.join(format!("{file_name}.obj")),
+                    &column,
+                    block_registry,
+                ),
+                MeshFormat::Gltf => {
+                    write_gltf(&mesh_directory, &file_name, &column, block_registry)
+                }
+            }
+            .map_err(|error| format!("Failed to write {file_name}: {error}"))?;
+
+            println!("Exported chunk [{chunk_x}, {chunk_z}]");
+        }
+    }
+
+    // Spread over the whole range of the image, as raw voxel heights would only use its lowest
+    // part.
+    let min_height = heights.iter().copied().fold(f64::INFINITY, f64::min);
+    let max_height = heights.iter().copied().fold(f64::NEG_INFINITY, f64::max);
+    let height_range = (max_height - min_height).max(f64::EPSILON);
+    let heightmap =
+        ImageBuffer::<Luma<u16>, Vec<u16>>::from_fn(size.x as u32, size.y as u32, |x, y| {
+            let height = heights[(y * size.x as u32 + x) as usize];
+            Luma([((height - min_height) / height_range * 65535.).round() as u16])
+        });
+    heightmap
+        .save(options.output.join("heightmap.png"))
+        .map_err(|error| error.to_string())?;
+    println!("Heightmap goes from black at {min_height:.2} to white at {max_height:.2} voxels");
+    material_map
+        .save(options.output.join("materials.png"))
+        .map_err(|error| error.to_string())?;
+
+    println!("Export written to {:?}", options.output);
+
+    Ok(())
+}
+
+fn get_top_block(column: &[GeneratedChunk], position: IVec2) -> BlockType {
+    for chunk in column.iter().rev() {
+        for y in (1..=chunk_size() as i32).rev() {
+            let block = chunk
+                .voxel_data
+                .get_block(IVec3::new(position.x, y, position.y));
+            if block != BlockType::AIR {
+                return block;
+            }
+        }
+    }
+
+    BlockType::AIR
+}
+
+fn get_chunk_offset(chunk: &GeneratedChunk) -> Vec3 {
+    Vec3::new(
+        chunk.chunk_pos.x as f32 * chunk_size() as f32 * VOXEL_SIZE,
+        0.,
+        chunk.chunk_pos.z as f32 * chunk_size() as f32 * VOXEL_SIZE,
+    )
+}
+
+/// Materials of the obj files, one for every texture layer.
+fn write_mtl(path: &Path, block_registry: &BlockRegistry) -> std::io::Result<()> {
+    let asset_directory = std::env::current_dir()?.join("assets");
+    let mut mtl = String::new();
+
+    for (texture_id, texture_path) in block_registry.get_texture_paths().iter().enumerate() {
+        let _ = writeln!(mtl, "newmtl texture_{texture_id}");
+        let _ = writeln!(
+            mtl,
+            "map_Kd {}",
+            asset_directory.join(texture_path).display()
+        );
+    }
+
+    fs::write(path, mtl)
+}
+
+fn write_obj(
+    path: &Path,
+    column: &[GeneratedChunk],
+    block_registry: &BlockRegistry,
+) -> std::io::Result<()> {
+    let mut obj = String::new();
+    let mut index_offset = 1;
+
+    let _ = writeln!(obj, "mtllib {MTL_FILE_NAME}");
+
+    for chunk in column {
+        let ChunkMeshes { solid, transparent } = chunk.generate_mesh(block_registry);
+        for (name, mesh) in [("solid", solid), ("transparent", transparent)] {
+            let Some(mesh) = mesh else {
+                continue;
+            };
+            let offset = get_chunk_offset(chunk);
+
+            let _ = writeln!(obj, "o chunk_{}_{name}", chunk.chunk_pos.y);
+            for position in &mesh.positions {
+                let position = Vec3::from_array(*position) + offset;
+                let _ = writeln!(obj, "v {} {} {}", position.x, position.y, position.z);
+            }
+            for normal in &mesh.normals {
+                let _ = writeln!(obj, "vn {} {} {}", normal[0], normal[1], normal[2]);
+            }
+            for uv in &mesh.uvs {
+                let _ = writeln!(obj, "vt {} {}", uv[0], uv[1]);
+            }
+
+            // Obj has no per vertex texture layers, so each layer becomes its own material.
+            let mut current_texture = None;
+            for triangle in mesh.triangles() {
+                let texture_id = mesh.texture_ids[triangle[0] as usize];
+                if current_texture != Some(texture_id) {
+                    let _ = writeln!(obj, "usemtl texture_{texture_id}");
+                    current_texture = Some(texture_id);
+                }
+
+                let [a, b, c] = triangle.map(|index| index + index_offset);
+                let _ = writeln!(obj, "f {a}/{a}/{a} {b}/{b}/{b} {c}/{c}/{c}");
+            }
+
+            index_offset += mesh.positions.len() as u32;
+        }
+    }
+
+    fs::write(path, obj)
+}
+
+fn write_gltf(
+    directory: &Path,
+    file_name: &str,
+    column: &[GeneratedChunk],
+    block_registry: &BlockRegistry,
+) -> std::io::Result<()> {
+    let mut buffer: Vec<u8> = Vec::new();
+    let mut buffer_views = Vec::new();
+    let mut accessors = Vec::new();
+    let mut meshes = Vec::new();
+    let mut nodes = Vec::new();
+
+    let mut push_view = |buffer: &mut Vec<u8>, data: &[u8], target: u32| {
+        let view = format!(
+            r#"{{"buffer":0,"byteOffset":{},"byteLength":{},"target":{target}}}"#,
+            buffer.len(),
+            data.len()
+        );
+        buffer.extend_from_slice(data);
+        buffer_views.push(view);
+        buffer_views.len() - 1
+    };
+
+    for chunk in column {
+        let ChunkMeshes { solid, transparent } = chunk.generate_mesh(block_registry);
+        for (name, mesh) in [("solid", solid), ("transparent", transparent)] {
+            let Some(mesh) = mesh else {
+                continue;
+            };
+            let ChunkMeshBuffers {
+                positions,
+                normals,
+                uvs,
+                indices,
+                ..
+            } = mesh;
+
+            let (min, max) = positions.iter().fold(
+                (Vec3::splat(f32::MAX), Vec3::splat(f32::MIN)),
+                |(min, max), position| {
+                    let position = Vec3::from_array(*position);
+                    (min.min(position), max.max(position))
+                },
+            );
+
+            let position_view = push_view(&mut buffer, &to_bytes(positions.as_flattened()), 34962);
+            let normal_view = push_view(&mut buffer, &to_bytes(normals.as_flattened()), 34962);
+            let uv_view = push_view(&mut buffer, &to_bytes(uvs.as_flattened()), 34962);
+            let index_bytes: Vec<u8> = indices
+                .iter()
+                .flat_map(|index| index.to_le_bytes())
+                .collect();
+            let index_view = push_view(&mut buffer, &index_bytes, 34963);
+
+            let first_accessor = accessors.len();
+            accessors.push(format!(
+                r#"{{"bufferView":{position_view},"componentType":5126,"count":{},"type":"VEC3","min":[{},{},{}],"max":[{},{},{}]}}"#,
+                positions.len(),
+                min.x,
+                min.y,
+                min.z,
+                max.x,
+                max.y,
+                max.z
+            ));
+            accessors.push(format!(
+                r#"{{"bufferView":{normal_view},"componentType":5126,"count":{},"type":"VEC3"}}"#,
+                normals.len()
+            ));
+            accessors.push(format!(
+                r#"{{"bufferView":{uv_view},"componentType":5126,"count":{},"type":"VEC2"}}"#,
+                uvs.len()
+            ));
+            accessors.push(format!(
+                r#"{{"bufferView":{index_view},"componentType":5125,"count":{},"type":"SCALAR"}}"#,
+                indices.len()
+            ));
+
+            meshes.push(format!(
+                r#"{{"primitives":[{{"attributes":{{"POSITION":{},"NORMAL":{},"TEXCOORD_0":{}}},"indices":{}}}]}}"#,
+                first_accessor,
+                first_accessor + 1,
+                first_accessor + 2,
+                first_accessor + 3
+            ));
+
+            let offset = get_chunk_offset(chunk);
+            nodes.push(format!(
+                r#"{{"name":"chunk_{}_{name}","mesh":{},"translation":[{},{},{}]}}"#,
+                chunk.chunk_pos.y,
+                meshes.len() - 1,
+                offset.x,
+                offset.y,
+                offset.z
+            ));
+        }
+    }
+
+    if nodes.is_empty() {
+        return Ok(());
+    }
+
+    let bin_name = format!("{file_name}.bin");
+    let scene_nodes = (0..nodes.len())
+        .map(|node| node.to_string())
+        .collect::<Vec<_>>()
+        .join(",");
+    let gltf = format!(
+        r#"{{"asset":{{"version":"2.0","generator":"opentale export"}},"scene":0,"scenes":[{{"nodes":[{scene_nodes}]}}],"nodes":[{}],"meshes":[{}],"accessors":[{}],"bufferViews":[{}],"buffers":[{{"uri":"{bin_name}","byteLength":{}}}]}}"#,
+        nodes.join(","),
+        meshes.join(","),
+        accessors.join(","),
+        buffer_views.join(","),
+        buffer.len()
+    );
+
+    fs::write(directory.join(bin_name), buffer)?;
+    fs::write(directory.join(format!("{file_name}.gltf")), gltf)
+}
+
+fn to_bytes(values: &[f32]) -> Vec<u8> {
+    values
+        .iter()
+        .flat_map(|value| value.to_le_bytes())
+        .collect()
+}
@@ -21,11 +21,14 @@ use opentale::world_generation::chunk_generation::structure_generator::{
     StructureGenerator, VoxelStructureMetadata,
 };
 use opentale::world_generation::chunk_generation::voxel_types::VoxelData;
-use opentale::world_generation::chunk_generation::{BlockType, CHUNK_SIZE, VOXEL_SIZE};
+use opentale::world_generation::chunk_generation::{chunk_size, BlockType, VOXEL_SIZE};
+use opentale::world_generation::chunk_settings::load_chunk_settings;
 use opentale::world_generation::voxel_world::ChunkLod;
 use std::f32::consts::PI;
 
 fn main() {
+    load_chunk_settings();
+
     App::new()
         .add_plugins((
             DefaultPlugins.set(WindowPlugin {
@@ -120,7 +123,7 @@ fn spawn_mesh(
         };
 
         commands.spawn((
-            Transform::from_translation(chunk_pos.as_vec3() * CHUNK_SIZE as f32 * VOXEL_SIZE),
+            Transform::from_translation(chunk_pos.as_vec3() * chunk_size() as f32 * VOXEL_SIZE),
             Name::new("Chunk"),
             Mesh3d(meshes.add(mesh.0)),
             MeshMaterial3d(materials.add(ExtendedMaterial {
@@ -130,7 +133,7 @@ fn spawn_mesh(
                     palette: chunk.palette,
                     chunk_pos: chunk_pos,
                     chunk_lod: ChunkLod::Full.multiplier_i32(),
-                    min_chunk_height: chunk_pos.y * CHUNK_SIZE as i32,
+                    min_chunk_height: chunk_pos.y * chunk_size() as i32,
                 },
             })),
             TreeGen,
@@ -180,6 +183,7 @@ fn get_tree_voxel_data() -> Vec<(Box<VoxelData>, IVec3)> {
 
     let tree_generator = PineStructureGenerator {
         fixed_structure_metadata: VoxelStructureMetadata {
+            name: "pine".into(),
             debug_rgb_multiplier: [0., 0., 0.],
             generate_debug_blocks: false,
             generation_size: [0, 0],
@@ -203,11 +207,11 @@ fn apply_trees(
     chunk_position: IVec3,
     tree_model: &Vec<Vec<Vec<BlockType>>>,
 ) {
-    let chunk_x = chunk_position.x * CHUNK_SIZE as i32;
-    let chunk_y = chunk_position.y * CHUNK_SIZE as i32;
-    let chunk_z = chunk_position.z * CHUNK_SIZE as i32;
+    let chunk_x = chunk_position.x * chunk_size() as i32;
+    let chunk_y = chunk_position.y * chunk_size() as i32;
+    let chunk_z = chunk_position.z * chunk_size() as i32;
 
-    for x in chunk_x - 1..chunk_x + CHUNK_SIZE as i32 + 1 {
+    for x in chunk_x - 1..chunk_x + chunk_size() as i32 + 1 {
         if x < 0 {
             continue;
         }
@@ -219,7 +223,7 @@ fn apply_trees(
         }
 
         let tree_model_x = &tree_model[x];
-        for y in chunk_y - 1..chunk_y + CHUNK_SIZE as i32 + 1 {
+        for y in chunk_y - 1..chunk_y + chunk_size() as i32 + 1 {
             if y < 0 {
                 continue;
             }
@@ -231,7 +235,7 @@ fn apply_trees(
             }
 
             let tree_model_y = &tree_model_x[y];
-            for z in chunk_z - 1..chunk_z + CHUNK_SIZE as i32 + 1 {
+            for z in chunk_z - 1..chunk_z + chunk_size() as i32 + 1 {
                 if z < 0 {
                     continue;
                 }
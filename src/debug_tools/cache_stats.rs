@@ -0,0 +1,101 @@
+use crate::debug_tools::debug_resource::SpellhavenDebug;
+use crate::world_generation::generation_options::{
+    GenerationCache, GenerationCacheItem, GenerationOptionsResource,
+};
+use bevy::app::App;
+use bevy::math::IVec2;
+use bevy::prelude::{Plugin, Res, ResMut, Update};
+use bevy_inspector_egui::bevy_egui::EguiContexts;
+use bevy_inspector_egui::egui;
+
+const MEBIBYTE: usize = 1 << 20;
+
+pub struct CacheStatsPlugin;
+
+impl Plugin for CacheStatsPlugin {
+    fn build(&self, app: &mut App) {
+        app.add_systems(Update, show_cache_stats);
+    }
+}
+
+fn show_cache_stats(
+    debug: Res<SpellhavenDebug>,
+    mut contexts: EguiContexts,
+    mut generation_options: ResMut<GenerationOptionsResource>,
+) {
+    if !debug.show_cache_stats {
+        return;
+    }
+
+    let generation_options = &mut *generation_options;
+    let options = &generation_options.0;
+    let country_caches = &mut generation_options.1;
+
+    egui::Window::new("Generation caches").show(contexts.ctx_mut(), |ui| {
+        egui::Grid::new("generation_caches")
+            .num_columns(4)
+            .striped(true)
+            .show(ui, |ui| {
+                ui.label("Cache");
+                ui.label("Entries");
+                ui.label("Memory");
+                ui.label("Budget");
+                ui.end_row();
+
+                cache_row(ui, "Paths", &options.path_cache);
+                cache_row(ui, "Structures", &options.structure_cache);
+                cache_row(ui, "Water", &options.water_cache);
+
+                ui.label("Countries");
+                ui.label(country_caches.len().to_string());
+                ui.label(format_memory(country_caches.memory()));
+                let mut max_memory = country_caches.max_memory / MEBIBYTE;
+                if ui
+                    .add(
+                        egui::DragValue::new(&mut max_memory)
+                            .range(1..=16384)
+                            .suffix(" MiB"),
+                    )
+                    .changed()
+                {
+                    country_caches.max_memory = max_memory * MEBIBYTE;
+                    country_caches.evict();
+                }
+                ui.end_row();
+            });
+
+        ui.label(
+            "Countries count the paths and structures they hold, which are counted by the path \
+             and structure caches as well until those evict them.",
+        );
+    });
+}
+
+fn cache_row<T: GenerationCacheItem<IVec2>>(
+    ui: &mut egui::Ui,
+    name: &str,
+    cache: &GenerationCache<IVec2, T>,
+) {
+    let stats = cache.stats();
+
+    ui.label(name);
+    ui.label(stats.entries.to_string());
+    ui.label(format_memory(stats.memory));
+
+    let mut max_memory = stats.max_memory / MEBIBYTE;
+    if ui
+        .add(
+            egui::DragValue::new(&mut max_memory)
+                .range(1..=16384)
+                .suffix(" MiB"),
+        )
+        .changed()
+    {
+        cache.set_max_memory(max_memory * MEBIBYTE);
+    }
+    ui.end_row();
+}
+
+fn format_memory(bytes: usize) -> String {
+    format!("{:.2} MiB", bytes as f64 / MEBIBYTE as f64)
+}
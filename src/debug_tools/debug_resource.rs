@@ -1,3 +1,5 @@
+use crate::debug_tools::cache_stats::CacheStatsPlugin;
+use crate::debug_tools::terrain_editor::TerrainEditorPlugin;
 use bevy::app::App;
 use bevy::prelude::{Plugin, Reflect, ReflectResource, Resource};
 use bevy_inspector_egui::quick::ResourceInspectorPlugin;
@@ -8,7 +10,11 @@ impl Plugin for SpellhavenDebugPlugin {
     fn build(&self, app: &mut App) {
         app.init_resource::<SpellhavenDebug>()
             .register_type::<SpellhavenDebug>()
-            .add_plugins(ResourceInspectorPlugin::<SpellhavenDebug>::default());
+            .add_plugins((
+                ResourceInspectorPlugin::<SpellhavenDebug>::default(),
+                TerrainEditorPlugin,
+                CacheStatsPlugin,
+            ));
     }
 }
 
@@ -19,6 +25,10 @@ pub struct SpellhavenDebug {
     pub show_path_debug: bool,
     pub path_circle_radius: f32,
     pub path_show_range: i32,
+    /// Shows a panel to tune the terrain and regenerate the world with it.
+    pub show_terrain_editor: bool,
+    /// Shows the entries and memory of the generation caches and lets their budgets be changed.
+    pub show_cache_stats: bool,
 }
 
 impl Default for SpellhavenDebug {
@@ -28,6 +38,8 @@ impl Default for SpellhavenDebug {
             show_path_debug: false,
             path_circle_radius: 1.,
             path_show_range: 500,
+            show_terrain_editor: false,
+            show_cache_stats: false,
         }
     }
 }
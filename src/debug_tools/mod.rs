@@ -1 +1,3 @@
+pub mod cache_stats;
 pub mod debug_resource;
+pub mod terrain_editor;
@@ -0,0 +1,371 @@
+use crate::debug_tools::debug_resource::SpellhavenDebug;
+use crate::world_generation::chunk_generation::biome_registry::BiomeRegistry;
+use crate::world_generation::chunk_generation::noise::noise_graph::{
+    NoiseGraphRegistry, NoiseNode,
+};
+use crate::world_generation::chunk_loading::chunk_loader::RegenerateWorld;
+use crate::world_generation::generation_options::{
+    GenerationOptions, GenerationOptionsResource, TerrainSettings,
+};
+use bevy::app::App;
+use bevy::log::error;
+use bevy::prelude::{EventWriter, Plugin, Res, ResMut, Resource, Update};
+use bevy_inspector_egui::bevy_egui::EguiContexts;
+use bevy_inspector_egui::egui;
+use bevy_inspector_egui::egui::emath::Numeric;
+use std::ops::RangeInclusive;
+use std::sync::{Arc, Weak};
+
+/// More octaves than this add detail far below the size of a voxel.
+const MAX_OCTAVES: usize = 16;
+
+pub struct TerrainEditorPlugin;
+
+impl Plugin for TerrainEditorPlugin {
+    fn build(&self, app: &mut App) {
+        app.init_resource::<TerrainEditor>()
+            .add_systems(Update, show_terrain_editor);
+    }
+}
+
+/// Copies of the tunable parts of the generation options, which the terrain editor changes until
+/// they are applied by regenerating the world.
+#[derive(Resource)]
+struct TerrainEditor {
+    /// Options the copies were taken from, to notice when the world is loaded or the noise graphs
+    /// are reloaded.
+    source: Weak<GenerationOptions>,
+    draft: Option<TerrainDraft>,
+    regenerate_on_change: bool,
+    changed: bool,
+}
+
+impl Default for TerrainEditor {
+    fn default() -> Self {
+        Self {
+            source: Weak::new(),
+            draft: None,
+            regenerate_on_change: true,
+            changed: false,
+        }
+    }
+}
+
+struct TerrainDraft {
+    noise_graphs: NoiseGraphRegistry,
+    biome_registry: BiomeRegistry,
+    terrain_settings: TerrainSettings,
+    generate_density: bool,
+}
+
+impl TerrainDraft {
+    fn new(generation_options: &GenerationOptions) -> Self {
+        Self {
+            noise_graphs: (*generation_options.noise_graphs).clone(),
+            biome_registry: (*generation_options.biome_registry).clone(),
+            terrain_settings: generation_options.terrain_settings.clone(),
+            generate_density: generation_options.generate_density,
+        }
+    }
+}
+
+fn show_terrain_editor(
+    debug: Res<SpellhavenDebug>,
+    mut contexts: EguiContexts,
+    mut editor: ResMut<TerrainEditor>,
+    mut generation_options: ResMut<GenerationOptionsResource>,
+    mut regenerate_world: EventWriter<RegenerateWorld>,
+) {
+    if !debug.show_terrain_editor {
+        return;
+    }
+
+    let editor = &mut *editor;
+
+    if !editor.source.ptr_eq(&Arc::downgrade(&generation_options.0)) || editor.draft.is_none() {
+        editor.source = Arc::downgrade(&generation_options.0);
+        editor.draft = Some(TerrainDraft::new(&generation_options.0));
+        editor.changed = false;
+    }
+
+    let ctx = contexts.ctx_mut();
+    let mut regenerate = false;
+    let mut reset = false;
+
+    egui::Window::new("Terrain").show(ctx, |ui| {
+        let draft = editor.draft.as_mut().unwrap();
+
+        egui::ScrollArea::vertical()
+            .max_height(600.)
+            .show(ui, |ui| {
+                ui.collapsing("Settings", |ui| {
+                    editor.changed |= ui
+                        .checkbox(&mut draft.generate_density, "Caves and overhangs")
+                        .changed();
+                    editor.changed |= terrain_settings_ui(ui, &mut draft.terrain_settings);
+                });
+                ui.collapsing("Biomes", |ui| {
+                    editor.changed |= biomes_ui(ui, &mut draft.biome_registry);
+                });
+                ui.collapsing("Noise graphs", |ui| {
+                    editor.changed |= noise_graphs_ui(ui, &mut draft.noise_graphs);
+                });
+            });
+
+        ui.separator();
+        ui.checkbox(&mut editor.regenerate_on_change, "Regenerate on change");
+        ui.horizontal(|ui| {
+            regenerate |= ui.button("Regenerate").clicked();
+            reset = ui.button("Reset").clicked();
+
+            if ui.button("Copy noise graphs").clicked() {
+                match draft.noise_graphs.to_ron() {
+                    Ok(noise_graphs) => ui.ctx().copy_text(noise_graphs),
+                    Err(error) => error!("Failed to write noise graphs: {error}"),
+                }
+            }
+        });
+    });
+
+    if reset {
+        editor.draft = Some(TerrainDraft::new(&generation_options.0));
+        editor.changed = false;
+        return;
+    }
+
+    // Waits for the pointer to be released, so dragging a value doesn't regenerate every frame.
+    regenerate |= editor.changed
+        && editor.regenerate_on_change
+        && !ctx.input(|input| input.pointer.any_down());
+
+    if !regenerate {
+        return;
+    }
+
+    let draft = editor.draft.as_ref().unwrap();
+    let mut retuned_options = generation_options.0.retune(
+        Arc::new(draft.noise_graphs.clone()),
+        Arc::new(draft.biome_registry.clone()),
+        draft.terrain_settings.clone(),
+    );
+    retuned_options.generate_density = draft.generate_density;
+    generation_options.0 = Arc::new(retuned_options);
+    editor.source = Arc::downgrade(&generation_options.0);
+    editor.changed = false;
+    regenerate_world.write(RegenerateWorld);
+}
+
+fn terrain_settings_ui(ui: &mut egui::Ui, terrain_settings: &mut TerrainSettings) -> bool {
+    let mut changed = false;
+    changed |= ranged_value_ui(
+        ui,
+        "Snow height",
+        &mut terrain_settings.snow_height,
+        positive(),
+    );
+    changed |= ranged_value_ui(
+        ui,
+        "Path width",
+        &mut terrain_settings.path_width,
+        0.0..=f32::MAX,
+    );
+    changed |= ranged_value_ui(
+        ui,
+        "Path blend distance",
+        &mut terrain_settings.path_blend_distance,
+        positive(),
+    );
+    changed |= ranged_value_ui(
+        ui,
+        "Structure max steepness",
+        &mut terrain_settings.structure_max_steepness,
+        0.0..=f64::MAX,
+    );
+    changed |= value_ui(
+        ui,
+        "Overhang steepness",
+        &mut terrain_settings.overhang_steepness,
+    );
+    changed
+}
+
+fn biomes_ui(ui: &mut egui::Ui, biome_registry: &mut BiomeRegistry) -> bool {
+    let mut changed = false;
+
+    for biome in biome_registry.iter_mut() {
+        egui::CollapsingHeader::new(biome.name.as_str()).show(ui, |ui| {
+            changed |= value_ui(ui, "Temperature", &mut biome.temperature);
+            changed |= value_ui(ui, "Moisture", &mut biome.moisture);
+            changed |= value_ui(ui, "Subsurface depth", &mut biome.subsurface_depth);
+            changed |= ranged_value_ui(
+                ui,
+                "Max surface steepness",
+                &mut biome.max_surface_steepness,
+                0.0..=f64::MAX,
+            );
+            changed |= value_ui(ui, "Height multiplier", &mut biome.height_multiplier);
+            changed |= value_ui(ui, "Height offset", &mut biome.height_offset);
+        });
+    }
+
+    changed
+}
+
+fn noise_graphs_ui(ui: &mut egui::Ui, noise_graphs: &mut NoiseGraphRegistry) -> bool {
+    let mut changed = false;
+
+    for (name, graph) in noise_graphs.iter_mut() {
+        egui::CollapsingHeader::new(name.as_str()).show(ui, |ui| {
+            changed |= value_ui(ui, "Seed offset", &mut graph.seed_offset);
+            changed |= noise_node_ui(ui, &mut graph.root, egui::Id::new(name));
+        });
+    }
+
+    changed
+}
+
+/// Shows the values of a node and its sources. The structure of the graph stays as it is, that is
+/// what the noise graph file is for.
+fn noise_node_ui(ui: &mut egui::Ui, node: &mut NoiseNode, id: egui::Id) -> bool {
+    let mut changed = false;
+
+    match node {
+        NoiseNode::Constant(value) => changed |= value_ui(ui, "Constant", value),
+        NoiseNode::Simplex => {
+            ui.label("Simplex");
+        }
+        NoiseNode::Graph(name) => {
+            ui.label(format!("Graph {name:?}"));
+        }
+        NoiseNode::Roughness {
+            frequency,
+            amplitude,
+        } => {
+            node_header(ui, "Roughness", id, |ui| {
+                changed |= ranged_value_ui(ui, "Frequency", frequency, positive());
+                changed |= value_ui(ui, "Amplitude", amplitude);
+            });
+        }
+        NoiseNode::Cellular {
+            frequency, jitter, ..
+        } => {
+            node_header(ui, "Cellular", id, |ui| {
+                changed |= ranged_value_ui(ui, "Frequency", frequency, positive());
+                changed |= ranged_value_ui(ui, "Jitter", jitter, 0.0..=f32::MAX);
+            });
+        }
+        NoiseNode::Add(a, b) => changed |= sources_ui(ui, "Add", a, b, id),
+        NoiseNode::Multiply(a, b) => changed |= sources_ui(ui, "Multiply", a, b, id),
+        NoiseNode::Min(a, b) => changed |= sources_ui(ui, "Min", a, b, id),
+        NoiseNode::Max(a, b) => changed |= sources_ui(ui, "Max", a, b, id),
+        NoiseNode::Scale { source, scale } => {
+            node_header(ui, "Scale", id, |ui| {
+                changed |= value_ui(ui, "Scale", scale);
+                changed |= noise_node_ui(ui, source, id.with(0));
+            });
+        }
+        NoiseNode::ShiftNScale {
+            source,
+            shift,
+            scale,
+        } => {
+            node_header(ui, "ShiftNScale", id, |ui| {
+                changed |= value_ui(ui, "Shift", shift);
+                changed |= ranged_value_ui(ui, "Scale", scale, positive());
+                changed |= noise_node_ui(ui, source, id.with(0));
+            });
+        }
+        NoiseNode::SmoothStep {
+            source,
+            steps,
+            smoothness,
+        } => {
+            node_header(ui, "SmoothStep", id, |ui| {
+                changed |= ranged_value_ui(ui, "Steps", steps, positive());
+                changed |= ranged_value_ui(ui, "Smoothness", smoothness, positive());
+                changed |= noise_node_ui(ui, source, id.with(0));
+            });
+        }
+        NoiseNode::Gft {
+            source,
+            octaves,
+            frequency,
+            lacunarity,
+            persistence,
+            gradient,
+            amplitude,
+        } => {
+            node_header(ui, "Gft", id, |ui| {
+                changed |= ranged_value_ui(ui, "Octaves", octaves, 1..=MAX_OCTAVES);
+                changed |= ranged_value_ui(ui, "Frequency", frequency, positive());
+                changed |= ranged_value_ui(ui, "Lacunarity", lacunarity, positive());
+                changed |= ranged_value_ui(ui, "Persistence", persistence, positive());
+                changed |= value_ui(ui, "Gradient", gradient);
+                changed |= value_ui(ui, "Amplitude", amplitude);
+                changed |= noise_node_ui(ui, source, id.with(0));
+            });
+        }
+    }
+
+    changed
+}
+
+fn sources_ui(
+    ui: &mut egui::Ui,
+    label: &str,
+    a: &mut NoiseNode,
+    b: &mut NoiseNode,
+    id: egui::Id,
+) -> bool {
+    let mut changed = false;
+    node_header(ui, label, id, |ui| {
+        changed |= noise_node_ui(ui, a, id.with(0));
+        changed |= noise_node_ui(ui, b, id.with(1));
+    });
+    changed
+}
+
+fn node_header(
+    ui: &mut egui::Ui,
+    label: &str,
+    id: egui::Id,
+    add_contents: impl FnOnce(&mut egui::Ui),
+) {
+    egui::CollapsingHeader::new(label)
+        .id_salt(id)
+        .default_open(true)
+        .show(ui, add_contents);
+}
+
+/// Drag value for values the generation accepts any number for.
+fn value_ui<T: Numeric>(ui: &mut egui::Ui, label: &str, value: &mut T) -> bool {
+    ranged_value_ui(ui, label, value, T::MIN..=T::MAX)
+}
+
+/// Drag value whose speed follows the size of the value, as the frequencies of the noise graphs
+/// are a few magnitudes smaller than their amplitudes. Limited to `range`, as values like a zero
+/// that ends up in a division fill the terrain with NaN heights.
+fn ranged_value_ui<T: Numeric>(
+    ui: &mut egui::Ui,
+    label: &str,
+    value: &mut T,
+    range: RangeInclusive<T>,
+) -> bool {
+    let speed = if T::INTEGRAL {
+        0.1
+    } else {
+        (value.to_f64().abs() * 0.01).max(1e-6)
+    };
+
+    ui.horizontal(|ui| {
+        ui.label(label);
+        ui.add(egui::DragValue::new(value).speed(speed).range(range))
+            .changed()
+    })
+    .inner
+}
+
+/// Smallest value above zero the drag values allow.
+fn positive<T: Numeric>() -> RangeInclusive<T> {
+    T::from_f64(1e-6)..=T::MAX
+}
@@ -2,8 +2,8 @@ use crate::debug_tools::debug_resource::SpellhavenDebug;
 use crate::ui::ui::UiSpawnCallback;
 use crate::world_generation::chunk_generation::VOXEL_SIZE;
 use crate::world_generation::chunk_loading::chunk_loader::ChunkLoader;
+use crate::world_generation::world_save::WorldSave;
 use bevy::core_pipeline::experimental::taa::TemporalAntiAliasPlugin;
-use bevy::pbr::ScreenSpaceAmbientOcclusion;
 use bevy::prelude::*;
 use bevy::render::camera::Exposure;
 use bevy::{core_pipeline::experimental::taa::TemporalAntiAliasing, ecs::system::SystemId};
@@ -38,7 +38,7 @@ pub struct Player {
 struct PlayerBody;
 
 #[derive(Component)]
-struct PlayerCamera;
+pub struct PlayerCamera;
 
 #[derive(Resource)]
 pub struct PlayerSpawnCallback(pub SystemId);
@@ -54,11 +54,16 @@ fn spawn_player(
     mut materials: ResMut<Assets<StandardMaterial>>,
     asset_server: Res<AssetServer>,
     ui_spawn_callback: Res<UiSpawnCallback>,
+    world_save: Option<Res<WorldSave>>,
 ) {
+    let spawn_position = world_save
+        .and_then(|world_save| world_save.player_position)
+        .unwrap_or(Vec3::new(0., 2200., 0.));
+
     // Player
     commands.spawn((
         RigidBody::KinematicPositionBased,
-        Transform::from_xyz(0., 2200., 0.),
+        Transform::from_translation(spawn_position),
         Collider::cuboid(0.4, 0.9, 0.4),
         KinematicCharacterController {
             offset: CharacterLength::Absolute(0.01),
@@ -92,7 +97,6 @@ fn spawn_player(
         AtmosphereCamera::default(),
         PlayerCamera,
         Name::new("PlayerCamera"),
-        ScreenSpaceAmbientOcclusion::default(),
     ));
 
     commands
@@ -32,6 +32,7 @@ pub fn update_triangle_ui(
             "Triangles: {}, Total: {}",
             triangle_count
                 .0
+                .iter()
                 .map(|x| x
                     .to_string()
                     .as_bytes()
@@ -41,6 +42,7 @@ pub fn update_triangle_ui(
                     .collect::<Result<Vec<&str>, _>>()
                     .unwrap()
                     .join("'"))
+                .collect::<Vec<_>>()
                 .join(", "),
             triangle_count.0.iter().sum::<u64>()
         );
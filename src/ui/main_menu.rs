@@ -1,10 +1,10 @@
-use crate::player::PlayerSpawnCallback;
-use crate::world_generation::generation_options::GenerationOptionsResource;
+use crate::world_generation::world_save::{WorldSave, SAVE_DIRECTORY};
 use bevy::app::App;
 use bevy::prelude::{info, Commands, Plugin, Res, ResMut, Resource, Update};
 use bevy_inspector_egui::bevy_egui::EguiContexts;
 use bevy_inspector_egui::egui;
 use std::hash::{DefaultHasher, Hash, Hasher};
+use std::path::Path;
 
 #[derive(Default)]
 pub struct MainMenuPlugin {}
@@ -20,6 +20,7 @@ impl Plugin for MainMenuPlugin {
 struct MainMenuState {
     state: MainMenuStates,
     seed: String,
+    world_name: String,
 }
 
 impl Default for MainMenuState {
@@ -27,6 +28,7 @@ impl Default for MainMenuState {
         Self {
             state: MainMenuStates::Shown,
             seed: "Seed".into(),
+            world_name: "World".into(),
         }
     }
 }
@@ -47,11 +49,15 @@ impl MainMenuState {
 
 fn spawn_main_menu(
     mut menu_state: ResMut<MainMenuState>,
-    mut gen_options: ResMut<GenerationOptionsResource>,
-    player_spawn_callback: Res<PlayerSpawnCallback>,
     mut contexts: EguiContexts,
     mut commands: Commands,
+    world_save: Option<Res<WorldSave>>,
 ) {
+    // Shown again when the world save was refused.
+    if world_save.is_none() {
+        menu_state.state = MainMenuStates::Shown;
+    }
+
     if !menu_state.show_menu() {
         return;
     }
@@ -62,6 +68,7 @@ fn spawn_main_menu(
         ui.vertical_centered(|ui| {
             ui.heading("Opentale Pre-Alpha");
 
+            ui.text_edit_singleline(&mut menu_state.world_name);
             ui.text_edit_singleline(&mut menu_state.seed);
             if ui.button("Start").clicked() {
                 let mut hasher = DefaultHasher::new();
@@ -69,10 +76,12 @@ fn spawn_main_menu(
                 let seed = hasher.finish();
 
                 info!("Seed to use: {}", seed);
-                *gen_options = GenerationOptionsResource::from_seed(seed);
+                commands.insert_resource(WorldSave::new(
+                    Path::new(SAVE_DIRECTORY).join(&menu_state.world_name),
+                    seed,
+                ));
 
                 menu_state.state = MainMenuStates::Hidden;
-                let _ = commands.run_system(player_spawn_callback.0);
             }
         });
     });
@@ -1,17 +1,24 @@
 pub mod array_texture;
+pub mod block_editing;
 pub mod chunk_generation;
 pub mod chunk_loading;
+pub mod chunk_settings;
 pub mod foliage_generation;
 pub mod generation_assets;
 pub mod generation_options;
 pub mod texture_loading;
 pub mod voxel_world;
+pub mod world_generator;
+pub mod world_save;
 
+use crate::world_generation::block_editing::BlockEditingPlugin;
 use crate::world_generation::chunk_generation::ChunkGenerationPlugin;
+use crate::world_generation::chunk_settings::load_chunk_settings;
 use crate::world_generation::generation_assets::{
     load_generation_assets, setup_array_texture, GenerationAssetState,
 };
 use crate::world_generation::texture_loading::texture_loading;
+use crate::world_generation::world_save::WorldSavePlugin;
 use bevy::app::{App, Startup, Update};
 use bevy::ecs::schedule::IntoScheduleConfigs;
 use bevy::prelude::Plugin;
@@ -23,6 +30,9 @@ pub struct WorldGenerationPlugin;
 
 impl Plugin for WorldGenerationPlugin {
     fn build(&self, app: &mut App) {
+        // Before anything uses the chunk size or the max lod.
+        load_chunk_settings();
+
         app.init_state::<GenerationAssetState>()
             .add_systems(
                 OnEnter(GenerationAssetState::Unloaded),
@@ -33,6 +43,6 @@ impl Plugin for WorldGenerationPlugin {
                 setup_array_texture.run_if(in_state(GenerationAssetState::Loading)),
             )
             .add_systems(Startup, texture_loading)
-            .add_plugins(ChunkGenerationPlugin);
+            .add_plugins((ChunkGenerationPlugin, BlockEditingPlugin, WorldSavePlugin));
     }
 }
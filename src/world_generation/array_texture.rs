@@ -13,6 +13,14 @@ use bevy::{
 pub const ATTRIBUTE_TEXTURE_ID: MeshVertexAttribute =
     MeshVertexAttribute::new("TextureId", 988543481, VertexFormat::Uint32);
 
+/// Brightness of a vertex from 0 to 1, baked from the blocks around it.
+pub const ATTRIBUTE_AMBIENT_OCCLUSION: MeshVertexAttribute =
+    MeshVertexAttribute::new("AmbientOcclusion", 988543482, VertexFormat::Float32);
+
+/// Sky and block light of a vertex from 0 to 1.
+pub const ATTRIBUTE_LIGHT: MeshVertexAttribute =
+    MeshVertexAttribute::new("Light", 988543483, VertexFormat::Float32x2);
+
 #[derive(Asset, TypePath, AsBindGroup, Debug, Clone)]
 pub struct ArrayTextureMaterial {
     #[texture(100, dimension = "2d_array")]
@@ -35,20 +43,26 @@ impl MaterialExtension for ArrayTextureMaterial {
         layout: &MeshVertexBufferLayoutRef,
         _key: MaterialExtensionKey<Self>,
     ) -> Result<(), SpecializedMeshPipelineError> {
-        if let Some(index) = layout
-            .0
-            .attribute_ids()
-            .iter()
-            .position(|id| *id == ATTRIBUTE_TEXTURE_ID.id)
-        {
-            let layout_attribute = &layout.0.layout().attributes[index];
-            descriptor.vertex.buffers[0]
-                .attributes
-                .push(VertexAttribute {
-                    format: layout_attribute.format,
-                    offset: layout_attribute.offset,
-                    shader_location: 8,
-                });
+        for (attribute, shader_location) in [
+            (ATTRIBUTE_TEXTURE_ID, 8),
+            (ATTRIBUTE_AMBIENT_OCCLUSION, 9),
+            (ATTRIBUTE_LIGHT, 10),
+        ] {
+            if let Some(index) = layout
+                .0
+                .attribute_ids()
+                .iter()
+                .position(|id| *id == attribute.id)
+            {
+                let layout_attribute = &layout.0.layout().attributes[index];
+                descriptor.vertex.buffers[0]
+                    .attributes
+                    .push(VertexAttribute {
+                        format: layout_attribute.format,
+                        offset: layout_attribute.offset,
+                        shader_location,
+                    });
+            }
         }
         Ok(())
     }
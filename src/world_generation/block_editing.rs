@@ -0,0 +1,282 @@
+use crate::player::PlayerCamera;
+use crate::world_generation::chunk_generation::chunk_edits::BlockEdits;
+use crate::world_generation::chunk_generation::{
+    regenerate_chunk, remove_chunk_meshes, sync_chunk_light, BlockType, Chunk, ChunkLodInfo,
+    ChunkMeshTask, ChunkParent, ChunkRemesh, ChunkTaskGenerator, ChunkTransparentMesh, ChunkVoxels,
+    VOXEL_SIZE,
+};
+use crate::world_generation::generation_assets::GenerationAssets;
+use crate::world_generation::generation_options::GenerationOptionsResource;
+use crate::world_generation::voxel_world::QuadTreeVoxelWorld;
+use bevy::prelude::*;
+use std::collections::HashSet;
+
+pub struct BlockEditingPlugin;
+
+impl Plugin for BlockEditingPlugin {
+    fn build(&self, app: &mut App) {
+        app.insert_resource(BlockEditor::default())
+            .add_systems(Update, edit_blocks);
+    }
+}
+
+#[derive(Resource)]
+pub struct BlockEditor {
+    pub selected_block: BlockType,
+    pub reach: f32,
+    pub break_key: KeyCode,
+    pub place_key: KeyCode,
+}
+
+impl Default for BlockEditor {
+    fn default() -> Self {
+        Self {
+            selected_block: BlockType::STONE,
+            reach: 64. * VOXEL_SIZE,
+            break_key: KeyCode::KeyX,
+            place_key: KeyCode::KeyC,
+        }
+    }
+}
+
+pub struct VoxelRaycastHit {
+    pub position: IVec3,
+    pub normal: IVec3,
+    pub block: BlockType,
+}
+
+/// Walks the voxel grid along the ray and returns the first loaded voxel that isn't air.
+pub fn raycast_voxels(
+    origin: Vec3,
+    direction: Vec3,
+    max_distance: f32,
+    get_block: impl Fn(IVec3) -> Option<BlockType>,
+) -> Option<VoxelRaycastHit> {
+    let origin = origin / VOXEL_SIZE;
+    let direction = direction.normalize_or_zero();
+    let max_distance = max_distance / VOXEL_SIZE;
+
+    if direction == Vec3::ZERO {
+        return None;
+    }
+
+    let mut position = origin.floor().as_ivec3();
+    let step = direction.signum().as_ivec3();
+    let delta = (Vec3::ONE / direction).abs();
+    let mut distance_to_next = Vec3::new(
+        next_boundary_distance(origin.x, direction.x),
+        next_boundary_distance(origin.y, direction.y),
+        next_boundary_distance(origin.z, direction.z),
+    );
+    let mut normal = IVec3::ZERO;
+    let mut travelled = 0.;
+
+    while travelled <= max_distance {
+        if let Some(block) = get_block(position).filter(|block| *block != BlockType::AIR) {
+            return Some(VoxelRaycastHit {
+                position,
+                normal,
+                block,
+            });
+        }
+
+        if distance_to_next.x < distance_to_next.y && distance_to_next.x < distance_to_next.z {
+            travelled = distance_to_next.x;
+            distance_to_next.x += delta.x;
+            position.x += step.x;
+            normal = IVec3::new(-step.x, 0, 0);
+        } else if distance_to_next.y < distance_to_next.z {
+            travelled = distance_to_next.y;
+            distance_to_next.y += delta.y;
+            position.y += step.y;
+            normal = IVec3::new(0, -step.y, 0);
+        } else {
+            travelled = distance_to_next.z;
+            distance_to_next.z += delta.z;
+            position.z += step.z;
+            normal = IVec3::new(0, 0, -step.z);
+        }
+    }
+
+    None
+}
+
+fn next_boundary_distance(origin: f32, direction: f32) -> f32 {
+    if direction > 0. {
+        (origin.floor() + 1. - origin) / direction
+    } else if direction < 0. {
+        (origin - origin.floor()) / -direction
+    } else {
+        f32::INFINITY
+    }
+}
+
+fn edit_blocks(
+    mut commands: Commands,
+    keyboard_input: Res<ButtonInput<KeyCode>>,
+    block_editor: Res<BlockEditor>,
+    mut block_edits: ResMut<BlockEdits>,
+    mut voxel_world: ResMut<QuadTreeVoxelWorld>,
+    generation_options: Res<GenerationOptionsResource>,
+    generation_assets: Res<GenerationAssets>,
+    mut meshes: ResMut<Assets<Mesh>>,
+    camera: Query<&GlobalTransform, With<PlayerCamera>>,
+    chunk_owners: Query<(Entity, &ChunkParent)>,
+    generated_chunks: Query<(), With<Chunk>>,
+    mut chunks: Query<(
+        Entity,
+        &mut ChunkVoxels,
+        &ChunkLodInfo,
+        Option<&ChunkTransparentMesh>,
+    )>,
+) {
+    let breaking = keyboard_input.just_pressed(block_editor.break_key);
+    let placing = keyboard_input.just_pressed(block_editor.place_key);
+
+    if !breaking && !placing {
+        return;
+    }
+
+    let Ok(camera) = camera.single() else {
+        return;
+    };
+
+    let block_registry = &generation_options.0.block_registry;
+
+    let get_block = |position: IVec3| {
+        chunks
+            .iter()
+            .find(|(_, chunk_voxels, _, _)| chunk_voxels.contains(position))
+            .and_then(|(_, chunk_voxels, _, _)| chunk_voxels.get_block(position))
+    };
+
+    // Non solid blocks are looked through, like air.
+    let Some(hit) = raycast_voxels(
+        camera.translation(),
+        camera.forward().into(),
+        block_editor.reach,
+        |position| get_block(position).filter(|block| block_registry.get(*block).solid),
+    ) else {
+        return;
+    };
+
+    let (position, block) = if breaking {
+        (hit.position, BlockType::AIR)
+    } else {
+        let position = hit.position + hit.normal;
+        if get_block(position).is_some_and(|block| block_registry.get(block).solid) {
+            return;
+        }
+        (position, block_editor.selected_block)
+    };
+
+    let is_loaded = get_block(position).is_some();
+
+    block_edits.set_block(position, block);
+
+    if !is_loaded {
+        regenerate_column(
+            &mut commands,
+            &mut voxel_world,
+            &chunk_owners,
+            &generated_chunks,
+            position,
+        );
+    }
+
+    let mut edited_chunks = HashSet::new();
+
+    // The voxel can also sit in the padding of up to seven neighbouring chunks.
+    for (entity, mut chunk_voxels, _, _) in &mut chunks {
+        if chunk_voxels.set_block(position, block) {
+            chunk_voxels.relight_block(position, block_registry);
+            edited_chunks.insert(entity);
+        }
+    }
+
+    // Light changes can reach into chunks that don't contain the voxel at all.
+    let relit_chunks = sync_chunk_light(
+        &mut chunks,
+        &voxel_world,
+        edited_chunks.clone(),
+        block_registry,
+    );
+
+    for changed_chunk in edited_chunks.union(&relit_chunks) {
+        let Ok((entity, chunk_voxels, lod_info, transparent_mesh)) = chunks.get(*changed_chunk)
+        else {
+            continue;
+        };
+
+        // Edited chunks are meshed right away, a waiting remesh would bring back the old blocks.
+        let mut entity = commands.entity(entity);
+        entity.remove::<(ChunkRemesh, ChunkMeshTask)>();
+
+        match chunk_voxels.generate_task_data(
+            lod_info.neighbour_lods,
+            &generation_options.0.block_registry,
+        ) {
+            None => remove_chunk_meshes(&mut entity, transparent_mesh),
+            Some(chunk_task_data) => chunk_task_data.insert_into(
+                &mut entity,
+                transparent_mesh,
+                &mut meshes,
+                &generation_assets,
+            ),
+        }
+    }
+}
+
+/// Generates the column of the full lod chunk containing the world voxel position again, for an
+/// edit that no loaded chunk contains. Such voxels sit above the top chunk of the column, which
+/// stacks chunks up to the edit once it is generated again, or in chunks that were despawned for
+/// being empty, which are spawned again.
+fn regenerate_column(
+    commands: &mut Commands,
+    voxel_world: &mut QuadTreeVoxelWorld,
+    chunk_owners: &Query<(Entity, &ChunkParent)>,
+    generated_chunks: &Query<(), With<Chunk>>,
+    position: IVec3,
+) {
+    let parent_pos = BlockEdits::get_parent_position(position);
+    let Some((owner, _)) = chunk_owners
+        .iter()
+        .find(|(_, chunk_parent)| chunk_parent.0 == parent_pos)
+    else {
+        return;
+    };
+    let Some((lod, lod_position, chunks)) =
+        voxel_world.get_leaf_mut(BlockEdits::get_chunk_position(position))
+    else {
+        return;
+    };
+    let Some(top_height) = chunks.keys().max().copied() else {
+        return;
+    };
+
+    for (height, chunk) in chunks.iter_mut() {
+        let chunk_task_generator = ChunkTaskGenerator(
+            IVec2::from_array(parent_pos),
+            lod,
+            lod_position,
+            *height,
+            owner,
+        );
+
+        if commands.get_entity(*chunk).is_err() {
+            let mut new_chunk = commands.spawn((
+                Name::new(format!(
+                    "SubChunk[lod: {lod:?}, pos: {lod_position:?}, height: {height}]"
+                )),
+                Visibility::Visible,
+            ));
+            regenerate_chunk(&mut new_chunk, chunk_task_generator);
+            *chunk = new_chunk.id();
+
+            commands.entity(owner).add_child(*chunk);
+        } else if *height == top_height && generated_chunks.contains(*chunk) {
+            // Chunks that are still generating pick the edit up on their own.
+            regenerate_chunk(&mut commands.entity(*chunk), chunk_task_generator);
+        }
+    }
+}
@@ -1,78 +1,215 @@
 use crate::debug_tools::debug_resource::SpellhavenDebug;
 use crate::player::Player;
+use crate::world_generation::chunk_generation::block_registry::BlockRegistry;
+use crate::world_generation::chunk_generation::chunk_edits::BlockEdits;
+use crate::world_generation::chunk_generation::light_propagation::{relight_block, update_light};
+use crate::world_generation::chunk_generation::mesh_generation::{generate_mesh, ChunkMesh};
 use crate::world_generation::chunk_generation::voxel_generation::get_terrain_noise;
-use crate::world_generation::chunk_loading::chunk_loader::{
-    get_chunk_position, ChunkLoader, ChunkLoaderPlugin,
+use crate::world_generation::chunk_generation::voxel_types::VoxelData;
+use crate::world_generation::chunk_loading::chunk_loader::{ChunkLoader, ChunkLoaderPlugin};
+use crate::world_generation::chunk_loading::chunk_scheduler::{ChunkPriorities, ChunkScheduler};
+use crate::world_generation::chunk_loading::country_cache::{
+    get_country_position, CountryCache, COUNTRY_SIZE,
 };
-use crate::world_generation::chunk_loading::country_cache::{CountryCache, COUNTRY_SIZE};
 use crate::world_generation::chunk_loading::quad_tree_data::QuadTreeNode;
 use crate::world_generation::chunk_loading::quad_tree_data::QuadTreeNode::{Data, Node};
+use crate::world_generation::chunk_settings::chunk_settings;
 use crate::world_generation::generation_assets::GenerationAssets;
 use crate::world_generation::generation_options::{
-    GenerationCacheItem, GenerationOptionsResource, GenerationState,
+    reload_noise_graphs, GenerationCacheItem, GenerationOptionsResource, GenerationState,
+    NoiseGraphWatcher,
 };
 use crate::world_generation::voxel_world::{
-    ChunkGenerationResult, ChunkLod, QuadTreeVoxelWorld, VoxelWorld, MAX_LOD,
+    max_lod, ChunkGenerationResult, ChunkLod, NeighbourLods, QuadTreeVoxelWorld, VoxelWorld,
 };
 use ::noise::{Add, Constant, NoiseFn};
+use bevy::ecs::system::EntityCommands;
 use bevy::math::Vec3A;
 use bevy::prelude::*;
+use bevy::render::primitives::Frustum;
 use bevy::tasks::{Task, TaskPool, TaskPoolBuilder};
 use bevy_rapier3d::prelude::{Collider, RigidBody};
 use futures_lite::future;
-use std::collections::HashMap;
+use std::collections::{HashMap, HashSet, VecDeque};
 use std::sync::{Arc, Mutex};
 
+pub mod biome_registry;
+pub mod block_registry;
+pub mod chunk_edits;
+pub mod density_generation;
+pub mod generation_pass;
+pub mod light_propagation;
 pub mod mesh_generation;
 pub mod noise;
 pub mod oak_structure_generator;
 pub mod pine_structure_generator;
+pub mod strata_generation;
 pub mod structure_generator;
 pub mod tree_structure_generator;
 pub mod voxel_generation;
 pub mod voxel_types;
 
 //pub const LEVEL_OF_DETAIL: i32 = 1;
-pub const CHUNK_SIZE: usize = 64;
+/// Largest chunk size, as the mesher packs a row of a chunk into a `u64`.
+pub const MAX_CHUNK_SIZE: usize = 64;
 pub const VOXEL_SIZE: f32 = 1.0;
 
+/// Voxels along each side of a chunk, as chosen in the [`chunk_settings`].
+pub fn chunk_size() -> usize {
+    chunk_settings().chunk_size
+}
+
 pub struct ChunkTaskData {
-    pub mesh: Mesh,
+    pub mesh: Option<Mesh>,
+    pub transparent_mesh: Option<Mesh>,
     pub transform: Transform,
     pub collider: Option<Collider>,
 }
 
-#[derive(Copy, Clone, PartialEq, Eq, Hash)]
-pub enum BlockType {
-    Air,
-    Stone,
-    Grass,
-    Path,
-    Snow,
-}
+impl ChunkTaskData {
+    pub fn from_mesh(
+        mesh: Option<ChunkMesh>,
+        chunk_pos: IVec3,
+        chunk_lod: ChunkLod,
+    ) -> Option<Self> {
+        let mesh = mesh?;
+
+        let chunk_transform_pos = Vec3::new(
+            chunk_pos.x as f32 * chunk_size() as f32 * VOXEL_SIZE,
+            0.0,
+            chunk_pos.z as f32 * chunk_size() as f32 * VOXEL_SIZE,
+        );
 
-impl BlockType {
-    pub fn get_texture_index(&self) -> IVec2 {
-        match self {
-            BlockType::Path => IVec2::new(25, 2),
-            BlockType::Grass => IVec2::new(29, 18),
-            BlockType::Stone => IVec2::new(30, 29),
-            BlockType::Snow => IVec2::new(9, 29),
-            _ => IVec2::ZERO,
-        }
+        Some(Self {
+            transform: Transform::from_translation(chunk_transform_pos),
+            collider: if chunk_lod == ChunkLod::Full && !mesh.collider_triangles.is_empty() {
+                Some(
+                    Collider::trimesh(mesh.collider_positions, mesh.collider_triangles)
+                        .expect("Failed to build trimesh"),
+                )
+            } else {
+                None
+            },
+            mesh: mesh.solid,
+            transparent_mesh: mesh.transparent,
+        })
     }
 
-    pub fn get_texture_id(&self) -> u32 {
-        match self {
-            BlockType::Path => 2,
-            BlockType::Grass => 0,
-            BlockType::Stone => 1,
-            BlockType::Snow => 3,
-            _ => 0,
+    pub fn triangle_count(&self) -> usize {
+        [&self.mesh, &self.transparent_mesh]
+            .into_iter()
+            .flatten()
+            .map(|mesh| mesh.indices().unwrap().len() / 3)
+            .sum()
+    }
+
+    /// Replaces the meshes and the collider of a chunk entity with the ones of this task.
+    pub fn insert_into(
+        self,
+        entity: &mut EntityCommands,
+        transparent_mesh: Option<&ChunkTransparentMesh>,
+        meshes: &mut Assets<Mesh>,
+        generation_assets: &GenerationAssets,
+    ) {
+        match self.mesh {
+            Some(mesh) => {
+                entity.insert((
+                    Mesh3d(meshes.add(mesh)),
+                    MeshMaterial3d(generation_assets.material.clone()),
+                ));
+            }
+            None => {
+                entity.remove::<Mesh3d>();
+            }
+        }
+
+        match self.collider {
+            Some(collider) => {
+                entity.insert(collider);
+            }
+            None => {
+                entity.remove::<Collider>();
+            }
+        }
+
+        match (self.transparent_mesh, transparent_mesh) {
+            (Some(mesh), Some(transparent_mesh)) => {
+                entity
+                    .commands()
+                    .entity(transparent_mesh.0)
+                    .insert(Mesh3d(meshes.add(mesh)));
+            }
+            (Some(mesh), None) => {
+                let child = entity
+                    .commands()
+                    .spawn((
+                        Mesh3d(meshes.add(mesh)),
+                        MeshMaterial3d(generation_assets.transparent_material.clone()),
+                        Transform::default(),
+                        Name::new("TransparentMesh"),
+                    ))
+                    .id();
+                entity.add_child(child).insert(ChunkTransparentMesh(child));
+            }
+            (None, Some(_)) => remove_transparent_mesh(entity, transparent_mesh),
+            (None, None) => {}
         }
     }
 }
 
+/// Child entity of a chunk that renders its transparent blocks with a blending material.
+#[derive(Component)]
+pub struct ChunkTransparentMesh(pub Entity);
+
+pub fn remove_chunk_meshes(
+    entity: &mut EntityCommands,
+    transparent_mesh: Option<&ChunkTransparentMesh>,
+) {
+    entity.remove::<(Mesh3d, Collider)>();
+    remove_transparent_mesh(entity, transparent_mesh);
+}
+
+fn remove_transparent_mesh(
+    entity: &mut EntityCommands,
+    transparent_mesh: Option<&ChunkTransparentMesh>,
+) {
+    if let Some(transparent_mesh) = transparent_mesh {
+        entity.commands().entity(transparent_mesh.0).despawn();
+        entity.remove::<ChunkTransparentMesh>();
+    }
+}
+
+/// Id of a block in the [`BlockRegistry`](block_registry::BlockRegistry).
+#[derive(Copy, Clone, Debug, Default, PartialEq, Eq, Hash)]
+pub struct BlockType(pub u8);
+
+impl BlockType {
+    pub const AIR: Self = Self(0);
+    pub const STONE: Self = Self(1);
+    pub const GRASS: Self = Self(2);
+    pub const PATH: Self = Self(3);
+    pub const SNOW: Self = Self(4);
+    pub const DIRT: Self = Self(5);
+    pub const LEAVES: Self = Self(6);
+    pub const WATER: Self = Self(7);
+
+    /// Blocks placed by the generator itself, which the registry has to define with these ids.
+    pub const BUILTIN: [(Self, &'static str); 8] = [
+        (Self::AIR, "air"),
+        (Self::STONE, "stone"),
+        (Self::GRASS, "grass"),
+        (Self::PATH, "path"),
+        (Self::SNOW, "snow"),
+        (Self::DIRT, "dirt"),
+        (Self::LEAVES, "leaves"),
+        (Self::WATER, "water"),
+    ];
+
+    pub fn id(&self) -> u8 {
+        self.0
+    }
+}
+
 pub struct ChunkGenerationPlugin;
 
 pub struct ChunkTaskPool(pub TaskPool);
@@ -83,7 +220,7 @@ impl Resource for CacheTaskPool {}
 
 #[derive(Resource, Reflect, Default)]
 #[reflect(Resource)]
-pub struct ChunkTriangles(pub [u64; MAX_LOD.usize()]);
+pub struct ChunkTriangles(pub Vec<u64>);
 
 impl Plugin for ChunkGenerationPlugin {
     fn build(&self, app: &mut App) {
@@ -96,6 +233,8 @@ impl Plugin for ChunkGenerationPlugin {
                     start_chunk_tasks,
                     set_generated_caches,
                     draw_path_gizmos,
+                    reload_noise_graphs,
+                    resume_chunks_in_vertical_range,
                 ),
             )
             .add_systems(
@@ -103,6 +242,15 @@ impl Plugin for ChunkGenerationPlugin {
                 start_generating_quadtree_chunks.after(upgrade_quad_trees),
             )
             .add_systems(Update, upgrade_quad_trees.after(set_generated_chunks))
+            .add_systems(Update, restitch_chunk_borders.after(upgrade_quad_trees))
+            .add_systems(Update, sync_chunk_padding.after(set_generated_chunks))
+            .add_systems(
+                Update,
+                start_chunk_remeshes
+                    .after(sync_chunk_padding)
+                    .after(restitch_chunk_borders),
+            )
+            .add_systems(Update, set_remeshed_chunks.before(start_chunk_remeshes))
             .add_systems(Startup, setup_gizmo_settings)
             .insert_resource(QuadTreeVoxelWorld::default())
             .insert_resource(ChunkTaskPool(
@@ -118,26 +266,223 @@ impl Plugin for ChunkGenerationPlugin {
                     .build(),
             ))
             .insert_resource(GenerationOptionsResource::default())
-            .insert_resource(ChunkTriangles([0; MAX_LOD.usize()]))
-            .register_type::<ChunkTriangles>();
+            .init_resource::<NoiseGraphWatcher>()
+            .insert_resource(BlockEdits::default())
+            .insert_resource(ChunkTriangles(vec![0; max_lod().usize()]))
+            .init_resource::<ChunkScheduler>()
+            .register_type::<ChunkTriangles>()
+            .register_type::<ChunkScheduler>();
     }
 }
 
+/// Running generation of a chunk, along with the generator it is queued again with when the
+/// scheduler cancels it.
 #[derive(Component)]
-pub struct ChunkGenerationTask(pub Task<ChunkGenerationResult>, pub Entity);
+pub struct ChunkGenerationTask(pub Task<ChunkGenerationResult>, pub ChunkTaskGenerator);
 
 #[derive(Component)]
 pub struct CacheGenerationTask(pub Task<CountryCache>);
 
-#[derive(Component)]
+#[derive(Component, Clone, Copy)]
 pub struct ChunkTaskGenerator(pub IVec2, pub ChunkLod, pub IVec2, pub i32, pub Entity);
 
+/// Chunk stacked onto a generated chunk, waiting for the vertical range of a loader to reach it.
+/// Holds the height in voxels its bottom starts at.
+#[derive(Component)]
+pub struct ChunkOutsideVerticalRange(pub ChunkTaskGenerator, pub f32);
+
+impl ChunkOutsideVerticalRange {
+    pub fn is_reached_by(&self, chunk_loader: &ChunkLoader, transform: &Transform) -> bool {
+        let chunk_height = chunk_size() as f32 * self.0 .1.multiplier_f32() * VOXEL_SIZE;
+
+        chunk_loader.keeps_loaded(transform, self.0 .0.to_array())
+            && chunk_loader.reaches_heights(transform, self.1, self.1 + chunk_height)
+    }
+}
+
 #[derive(Component)]
 pub struct Chunk(pub [i32; 3]);
 
 #[derive(Component, Reflect)]
 pub struct ChunkParent(pub [i32; 2]);
 
+/// Quadtree node of a generated chunk and the neighbour lods its mesh was stitched against.
+#[derive(Component)]
+pub struct ChunkLodInfo {
+    pub lod: ChunkLod,
+    pub lod_position: IVec2,
+    pub neighbour_lods: NeighbourLods,
+}
+
+/// Marks a chunk whose mesh is rebuilt while the quadtree stays as is. Chunks with [`ChunkVoxels`]
+/// are meshed again from them, everything else is generated again.
+#[derive(Component)]
+pub struct ChunkRemesh;
+
+/// Marks a generated chunk that is generated again with new generation options, along with the
+/// chunks stacked onto it meanwhile. It keeps its meshes until the new ones arrive, and the chunks
+/// stacked on it follow the new terrain height.
+#[derive(Component)]
+pub struct ChunkRegenerate;
+
+/// Queues a generated chunk to be generated again, dropping the running tasks of its old voxels.
+pub fn regenerate_chunk(entity: &mut EntityCommands, chunk_task_generator: ChunkTaskGenerator) {
+    entity
+        .remove::<(ChunkGenerationTask, ChunkMeshTask, ChunkRemesh)>()
+        .insert((chunk_task_generator, ChunkRegenerate));
+}
+
+/// Marks a chunk whose neighbouring quadtree leaves changed, so its borders may have to be
+/// stitched again.
+#[derive(Component)]
+pub struct ChunkNeighboursChanged;
+
+/// Running remesh of a chunk from its [`ChunkVoxels`].
+#[derive(Component)]
+pub struct ChunkMeshTask(pub Task<Option<ChunkTaskData>>);
+
+/// Voxels of a generated full lod chunk, kept around so the chunk can be edited and remeshed.
+/// The voxels are shared with running remesh tasks and only copied when they change meanwhile.
+#[derive(Component, Clone)]
+pub struct ChunkVoxels {
+    pub data: Arc<VoxelData>,
+    pub chunk_pos: IVec3,
+    pub min_height: i32,
+}
+
+impl ChunkVoxels {
+    /// World voxel position of the first padding voxel of this chunk.
+    pub fn get_origin(&self) -> IVec3 {
+        IVec3::new(
+            self.chunk_pos.x * chunk_size() as i32,
+            self.min_height,
+            self.chunk_pos.z * chunk_size() as i32,
+        )
+    }
+
+    /// Converts a world voxel position into this chunks padded voxel array.
+    pub fn get_local_position(&self, position: IVec3) -> Option<IVec3> {
+        let local = position - self.get_origin();
+
+        if local.min_element() < 0 || local.max_element() >= chunk_size() as i32 + 2 {
+            None
+        } else {
+            Some(local)
+        }
+    }
+
+    /// Whether the world voxel position is meshed by this chunk, padding excluded.
+    pub fn contains(&self, position: IVec3) -> bool {
+        self.get_local_position(position).is_some_and(|local| {
+            local.min_element() >= 1 && local.max_element() <= chunk_size() as i32
+        })
+    }
+
+    pub fn get_block(&self, position: IVec3) -> Option<BlockType> {
+        self.get_local_position(position)
+            .map(|local| self.data.get_block(local))
+    }
+
+    pub fn set_block(&mut self, position: IVec3, block: BlockType) -> bool {
+        match self.get_local_position(position) {
+            Some(local) if self.data.get_block(local) != block => {
+                Arc::make_mut(&mut self.data).set_block(local, block);
+                true
+            }
+            _ => false,
+        }
+    }
+
+    /// Updates the light of the chunk after the block at the world voxel position changed.
+    pub fn relight_block(&mut self, position: IVec3, block_registry: &BlockRegistry) {
+        if let Some(local) = self.get_local_position(position) {
+            relight_block(Arc::make_mut(&mut self.data), block_registry, local);
+        }
+    }
+
+    /// World voxel positions in the padding of this chunk that the neighbour meshes itself.
+    fn get_shared_padding(&self, neighbour: &ChunkVoxels) -> impl Iterator<Item = IVec3> + '_ {
+        let min = self.get_origin().max(neighbour.get_origin() + 1);
+        let max = (self.get_origin() + chunk_size() as i32 + 1)
+            .min(neighbour.get_origin() + chunk_size() as i32);
+
+        (min.x..=max.x)
+            .flat_map(move |x| (min.y..=max.y).map(move |y| (x, y)))
+            .flat_map(move |(x, y)| (min.z..=max.z).map(move |z| IVec3::new(x, y, z)))
+            .filter(|position| !self.contains(*position))
+    }
+
+    /// Overwrites the padding voxels this chunk shares with a loaded neighbour with the
+    /// neighbours real blocks, which can differ from the regenerated padding once structures or
+    /// edits cross the border. Returns whether any padding voxel changed.
+    pub fn copy_padding_from(&mut self, neighbour: &ChunkVoxels) -> bool {
+        let shared_padding = self.get_shared_padding(neighbour).collect::<Vec<_>>();
+        let mut changed = false;
+
+        for position in shared_padding {
+            if let Some(block) = neighbour.get_block(position) {
+                changed |= self.set_block(position, block);
+            }
+        }
+
+        changed
+    }
+
+    /// Overwrites the light of the padding voxels this chunk shares with a loaded neighbour with
+    /// the neighbours light and spreads the difference into the chunk. Returns whether any light
+    /// changed.
+    pub fn copy_light_padding_from(
+        &mut self,
+        neighbour: &ChunkVoxels,
+        block_registry: &BlockRegistry,
+    ) -> bool {
+        let shared_padding = self.get_shared_padding(neighbour).collect::<Vec<_>>();
+        let mut changes = vec![];
+
+        for position in shared_padding {
+            let (Some(local), Some(neighbour_local)) = (
+                self.get_local_position(position),
+                neighbour.get_local_position(position),
+            ) else {
+                continue;
+            };
+
+            let light = neighbour.data.get_light(neighbour_local);
+            let previous_light = self.data.get_light(local);
+
+            if light != previous_light {
+                Arc::make_mut(&mut self.data).set_light(local, light);
+                changes.push((local, previous_light));
+            }
+        }
+
+        if changes.is_empty() {
+            return false;
+        }
+
+        update_light(Arc::make_mut(&mut self.data), block_registry, &changes);
+        true
+    }
+
+    pub fn generate_task_data(
+        &self,
+        neighbour_lods: NeighbourLods,
+        block_registry: &BlockRegistry,
+    ) -> Option<ChunkTaskData> {
+        ChunkTaskData::from_mesh(
+            generate_mesh(
+                &self.data,
+                self.min_height,
+                ChunkLod::Full,
+                neighbour_lods,
+                block_registry,
+            ),
+            self.chunk_pos,
+            ChunkLod::Full,
+        )
+    }
+}
+
 #[derive(Component)]
 pub struct ChunkGenerator(pub [i32; 2]);
 
@@ -146,28 +491,52 @@ fn start_chunk_tasks(
     chunk_task_pool: Res<ChunkTaskPool>,
     cache_task_pool: Res<CacheTaskPool>,
     chunk_task_generators: Query<(Entity, &ChunkTaskGenerator)>,
-    chunk_tasks: Query<(), With<ChunkGenerationTask>>,
+    chunk_tasks: Query<(Entity, &ChunkGenerationTask)>,
+    chunk_loaders: Query<(&ChunkLoader, &Transform)>,
+    cameras: Query<(&Camera, &Frustum)>,
+    chunk_scheduler: Res<ChunkScheduler>,
     mut generation_options: ResMut<GenerationOptionsResource>,
+    block_edits: Res<BlockEdits>,
+    voxel_world: Res<QuadTreeVoxelWorld>,
 ) {
-    let chunk_task_count = chunk_tasks.iter().count();
-
-    if chunk_task_count >= 5 {
-        return;
-    }
-
-    let mut current_added_tasks = 0usize;
-
-    let mut chunk_tasks_vec = chunk_task_generators.iter().collect::<Vec<_>>();
-    chunk_tasks_vec.sort_by(|a, b| a.1 .1.usize().cmp(&b.1 .1.usize()));
+    let chunk_priorities = ChunkPriorities::new(
+        &chunk_scheduler,
+        &chunk_loaders,
+        cameras
+            .iter()
+            .filter(|(camera, _)| camera.is_active)
+            .map(|(_, frustum)| frustum),
+    );
+    let generation_options = &mut *generation_options;
+
+    // Sorted so the least important running task is the first to be cancelled.
+    let mut running_tasks = chunk_tasks
+        .iter()
+        .map(|(entity, task)| (chunk_priorities.get(&task.1), entity, task.1))
+        .collect::<Vec<_>>();
+    running_tasks.sort_by(|a, b| a.0.total_cmp(&b.0));
+
+    let mut chunk_task_count = running_tasks.len();
+
+    let mut chunk_tasks_vec = chunk_task_generators
+        .iter()
+        .map(|(entity, chunk_task_generator)| {
+            (
+                chunk_priorities.get(chunk_task_generator),
+                entity,
+                chunk_task_generator,
+            )
+        })
+        .collect::<Vec<_>>();
+    chunk_tasks_vec.sort_by(|a, b| a.0.total_cmp(&b.0));
 
-    for (entity, chunk_task_generator) in chunk_tasks_vec {
+    for (priority, entity, chunk_task_generator) in chunk_tasks_vec {
         let parent_pos = chunk_task_generator.0;
-        let country_pos = (parent_pos.as_vec2()
-            / (COUNTRY_SIZE as f32 / (MAX_LOD.multiplier_f32() * CHUNK_SIZE as f32)))
-            .floor()
-            .as_ivec2();
+        let country_pos = get_country_position(parent_pos);
 
-        match generation_options.1.get(&country_pos) {
+        // Chunks wait for their country cache before they take a task, so they don't cancel
+        // running tasks they can't replace yet.
+        let country_cache = match generation_options.1.get(&country_pos) {
             None => {
                 let arc_generation_options = generation_options.0.clone();
                 commands.spawn(CacheGenerationTask(cache_task_pool.0.spawn(async move {
@@ -177,39 +546,60 @@ fn start_chunk_tasks(
                 generation_options
                     .1
                     .insert(country_pos, GenerationState::Generating);
+                continue;
             }
-            Some(country_cache) => match country_cache {
-                GenerationState::Generating => {}
-                GenerationState::Some(country_cache) => {
-                    if let Ok(mut entity) = commands.get_entity(entity) {
-                        current_added_tasks += 1;
-
-                        let generation_options = generation_options.0.clone();
-                        let chunk_lod = chunk_task_generator.1;
-                        let lod_pos = chunk_task_generator.2;
-                        let height = chunk_task_generator.3;
-                        let country_cache = country_cache.clone();
-                        let task = chunk_task_pool.0.spawn(async move {
-                            QuadTreeVoxelWorld::generate_chunk(
-                                parent_pos,
-                                chunk_lod,
-                                lod_pos,
-                                generation_options,
-                                height,
-                                &country_cache,
-                            )
-                        });
-
-                        entity
-                            .remove::<ChunkTaskGenerator>()
-                            .insert(ChunkGenerationTask(task, chunk_task_generator.4));
+            Some(GenerationState::Generating) => continue,
+            Some(GenerationState::Some(country_cache)) => country_cache.clone(),
+        };
+
+        if chunk_task_count >= chunk_scheduler.max_tasks {
+            match running_tasks.last() {
+                Some(&(running_priority, running_entity, running_generator))
+                    if chunk_scheduler.should_cancel(running_priority, priority) =>
+                {
+                    running_tasks.pop();
+
+                    // Dropping the task cancels it, the chunk waits in the queue again.
+                    if let Ok(mut running_entity) = commands.get_entity(running_entity) {
+                        running_entity
+                            .remove::<ChunkGenerationTask>()
+                            .insert(running_generator);
+                        chunk_task_count -= 1;
                     }
                 }
-            },
+                _ => return,
+            }
+
+            if chunk_task_count >= chunk_scheduler.max_tasks {
+                continue;
+            }
         }
 
-        if chunk_task_count + current_added_tasks >= 5 {
-            return;
+        if let Ok(mut entity) = commands.get_entity(entity) {
+            chunk_task_count += 1;
+
+            let generation_options = generation_options.0.clone();
+            let chunk_lod = chunk_task_generator.1;
+            let lod_pos = chunk_task_generator.2;
+            let height = chunk_task_generator.3;
+            let chunk_edits = block_edits.get_surrounding_edits(parent_pos);
+            let neighbour_lods = voxel_world.get_neighbour_lods(parent_pos, chunk_lod, lod_pos);
+            let task = chunk_task_pool.0.spawn(async move {
+                QuadTreeVoxelWorld::generate_chunk(
+                    parent_pos,
+                    chunk_lod,
+                    lod_pos,
+                    generation_options,
+                    height,
+                    &country_cache,
+                    &chunk_edits,
+                    neighbour_lods,
+                )
+            });
+
+            entity
+                .remove::<ChunkTaskGenerator>()
+                .insert(ChunkGenerationTask(task, *chunk_task_generator));
         }
     }
 }
@@ -226,7 +616,7 @@ fn start_generating_quadtree_chunks(
             Some(chunk_tree) => {
                 let tree = generate_quad_tree_chunk(
                     entity,
-                    MAX_LOD,
+                    max_lod(),
                     [0, 0],
                     chunk_generator.0,
                     &chunk_loaders,
@@ -235,6 +625,11 @@ fn start_generating_quadtree_chunks(
                 );
 
                 **chunk_tree = Some(tree);
+                mark_chunks_around(
+                    &mut commands,
+                    &voxel_world,
+                    get_lod_area(chunk_generator.0, max_lod(), [0, 0]),
+                );
 
                 commands.entity(entity).insert(Name::new(
                     "Chunk [".to_owned()
@@ -262,20 +657,13 @@ fn generate_quad_tree_chunk(
     let mut divide = false;
 
     if current_lod != ChunkLod::Full {
+        let current_chunk_pos = [
+            owner_chunk_pos[0] * current_lod.inverse_multiplier_i32() + current_lod_pos[0],
+            owner_chunk_pos[1] * current_lod.inverse_multiplier_i32() + current_lod_pos[1],
+        ];
+
         for (chunk_loader, transform) in chunk_loaders {
-            let loader_chunk_position = get_chunk_position(transform.translation, current_lod);
-            let current_chunk_pos = [
-                owner_chunk_pos[0] * current_lod.inverse_multiplier_i32() + current_lod_pos[0],
-                owner_chunk_pos[1] * current_lod.inverse_multiplier_i32() + current_lod_pos[1],
-            ];
-            let position_difference = [
-                loader_chunk_position[0] - current_chunk_pos[0],
-                loader_chunk_position[1] - current_chunk_pos[1],
-            ];
-            let current_range = chunk_loader.lod_range[MAX_LOD.usize() - current_lod.usize()];
-            if position_difference[0].abs() <= current_range
-                && position_difference[1].abs() <= current_range
-            {
+            if chunk_loader.divides(transform, current_lod, current_chunk_pos) {
                 divide = true;
             }
         }
@@ -359,6 +747,8 @@ pub(crate) fn upgrade_quad_trees(
     chunk_loaders: Query<(&ChunkLoader, &Transform)>,
     generated_chunks: Query<Entity, With<Chunk>>,
 ) {
+    let mut changed_areas = vec![];
+
     for chunk in &chunks {
         let boxed_tree = voxel_world.get_chunk(chunk.1 .0).expect("Chunk not found!");
 
@@ -368,18 +758,44 @@ pub(crate) fn upgrade_quad_trees(
                 let tree = upgrade_tree_recursion(
                     chunk.0,
                     chunk_tree,
-                    MAX_LOD,
+                    max_lod(),
                     [0, 0],
                     chunk.1 .0,
                     &chunk_loaders,
                     &mut commands,
                     &generated_chunks,
+                    &mut changed_areas,
                 );
 
                 **boxed_tree = Some(tree);
             }
         }
     }
+
+    for changed_area in changed_areas {
+        mark_chunks_around(&mut commands, &voxel_world, changed_area);
+    }
+}
+
+/// Area of a quadtree node in full lod chunks, from its first chunk to the one after its last.
+pub fn get_lod_area(owner_chunk_pos: [i32; 2], lod: ChunkLod, lod_pos: [i32; 2]) -> (IVec2, IVec2) {
+    let size = lod.multiplier_i32();
+    let min = IVec2::from_array(owner_chunk_pos) * max_lod().multiplier_i32()
+        + IVec2::from_array(lod_pos) * size;
+
+    (min, min + size)
+}
+
+/// Marks the chunks in and around the area of changed quadtree leaves, given in full lod chunks,
+/// so their borders are checked against the new lods.
+pub fn mark_chunks_around(
+    commands: &mut Commands,
+    voxel_world: &QuadTreeVoxelWorld,
+    (min, max): (IVec2, IVec2),
+) {
+    for chunk in voxel_world.get_chunks_around(min, max) {
+        commands.entity(chunk).try_insert(ChunkNeighboursChanged);
+    }
 }
 
 fn upgrade_tree_recursion(
@@ -391,30 +807,20 @@ fn upgrade_tree_recursion(
     chunk_loaders: &Query<(&ChunkLoader, &Transform)>,
     commands: &mut Commands,
     generated_chunks: &Query<Entity, With<Chunk>>,
+    changed_areas: &mut Vec<(IVec2, IVec2)>,
 ) -> QuadTreeNode<HashMap<i32, Entity>> {
     match current_node {
         Data(children, entities) => {
             let mut divide = false;
 
             if current_lod != ChunkLod::Full {
+                let current_chunk_pos = [
+                    owner_chunk_pos[0] * current_lod.inverse_multiplier_i32() + current_lod_pos[0],
+                    owner_chunk_pos[1] * current_lod.inverse_multiplier_i32() + current_lod_pos[1],
+                ];
+
                 for (chunk_loader, transform) in chunk_loaders {
-                    let loader_chunk_position =
-                        get_chunk_position(transform.translation, current_lod);
-                    let current_chunk_pos = [
-                        owner_chunk_pos[0] * current_lod.inverse_multiplier_i32()
-                            + current_lod_pos[0],
-                        owner_chunk_pos[1] * current_lod.inverse_multiplier_i32()
-                            + current_lod_pos[1],
-                    ];
-                    let position_difference = [
-                        loader_chunk_position[0] - current_chunk_pos[0],
-                        loader_chunk_position[1] - current_chunk_pos[1],
-                    ];
-                    let current_range =
-                        chunk_loader.lod_range[MAX_LOD.usize() - current_lod.usize()];
-                    if position_difference[0].abs() <= current_range
-                        && position_difference[1].abs() <= current_range
-                    {
+                    if chunk_loader.divides(transform, current_lod, current_chunk_pos) {
                         divide = true;
                     }
                 }
@@ -424,6 +830,8 @@ fn upgrade_tree_recursion(
                 return Data(children.clone(), entities.clone());
             }
 
+            changed_areas.push(get_lod_area(owner_chunk_pos, current_lod, current_lod_pos));
+
             let entities = check_entities_for_deletion(
                 [children.clone().into_values().collect(), entities.clone()].concat(),
                 commands,
@@ -475,24 +883,13 @@ fn upgrade_tree_recursion(
             let mut divide = false;
 
             if current_lod != ChunkLod::Full {
+                let current_chunk_pos = [
+                    owner_chunk_pos[0] * current_lod.inverse_multiplier_i32() + current_lod_pos[0],
+                    owner_chunk_pos[1] * current_lod.inverse_multiplier_i32() + current_lod_pos[1],
+                ];
+
                 for (chunk_loader, transform) in chunk_loaders {
-                    let loader_chunk_position =
-                        get_chunk_position(transform.translation, current_lod);
-                    let current_chunk_pos = [
-                        owner_chunk_pos[0] * current_lod.inverse_multiplier_i32()
-                            + current_lod_pos[0],
-                        owner_chunk_pos[1] * current_lod.inverse_multiplier_i32()
-                            + current_lod_pos[1],
-                    ];
-                    let position_difference = [
-                        loader_chunk_position[0] - current_chunk_pos[0],
-                        loader_chunk_position[1] - current_chunk_pos[1],
-                    ];
-                    let current_range =
-                        chunk_loader.lod_range[MAX_LOD.usize() - current_lod.usize()];
-                    if position_difference[0].abs() <= current_range
-                        && position_difference[1].abs() <= current_range
-                    {
+                    if chunk_loader.divides(transform, current_lod, current_chunk_pos) {
                         divide = true;
                     }
                 }
@@ -509,6 +906,7 @@ fn upgrade_tree_recursion(
                         chunk_loaders,
                         commands,
                         generated_chunks,
+                        changed_areas,
                     )),
                     Box::new(upgrade_tree_recursion(
                         owner,
@@ -519,6 +917,7 @@ fn upgrade_tree_recursion(
                         chunk_loaders,
                         commands,
                         generated_chunks,
+                        changed_areas,
                     )),
                     Box::new(upgrade_tree_recursion(
                         owner,
@@ -529,6 +928,7 @@ fn upgrade_tree_recursion(
                         chunk_loaders,
                         commands,
                         generated_chunks,
+                        changed_areas,
                     )),
                     Box::new(upgrade_tree_recursion(
                         owner,
@@ -539,6 +939,7 @@ fn upgrade_tree_recursion(
                         chunk_loaders,
                         commands,
                         generated_chunks,
+                        changed_areas,
                     )),
                     current_mutex.clone(),
                     current_entities.clone(),
@@ -555,6 +956,7 @@ fn upgrade_tree_recursion(
             .concat();
 
             let entities = check_entities_for_deletion(entities, commands, generated_chunks);
+            changed_areas.push(get_lod_area(owner_chunk_pos, current_lod, current_lod_pos));
 
             generate_quad_tree_chunk(
                 owner,
@@ -609,18 +1011,135 @@ fn get_entities_recursive(current_node: &QuadTreeNode<HashMap<i32, Entity>>) ->
     }
 }
 
+/// Remeshes the chunks marked by quadtree changes whose neighbours changed lod since they were
+/// meshed, so their borders stay stitched to the current quadtree. Chunks that are still generating
+/// keep the mark until they are done.
+fn restitch_chunk_borders(
+    mut commands: Commands,
+    voxel_world: Res<QuadTreeVoxelWorld>,
+    mut chunks: Query<
+        (
+            Entity,
+            &Chunk,
+            &ChildOf,
+            &mut ChunkLodInfo,
+            Has<ChunkVoxels>,
+        ),
+        (
+            With<ChunkNeighboursChanged>,
+            Without<ChunkRemesh>,
+            Without<ChunkRegenerate>,
+        ),
+    >,
+) {
+    for (entity, chunk, child_of, mut lod_info, has_voxels) in &mut chunks {
+        let mut entity = commands.entity(entity);
+        entity.remove::<ChunkNeighboursChanged>();
+
+        let parent_pos = IVec2::new(chunk.0[0], chunk.0[2]);
+        let chunk_lod = lod_info.lod;
+
+        // Chunks that only wait for their replacement to finish generating are left alone.
+        if voxel_world.get_leaf_lod(parent_pos, chunk_lod, lod_info.lod_position) != Some(chunk_lod)
+        {
+            continue;
+        }
+
+        let neighbour_lods =
+            voxel_world.get_neighbour_lods(parent_pos, chunk_lod, lod_info.lod_position);
+
+        if neighbour_lods.stitches_like(&lod_info.neighbour_lods, chunk_lod) {
+            continue;
+        }
+
+        // Full lod chunks keep their voxels, everything else has to be generated again.
+        if has_voxels {
+            lod_info.neighbour_lods = neighbour_lods;
+            entity.insert(ChunkRemesh);
+        } else {
+            entity.insert((
+                ChunkTaskGenerator(
+                    parent_pos,
+                    chunk_lod,
+                    lod_info.lod_position,
+                    chunk.0[1],
+                    child_of.parent(),
+                ),
+                ChunkRemesh,
+            ));
+        }
+    }
+}
+
 fn set_generated_chunks(
     mut commands: Commands,
-    mut chunks: Query<(Entity, &mut ChunkGenerationTask)>,
+    mut chunks: Query<(
+        Entity,
+        &mut ChunkGenerationTask,
+        Has<ChunkRemesh>,
+        Has<ChunkRegenerate>,
+        Option<&ChunkTransparentMesh>,
+    )>,
     mut meshes: ResMut<Assets<Mesh>>,
     mut voxel_world: ResMut<QuadTreeVoxelWorld>,
     mut chunk_triangles: ResMut<ChunkTriangles>,
     generation_assets: Res<GenerationAssets>,
+    block_edits: Res<BlockEdits>,
+    chunk_loaders: Query<(&ChunkLoader, &Transform)>,
 ) {
-    for (entity, mut task) in &mut chunks {
-        if let Some(chunk_generation_result) = future::block_on(future::poll_once(&mut task.0)) {
-            let tree_depth = <ChunkLod as Into<i32>>::into(MAX_LOD)
-                - <ChunkLod as Into<i32>>::into(chunk_generation_result.lod);
+    for (entity, mut task, remesh, regenerate, transparent_mesh) in &mut chunks {
+        if let Some(mut chunk_generation_result) = future::block_on(future::poll_once(&mut task.0))
+        {
+            if remesh {
+                let mut current_entity = commands.entity(entity);
+                current_entity
+                    .remove::<(ChunkGenerationTask, ChunkRemesh)>()
+                    .insert(ChunkLodInfo {
+                        lod: chunk_generation_result.lod,
+                        lod_position: chunk_generation_result.lod_position,
+                        neighbour_lods: chunk_generation_result.neighbour_lods,
+                    });
+
+                match chunk_generation_result.task_data {
+                    None => remove_chunk_meshes(&mut current_entity, transparent_mesh),
+                    Some(chunk_task_data) => chunk_task_data.insert_into(
+                        &mut current_entity,
+                        transparent_mesh,
+                        &mut meshes,
+                        &generation_assets,
+                    ),
+                }
+
+                continue;
+            }
+
+            // Edits made while the task was running are missing from its snapshot, so the chunk is
+            // queued again to be generated and lit with them.
+            if chunk_generation_result.lod == ChunkLod::Full {
+                let applied_edits: usize = block_edits
+                    .get_surrounding_edits(chunk_generation_result.parent_pos)
+                    .iter()
+                    .map(|edits| {
+                        edits.apply(
+                            &mut chunk_generation_result.voxel_data,
+                            chunk_generation_result.chunk_pos,
+                            chunk_generation_result.min_height,
+                            ChunkLod::Full,
+                        )
+                    })
+                    .sum();
+
+                if applied_edits > 0 {
+                    commands
+                        .entity(entity)
+                        .remove::<ChunkGenerationTask>()
+                        .insert(task.1);
+                    continue;
+                }
+            }
+
+            let tree_depth =
+                max_lod().i32() - <ChunkLod as Into<i32>>::into(chunk_generation_result.lod);
             match voxel_world.get_chunk(chunk_generation_result.parent_pos.to_array()) {
                 None => {
                     info!("Owner not found!")
@@ -636,7 +1155,7 @@ fn set_generated_chunks(
                             None => {
                                 info!(
                                     "Map not found! depth: {0}, pos: [{1}, {2}]",
-                                    <ChunkLod as Into<i32>>::into(MAX_LOD)
+                                    max_lod().i32()
                                         - <ChunkLod as Into<i32>>::into(
                                             chunk_generation_result.lod
                                         ),
@@ -649,15 +1168,73 @@ fn set_generated_chunks(
                                     if let Data(map, _) = node {
                                         let new_height = chunk_generation_result.chunk_height + 1;
 
-                                        let child = commands.spawn((
-                                                ChunkTaskGenerator(chunk_generation_result.parent_pos, chunk_generation_result.lod, chunk_generation_result.lod_position, new_height, task.1),
-                                                Name::new(format!("SubChunk[lod: {0:?}, pos: {1:?}, height: {new_height}]", chunk_generation_result.lod, chunk_generation_result.lod_position)),
-                                                Visibility::Visible
-                                            )).id();
+                                        // Regenerated chunks keep the chunk stacked on them, unless
+                                        // the terrain grew past their old top.
+                                        let has_chunk_above =
+                                            map.get(&new_height).is_some_and(|chunk_above| {
+                                                commands.get_entity(*chunk_above).is_ok()
+                                            });
+
+                                        if !regenerate || !has_chunk_above {
+                                            let chunk_above = ChunkOutsideVerticalRange(
+                                                ChunkTaskGenerator(
+                                                    chunk_generation_result.parent_pos,
+                                                    chunk_generation_result.lod,
+                                                    chunk_generation_result.lod_position,
+                                                    new_height,
+                                                    task.1 .4,
+                                                ),
+                                                (chunk_generation_result.min_height
+                                                    + chunk_size() as i32)
+                                                    as f32
+                                                    * chunk_generation_result.lod.multiplier_f32()
+                                                    * VOXEL_SIZE,
+                                            );
+
+                                            let mut child = commands.spawn((
+                                                    Name::new(format!("SubChunk[lod: {0:?}, pos: {1:?}, height: {new_height}]", chunk_generation_result.lod, chunk_generation_result.lod_position)),
+                                                    Visibility::Visible
+                                                ));
+
+                                            if chunk_loaders.iter().any(
+                                                |(chunk_loader, transform)| {
+                                                    chunk_above
+                                                        .is_reached_by(chunk_loader, transform)
+                                                },
+                                            ) {
+                                                child.insert(chunk_above.0);
+                                            } else {
+                                                child.insert(chunk_above);
+                                            }
+
+                                            if regenerate {
+                                                child.insert(ChunkRegenerate);
+                                            }
+
+                                            let child = child.id();
 
-                                        commands.entity(task.1).add_child(child);
+                                            commands.entity(task.1 .4).add_child(child);
 
-                                        map.insert(new_height, child);
+                                            map.insert(new_height, child);
+                                        }
+                                    }
+                                } else if regenerate {
+                                    // The terrain may have sunk below the chunks stacked on a
+                                    // regenerated chunk, which go away with it.
+                                    if let Data(map, _) = node {
+                                        map.retain(|height, chunk_above| {
+                                            let above =
+                                                *height > chunk_generation_result.chunk_height;
+                                            if above {
+                                                if let Ok(mut chunk_above) =
+                                                    commands.get_entity(*chunk_above)
+                                                {
+                                                    chunk_above.despawn();
+                                                }
+                                            }
+
+                                            !above
+                                        });
                                     }
                                 } else {
                                     if let Data(_, despawn_entities) = node {
@@ -684,34 +1261,60 @@ fn set_generated_chunks(
                 },
             }
 
-            if let Ok(mut current_entity) = commands.get_entity(entity) {
-                if let Some(chunk_task_data) = chunk_generation_result.task_data {
-                    let triangle_count = chunk_task_data.mesh.indices().unwrap().len() / 3;
+            let task_data = chunk_generation_result.task_data;
+            let chunk_voxels =
+                (chunk_generation_result.lod == ChunkLod::Full).then(|| ChunkVoxels {
+                    data: Arc::new(chunk_generation_result.voxel_data),
+                    chunk_pos: chunk_generation_result.chunk_pos,
+                    min_height: chunk_generation_result.min_height,
+                });
 
-                    chunk_triangles.0[chunk_generation_result.lod.usize() - 1] +=
-                        triangle_count as u64;
+            if let Ok(mut current_entity) = commands.get_entity(entity) {
+                if let Some(chunk_task_data) = task_data {
+                    if !regenerate {
+                        chunk_triangles.0[chunk_generation_result.lod.usize() - 1] +=
+                            chunk_task_data.triangle_count() as u64;
+                    }
 
                     current_entity.remove::<ChunkGenerationTask>().insert((
                         chunk_task_data.transform,
-                        Mesh3d(meshes.add(chunk_task_data.mesh)),
-                        MeshMaterial3d(generation_assets.material.clone()),
                         Chunk([
                             chunk_generation_result.parent_pos[0],
                             chunk_generation_result.chunk_height,
                             chunk_generation_result.parent_pos[1],
                         ]),
+                        ChunkLodInfo {
+                            lod: chunk_generation_result.lod,
+                            lod_position: chunk_generation_result.lod_position,
+                            neighbour_lods: chunk_generation_result.neighbour_lods,
+                        },
                         //SpawnAnimation::default()
                     ));
 
                     if chunk_generation_result.lod == ChunkLod::Full {
                         current_entity.insert((
                             RigidBody::Fixed,
-                            chunk_task_data.collider.unwrap(),
                             GlobalTransform::from_translation(
                                 chunk_task_data.transform.translation,
                             ),
                         ));
                     }
+
+                    if let Some(chunk_voxels) = chunk_voxels {
+                        current_entity.insert(chunk_voxels);
+                    }
+
+                    // Remeshes of the old voxels would replace the regenerated meshes.
+                    if regenerate {
+                        current_entity.remove::<(ChunkRegenerate, ChunkMeshTask, ChunkRemesh)>();
+                    }
+
+                    chunk_task_data.insert_into(
+                        &mut current_entity,
+                        transparent_mesh,
+                        &mut meshes,
+                        &generation_assets,
+                    );
                 } else {
                     current_entity.despawn();
                 }
@@ -720,6 +1323,187 @@ fn set_generated_chunks(
     }
 }
 
+/// Queues the chunks waiting outside the vertical range of the loaders once a loader reaches them.
+fn resume_chunks_in_vertical_range(
+    mut commands: Commands,
+    chunks: Query<(Entity, &ChunkOutsideVerticalRange)>,
+    chunk_loaders: Query<(&ChunkLoader, &Transform)>,
+) {
+    for (entity, chunk_above) in &chunks {
+        if chunk_loaders
+            .iter()
+            .any(|(chunk_loader, transform)| chunk_above.is_reached_by(chunk_loader, transform))
+        {
+            commands
+                .entity(entity)
+                .remove::<ChunkOutsideVerticalRange>()
+                .insert(chunk_above.0);
+        }
+    }
+}
+
+/// Fills the padding of newly generated full lod chunks from their loaded neighbours and the
+/// neighbours padding from them, then marks every chunk whose padding or light changed for
+/// remeshing. Padding towards chunks that aren't loaded keeps the generated blocks and light.
+fn sync_chunk_padding(
+    mut commands: Commands,
+    voxel_world: Res<QuadTreeVoxelWorld>,
+    generation_options: Res<GenerationOptionsResource>,
+    added_chunks: Query<Entity, Added<ChunkVoxels>>,
+    mut chunks: Query<(
+        Entity,
+        &mut ChunkVoxels,
+        &ChunkLodInfo,
+        Option<&ChunkTransparentMesh>,
+    )>,
+) {
+    let mut changed_chunks = HashSet::new();
+
+    for added_chunk in &added_chunks {
+        let Ok((_, added_voxels, _, _)) = chunks.get(added_chunk) else {
+            continue;
+        };
+        let neighbours = get_neighbour_chunks(&voxel_world, added_voxels.chunk_pos, added_chunk);
+
+        for neighbour in neighbours {
+            let Ok([(_, mut added_voxels, _, _), (_, mut neighbour_voxels, _, _)]) =
+                chunks.get_many_mut([added_chunk, neighbour])
+            else {
+                continue;
+            };
+
+            if added_voxels.copy_padding_from(&neighbour_voxels) {
+                changed_chunks.insert(added_chunk);
+            }
+
+            if neighbour_voxels.copy_padding_from(&added_voxels) {
+                changed_chunks.insert(neighbour);
+            }
+        }
+    }
+
+    changed_chunks.extend(sync_chunk_light(
+        &mut chunks,
+        &voxel_world,
+        added_chunks.iter(),
+        &generation_options.0.block_registry,
+    ));
+
+    for changed_chunk in changed_chunks {
+        commands.entity(changed_chunk).insert(ChunkRemesh);
+    }
+}
+
+/// Meshes the chunks marked with [`ChunkRemesh`] again from their voxels on the
+/// [`ChunkTaskPool`]. Marking a chunk while it is meshed replaces the running task, as its voxels
+/// are outdated.
+fn start_chunk_remeshes(
+    mut commands: Commands,
+    chunk_task_pool: Res<ChunkTaskPool>,
+    generation_options: Res<GenerationOptionsResource>,
+    chunks: Query<(Entity, &ChunkVoxels, &ChunkLodInfo), With<ChunkRemesh>>,
+) {
+    for (entity, chunk_voxels, lod_info) in &chunks {
+        let chunk_voxels = chunk_voxels.clone();
+        let neighbour_lods = lod_info.neighbour_lods;
+        let generation_options = generation_options.0.clone();
+
+        let task = chunk_task_pool.0.spawn(async move {
+            chunk_voxels.generate_task_data(neighbour_lods, &generation_options.block_registry)
+        });
+
+        commands
+            .entity(entity)
+            .remove::<ChunkRemesh>()
+            .insert(ChunkMeshTask(task));
+    }
+}
+
+fn set_remeshed_chunks(
+    mut commands: Commands,
+    mut chunks: Query<(Entity, &mut ChunkMeshTask, Option<&ChunkTransparentMesh>)>,
+    mut meshes: ResMut<Assets<Mesh>>,
+    generation_assets: Res<GenerationAssets>,
+) {
+    for (entity, mut task, transparent_mesh) in &mut chunks {
+        if let Some(task_data) = future::block_on(future::poll_once(&mut task.0)) {
+            let mut entity = commands.entity(entity);
+            entity.remove::<ChunkMeshTask>();
+
+            match task_data {
+                None => remove_chunk_meshes(&mut entity, transparent_mesh),
+                Some(chunk_task_data) => chunk_task_data.insert_into(
+                    &mut entity,
+                    transparent_mesh,
+                    &mut meshes,
+                    &generation_assets,
+                ),
+            }
+        }
+    }
+}
+
+/// Copies light into the padding of the given chunks and their neighbours until every padding voxel
+/// matches the chunk it mirrors, following the light into further chunks as far as it changes.
+/// Returns the chunks whose light changed.
+pub fn sync_chunk_light(
+    chunks: &mut Query<(
+        Entity,
+        &mut ChunkVoxels,
+        &ChunkLodInfo,
+        Option<&ChunkTransparentMesh>,
+    )>,
+    voxel_world: &QuadTreeVoxelWorld,
+    changed_chunks: impl IntoIterator<Item = Entity>,
+    block_registry: &BlockRegistry,
+) -> HashSet<Entity> {
+    let mut pending_chunks = changed_chunks.into_iter().collect::<VecDeque<_>>();
+    let mut relit_chunks = HashSet::new();
+
+    while let Some(chunk) = pending_chunks.pop_front() {
+        let Ok((_, chunk_voxels, _, _)) = chunks.get(chunk) else {
+            continue;
+        };
+        let neighbours = get_neighbour_chunks(voxel_world, chunk_voxels.chunk_pos, chunk);
+
+        for neighbour in neighbours {
+            let Ok([(_, mut chunk_voxels, _, _), (_, mut neighbour_voxels, _, _)]) =
+                chunks.get_many_mut([chunk, neighbour])
+            else {
+                continue;
+            };
+
+            if neighbour_voxels.copy_light_padding_from(&chunk_voxels, block_registry) {
+                relit_chunks.insert(neighbour);
+                if !pending_chunks.contains(&neighbour) {
+                    pending_chunks.push_back(neighbour);
+                }
+            }
+
+            if chunk_voxels.copy_light_padding_from(&neighbour_voxels, block_registry) {
+                relit_chunks.insert(chunk);
+                if !pending_chunks.contains(&chunk) {
+                    pending_chunks.push_back(chunk);
+                }
+            }
+        }
+    }
+
+    relit_chunks
+}
+
+/// Chunks of the quadtree leaves next to or stacked on a full lod chunk, which includes every
+/// loaded chunk sharing padding with it. Coarser leaves are returned as well, they have no voxels.
+fn get_neighbour_chunks(
+    voxel_world: &QuadTreeVoxelWorld,
+    chunk_pos: IVec3,
+    chunk: Entity,
+) -> Vec<Entity> {
+    let mut neighbours = voxel_world.get_chunks_around(chunk_pos.xz(), chunk_pos.xz() + 1);
+    neighbours.retain(|neighbour| *neighbour != chunk);
+    neighbours
+}
+
 fn set_generated_caches(
     mut commands: Commands,
     mut chunks: Query<(Entity, &mut CacheGenerationTask)>,
@@ -759,7 +1543,7 @@ fn draw_path_gizmos(
             .floor()
             .as_ivec3();
         let player_voxel_pos = (player.translation / VOXEL_SIZE).as_ivec3().xz();
-        match generation_options.1.get(&player_country_pos.xz()) {
+        match generation_options.1.peek(&player_country_pos.xz()) {
             None => {}
             Some(country_cache) => match country_cache {
                 GenerationState::Some(country_cache) => {
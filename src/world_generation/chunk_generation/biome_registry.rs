@@ -0,0 +1,318 @@
+use crate::world_generation::chunk_generation::block_registry::BlockRegistry;
+use crate::world_generation::chunk_generation::BlockType;
+use noise::NoiseFn;
+use serde::Deserialize;
+use std::fs;
+use std::path::Path;
+
+pub const BIOME_REGISTRY_FILE: &str = "biomes.ron";
+
+/// Climate distance over which the height modifiers of neighbouring biomes are blended.
+const BIOME_BLEND_DISTANCE: f32 = 0.15;
+
+#[derive(Deserialize)]
+struct BiomeRegistryFile {
+    biomes: Vec<BiomeFileEntry>,
+    #[serde(default)]
+    strata: Vec<StratumFileEntry>,
+    #[serde(default)]
+    ores: Vec<OreFileEntry>,
+}
+
+#[derive(Deserialize)]
+struct StratumFileEntry {
+    block: String,
+    min_depth: f32,
+}
+
+#[derive(Deserialize)]
+struct OreFileEntry {
+    block: String,
+    min_depth: f32,
+    max_depth: f32,
+    size: f32,
+    threshold: f32,
+    #[serde(default)]
+    biomes: Vec<String>,
+}
+
+#[derive(Deserialize)]
+struct BiomeFileEntry {
+    name: String,
+    temperature: f32,
+    moisture: f32,
+    surface: String,
+    subsurface: String,
+    #[serde(default = "default_subsurface_depth")]
+    subsurface_depth: u32,
+    #[serde(default = "default_max_surface_steepness")]
+    max_surface_steepness: f64,
+    #[serde(default)]
+    structures: Vec<String>,
+    #[serde(default = "default_height_multiplier")]
+    height_multiplier: f32,
+    #[serde(default)]
+    height_offset: f32,
+}
+
+fn default_subsurface_depth() -> u32 {
+    3
+}
+
+fn default_max_surface_steepness() -> f64 {
+    0.8
+}
+
+fn default_height_multiplier() -> f32 {
+    1.
+}
+
+#[derive(Copy, Clone, Debug)]
+pub struct Climate {
+    pub temperature: f32,
+    pub moisture: f32,
+}
+
+impl Climate {
+    fn distance_squared(&self, temperature: f32, moisture: f32) -> f32 {
+        (self.temperature - temperature).powi(2) + (self.moisture - moisture).powi(2)
+    }
+}
+
+/// Samples the climate at a world position. Both values are roughly in `0..1`, the temperature
+/// gets colder the higher the terrain is.
+pub struct ClimateNoise<T, M> {
+    temperature: T,
+    moisture: M,
+    sea_level: f64,
+    snow_height: f64,
+}
+
+impl<T: NoiseFn<f64, 2>, M: NoiseFn<f64, 2>> ClimateNoise<T, M> {
+    /// The temperature drops by one every `snow_height` voxels above the sea level.
+    pub fn new(temperature: T, moisture: M, sea_level: f32, snow_height: f32) -> Self {
+        Self {
+            temperature,
+            moisture,
+            sea_level: sea_level as f64,
+            snow_height: snow_height as f64,
+        }
+    }
+
+    pub fn get(&self, point: [f64; 2], height: f64) -> Climate {
+        Climate {
+            temperature: (self.temperature.get(point)
+                - (height - self.sea_level).max(0.) / self.snow_height)
+                as f32,
+            moisture: self.moisture.get(point) as f32,
+        }
+    }
+}
+
+#[derive(Clone)]
+pub struct Biome {
+    pub name: String,
+    pub temperature: f32,
+    pub moisture: f32,
+    pub surface: BlockType,
+    pub subsurface: BlockType,
+    pub subsurface_depth: u32,
+    /// Steeper columns show their stone instead of the surface block.
+    pub max_surface_steepness: f64,
+    /// Names of the structure generators allowed to spawn in this biome.
+    pub structures: Vec<String>,
+    pub height_multiplier: f32,
+    pub height_offset: f32,
+}
+
+/// A layer replacing stone from `min_depth` voxels below the surface downwards.
+#[derive(Clone)]
+pub struct Stratum {
+    pub block: BlockType,
+    pub min_depth: f32,
+}
+
+#[derive(Clone)]
+pub struct Ore {
+    pub block: BlockType,
+    pub min_depth: f32,
+    pub max_depth: f32,
+    /// Rough size of a vein in voxels, the inverse frequency of its noise.
+    pub size: f32,
+    pub threshold: f32,
+    /// Biomes the ore is limited to, or empty for all biomes.
+    pub biomes: Vec<String>,
+}
+
+impl Ore {
+    pub fn is_in_biome(&self, biome: &Biome) -> bool {
+        self.biomes.is_empty() || self.biomes.contains(&biome.name)
+    }
+}
+
+#[derive(Clone)]
+pub struct BiomeRegistry {
+    biomes: Vec<Biome>,
+    /// Sorted by depth, from the surface downwards.
+    pub strata: Vec<Stratum>,
+    pub ores: Vec<Ore>,
+}
+
+impl BiomeRegistry {
+    pub fn load(
+        path: impl AsRef<Path>,
+        block_registry: &BlockRegistry,
+        structure_names: &[&str],
+    ) -> Result<Self, String> {
+        let path = path.as_ref();
+        let file = fs::read_to_string(path)
+            .map_err(|error| format!("Failed to read biome registry {path:?}: {error}"))?;
+
+        Self::parse(&file, block_registry, structure_names)
+            .map_err(|error| format!("Invalid biome registry {path:?}: {error}"))
+    }
+
+    pub fn parse(
+        file: &str,
+        block_registry: &BlockRegistry,
+        structure_names: &[&str],
+    ) -> Result<Self, String> {
+        let file: BiomeRegistryFile = ron::from_str(file).map_err(|error| error.to_string())?;
+
+        let get_block = |biome: &str, name: &str| {
+            block_registry
+                .get_by_name(name)
+                .ok_or_else(|| format!("Biome {biome:?} uses unknown block {name:?}"))
+        };
+
+        let mut biomes: Vec<Biome> = Vec::with_capacity(file.biomes.len());
+
+        for entry in file.biomes {
+            if biomes.iter().any(|biome| biome.name == entry.name) {
+                return Err(format!("Duplicate biome name {:?}", entry.name));
+            }
+
+            if let Some(structure) = entry
+                .structures
+                .iter()
+                .find(|structure| !structure_names.contains(&structure.as_str()))
+            {
+                return Err(format!(
+                    "Biome {:?} uses unknown structure {structure:?}",
+                    entry.name
+                ));
+            }
+
+            biomes.push(Biome {
+                surface: get_block(&entry.name, &entry.surface)?,
+                subsurface: get_block(&entry.name, &entry.subsurface)?,
+                name: entry.name,
+                temperature: entry.temperature,
+                moisture: entry.moisture,
+                subsurface_depth: entry.subsurface_depth,
+                max_surface_steepness: entry.max_surface_steepness,
+                structures: entry.structures,
+                height_multiplier: entry.height_multiplier,
+                height_offset: entry.height_offset,
+            });
+        }
+
+        if biomes.is_empty() {
+            return Err("At least one biome is required".into());
+        }
+
+        let mut strata = file
+            .strata
+            .into_iter()
+            .map(|entry| {
+                Ok(Stratum {
+                    block: get_block("strata", &entry.block)?,
+                    min_depth: entry.min_depth,
+                })
+            })
+            .collect::<Result<Vec<_>, String>>()?;
+        strata.sort_by(|a, b| a.min_depth.total_cmp(&b.min_depth));
+
+        let ores = file
+            .ores
+            .into_iter()
+            .map(|entry| {
+                if let Some(biome) = entry
+                    .biomes
+                    .iter()
+                    .find(|name| !biomes.iter().any(|biome| &biome.name == *name))
+                {
+                    return Err(format!(
+                        "Ore {:?} uses unknown biome {biome:?}",
+                        entry.block
+                    ));
+                }
+
+                Ok(Ore {
+                    block: get_block("ores", &entry.block)?,
+                    min_depth: entry.min_depth,
+                    max_depth: entry.max_depth,
+                    size: entry.size,
+                    threshold: entry.threshold,
+                    biomes: entry.biomes,
+                })
+            })
+            .collect::<Result<Vec<_>, String>>()?;
+
+        Ok(Self {
+            biomes,
+            strata,
+            ores,
+        })
+    }
+
+    /// The biome whose climate is closest to the given one.
+    pub fn get_biome(&self, climate: Climate) -> &Biome {
+        self.biomes
+            .iter()
+            .min_by(|a, b| {
+                climate
+                    .distance_squared(a.temperature, a.moisture)
+                    .total_cmp(&climate.distance_squared(b.temperature, b.moisture))
+            })
+            .unwrap()
+    }
+
+    /// Applies the height modifiers of all biomes, weighted by their climate distance so the
+    /// terrain stays continuous across biome borders.
+    pub fn modify_height(&self, height: f32, sea_level: f32, climate: Climate) -> f32 {
+        let closest_biome = self.get_biome(climate);
+        let closest = climate.distance_squared(closest_biome.temperature, closest_biome.moisture);
+
+        let (multiplier, offset, total_weight) =
+            self.biomes
+                .iter()
+                .fold((0., 0., 0.), |(multiplier, offset, total_weight), biome| {
+                    let distance = climate.distance_squared(biome.temperature, biome.moisture);
+                    let weight = (-(distance - closest) / BIOME_BLEND_DISTANCE.powi(2)).exp();
+                    (
+                        multiplier + biome.height_multiplier * weight,
+                        offset + biome.height_offset * weight,
+                        total_weight + weight,
+                    )
+                });
+
+        (height - sea_level) * (multiplier / total_weight) + sea_level + offset / total_weight
+    }
+
+    pub fn iter(&self) -> impl Iterator<Item = &Biome> {
+        self.biomes.iter()
+    }
+
+    pub fn iter_mut(&mut self) -> impl Iterator<Item = &mut Biome> {
+        self.biomes.iter_mut()
+    }
+
+    pub fn len(&self) -> usize {
+        self.biomes.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.biomes.is_empty()
+    }
+}
@@ -0,0 +1,221 @@
+use crate::world_generation::chunk_generation::light_propagation::MAX_LIGHT;
+use crate::world_generation::chunk_generation::BlockType;
+use bevy::math::IVec3;
+use serde::Deserialize;
+use std::collections::HashMap;
+use std::fs;
+use std::path::Path;
+
+pub const BLOCK_REGISTRY_FILE: &str = "blocks.ron";
+pub const BLOCK_TEXTURE_DIRECTORY: &str = "textures/blocks";
+
+#[derive(Deserialize)]
+struct BlockRegistryFile {
+    blocks: Vec<BlockFileEntry>,
+}
+
+#[derive(Deserialize)]
+struct BlockFileEntry {
+    name: String,
+    #[serde(default)]
+    textures: BlockFileTextures,
+    #[serde(default = "default_solid")]
+    solid: bool,
+    #[serde(default)]
+    render_mode: BlockRenderMode,
+    #[serde(default)]
+    light: u8,
+    map_color: (u8, u8, u8),
+}
+
+#[derive(Deserialize, Default)]
+enum BlockFileTextures {
+    #[default]
+    None,
+    All(String),
+    Faces {
+        top: String,
+        bottom: String,
+        side: String,
+    },
+}
+
+/// How the faces of a block are drawn. Cutout blocks discard pixels with low alpha and stay in the
+/// solid chunk mesh, transparent blocks are blended in a separate mesh.
+#[derive(Deserialize, Copy, Clone, Default, PartialEq, Eq, Debug)]
+pub enum BlockRenderMode {
+    #[default]
+    Opaque,
+    Cutout,
+    Transparent,
+}
+
+fn default_solid() -> bool {
+    true
+}
+
+/// Array texture layers of the faces of a block.
+#[derive(Copy, Clone, Default, PartialEq, Eq)]
+pub struct BlockFaces {
+    pub top: u32,
+    pub bottom: u32,
+    pub side: u32,
+}
+
+impl BlockFaces {
+    pub fn get_layer(&self, direction: IVec3) -> u32 {
+        match direction {
+            IVec3::Y => self.top,
+            IVec3::NEG_Y => self.bottom,
+            _ => self.side,
+        }
+    }
+}
+
+pub struct BlockDefinition {
+    pub name: String,
+    pub faces: BlockFaces,
+    pub solid: bool,
+    pub render_mode: BlockRenderMode,
+    /// Block light the block gives off, up to [`MAX_LIGHT`].
+    pub light: u8,
+    pub map_color: [u8; 3],
+}
+
+pub struct BlockRegistry {
+    blocks: Vec<BlockDefinition>,
+    block_ids: HashMap<String, BlockType>,
+    textures: Vec<String>,
+}
+
+impl BlockRegistry {
+    pub fn load(path: impl AsRef<Path>) -> Result<Self, String> {
+        let path = path.as_ref();
+        let file = fs::read_to_string(path)
+            .map_err(|error| format!("Failed to read block registry {path:?}: {error}"))?;
+
+        Self::parse(&file).map_err(|error| format!("Invalid block registry {path:?}: {error}"))
+    }
+
+    pub fn parse(file: &str) -> Result<Self, String> {
+        let file: BlockRegistryFile = ron::from_str(file).map_err(|error| error.to_string())?;
+
+        if file.blocks.len() > u8::MAX as usize + 1 {
+            return Err(format!("Too many blocks: {}", file.blocks.len()));
+        }
+
+        let mut registry = Self {
+            blocks: Vec::with_capacity(file.blocks.len()),
+            block_ids: HashMap::new(),
+            textures: vec![],
+        };
+
+        for entry in file.blocks {
+            if entry.light > MAX_LIGHT {
+                return Err(format!(
+                    "Block {:?} is brighter than the maximum light of {MAX_LIGHT}",
+                    entry.name
+                ));
+            }
+
+            let faces = match &entry.textures {
+                BlockFileTextures::None => BlockFaces::default(),
+                BlockFileTextures::All(texture) => {
+                    let layer = registry.get_texture_layer(texture);
+                    BlockFaces {
+                        top: layer,
+                        bottom: layer,
+                        side: layer,
+                    }
+                }
+                BlockFileTextures::Faces { top, bottom, side } => BlockFaces {
+                    top: registry.get_texture_layer(top),
+                    bottom: registry.get_texture_layer(bottom),
+                    side: registry.get_texture_layer(side),
+                },
+            };
+
+            let block_type = BlockType(registry.blocks.len() as u8);
+            if registry
+                .block_ids
+                .insert(entry.name.clone(), block_type)
+                .is_some()
+            {
+                return Err(format!("Duplicate block name {:?}", entry.name));
+            }
+
+            registry.blocks.push(BlockDefinition {
+                name: entry.name,
+                faces,
+                solid: entry.solid,
+                render_mode: entry.render_mode,
+                light: entry.light,
+                map_color: [entry.map_color.0, entry.map_color.1, entry.map_color.2],
+            });
+        }
+
+        for (block_type, name) in BlockType::BUILTIN {
+            if registry.get_name(block_type) != Some(name) {
+                return Err(format!(
+                    "Block {} has to be {name:?}, as the generator relies on it",
+                    block_type.id()
+                ));
+            }
+        }
+
+        if registry.textures.is_empty() {
+            return Err("At least one block needs a texture".into());
+        }
+
+        Ok(registry)
+    }
+
+    fn get_texture_layer(&mut self, texture: &str) -> u32 {
+        match self.textures.iter().position(|name| name == texture) {
+            Some(layer) => layer as u32,
+            None => {
+                self.textures.push(texture.to_string());
+                self.textures.len() as u32 - 1
+            }
+        }
+    }
+
+    pub fn get(&self, block_type: BlockType) -> &BlockDefinition {
+        &self.blocks[block_type.id() as usize]
+    }
+
+    pub fn try_get(&self, block_type: BlockType) -> Option<&BlockDefinition> {
+        self.blocks.get(block_type.id() as usize)
+    }
+
+    pub fn get_name(&self, block_type: BlockType) -> Option<&str> {
+        self.try_get(block_type).map(|block| block.name.as_str())
+    }
+
+    pub fn get_by_name(&self, name: &str) -> Option<BlockType> {
+        self.block_ids.get(name).copied()
+    }
+
+    pub fn iter(&self) -> impl Iterator<Item = (BlockType, &BlockDefinition)> {
+        self.blocks
+            .iter()
+            .enumerate()
+            .map(|(id, block)| (BlockType(id as u8), block))
+    }
+
+    pub fn len(&self) -> usize {
+        self.blocks.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.blocks.is_empty()
+    }
+
+    /// Asset paths of the array texture layers, in layer order.
+    pub fn get_texture_paths(&self) -> Vec<String> {
+        self.textures
+            .iter()
+            .map(|texture| format!("{BLOCK_TEXTURE_DIRECTORY}/{texture}.png"))
+            .collect()
+    }
+}
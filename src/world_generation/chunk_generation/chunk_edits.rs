@@ -0,0 +1,155 @@
+use crate::world_generation::chunk_generation::{chunk_size, BlockType};
+use crate::world_generation::voxel_world::{max_lod, ChunkLod};
+use bevy::math::{IVec2, IVec3};
+use bevy::prelude::Resource;
+use std::collections::HashMap;
+use std::sync::Arc;
+
+use super::voxel_types::VoxelData;
+
+/// Blocks placed or broken by the player inside one quad tree root chunk, keyed by world voxel
+/// position.
+#[derive(Clone, Default)]
+pub struct ChunkEdits {
+    pub blocks: HashMap<IVec3, BlockType>,
+}
+
+impl ChunkEdits {
+    /// Writes every edit that falls into the (padded) voxel area of the given chunk. On coarser
+    /// lods the edits are gathered per cell: a cell takes the block placed most often in it, and
+    /// only turns into air once more than half of its voxels are broken.
+    pub fn apply(
+        &self,
+        blocks: &mut VoxelData,
+        chunk_pos: IVec3,
+        min_height: i32,
+        chunk_lod: ChunkLod,
+    ) -> usize {
+        let mut cells: HashMap<IVec3, HashMap<BlockType, i32>> = HashMap::new();
+
+        for (position, block) in &self.blocks {
+            let local = Self::get_local_position(*position, chunk_pos, min_height, chunk_lod);
+
+            if local.min_element() < 0 || local.max_element() >= chunk_size() as i32 + 2 {
+                continue;
+            }
+
+            *cells.entry(local).or_default().entry(*block).or_default() += 1;
+        }
+
+        let cell_volume = chunk_lod.multiplier_i32().pow(3);
+        let mut applied = 0;
+
+        for (local, block_counts) in cells {
+            let placed = block_counts
+                .iter()
+                .filter(|(block, _)| **block != BlockType::AIR)
+                .max_by_key(|(block, count)| (**count, block.id()));
+
+            let block = match placed {
+                Some((block, _)) => *block,
+                None if block_counts[&BlockType::AIR] * 2 > cell_volume => BlockType::AIR,
+                None => continue,
+            };
+
+            if blocks.get_block(local) != block {
+                blocks.set_block(local, block);
+                applied += 1;
+            }
+        }
+
+        applied
+    }
+
+    /// Whether a block is placed in the columns of the given chunk above its voxel area, so the
+    /// chunk above it has to be generated even if the terrain doesn't reach it.
+    pub fn reaches_above(&self, chunk_pos: IVec3, min_height: i32, chunk_lod: ChunkLod) -> bool {
+        let size = chunk_size() as i32;
+
+        self.blocks.iter().any(|(position, block)| {
+            let local = Self::get_local_position(*position, chunk_pos, min_height, chunk_lod);
+
+            *block != BlockType::AIR
+                && local.x >= 1
+                && local.x <= size
+                && local.z >= 1
+                && local.z <= size
+                && local.y > size
+        })
+    }
+
+    /// Position of the cell containing the world voxel position in the padded voxels of the
+    /// given chunk.
+    fn get_local_position(
+        position: IVec3,
+        chunk_pos: IVec3,
+        min_height: i32,
+        chunk_lod: ChunkLod,
+    ) -> IVec3 {
+        let multiplier = chunk_lod.multiplier_i32();
+        let origin = chunk_pos * chunk_size() as i32;
+
+        IVec3::new(
+            (position.x - origin.x).div_euclid(multiplier),
+            position.y.div_euclid(multiplier) - min_height,
+            (position.z - origin.z).div_euclid(multiplier),
+        )
+    }
+}
+
+#[derive(Resource, Default)]
+pub struct BlockEdits {
+    chunk_edits: HashMap<[i32; 2], Arc<ChunkEdits>>,
+}
+
+impl BlockEdits {
+    pub fn set_block(&mut self, position: IVec3, block: BlockType) {
+        let parent_pos = Self::get_parent_position(position);
+        let chunk_edits = self.chunk_edits.entry(parent_pos).or_default();
+        Arc::make_mut(chunk_edits).blocks.insert(position, block);
+    }
+
+    pub fn insert_chunk_edits(&mut self, parent_pos: [i32; 2], chunk_edits: ChunkEdits) {
+        self.chunk_edits.insert(parent_pos, Arc::new(chunk_edits));
+    }
+
+    pub fn get_chunk_edits(&self, parent_pos: [i32; 2]) -> Option<&Arc<ChunkEdits>> {
+        self.chunk_edits.get(&parent_pos)
+    }
+
+    /// Returns the overlays of the given root chunk and its eight neighbours, as edits close to
+    /// the border also end up in the padding of the neighbouring chunks.
+    pub fn get_surrounding_edits(&self, parent_pos: IVec2) -> Vec<Arc<ChunkEdits>> {
+        let mut edits = vec![];
+
+        for x in -1..=1 {
+            for z in -1..=1 {
+                if let Some(chunk_edits) =
+                    self.get_chunk_edits([parent_pos.x + x, parent_pos.y + z])
+                {
+                    edits.push(chunk_edits.clone());
+                }
+            }
+        }
+
+        edits
+    }
+
+    pub fn iter(&self) -> impl Iterator<Item = (&[i32; 2], &Arc<ChunkEdits>)> {
+        self.chunk_edits.iter()
+    }
+
+    pub fn get_parent_position(position: IVec3) -> [i32; 2] {
+        Self::get_chunk_position(position)
+            .div_euclid(IVec2::splat(max_lod().multiplier_i32()))
+            .to_array()
+    }
+
+    /// Full lod chunk whose voxels, without the padding, contain the world voxel position.
+    pub fn get_chunk_position(position: IVec3) -> IVec2 {
+        IVec2::new(
+            (position.x - 1).div_euclid(chunk_size() as i32),
+            (position.z - 1).div_euclid(chunk_size() as i32),
+        )
+    }
+}
@@ -0,0 +1,201 @@
+use crate::world_generation::chunk_generation::chunk_size;
+use crate::world_generation::chunk_generation::noise::lod_height_adjuster::from_lod_height;
+use crate::world_generation::generation_options::GenerationOptions;
+use crate::world_generation::voxel_world::ChunkLod;
+use bevy::math::{IVec2, IVec3, Vec3};
+use noise::{Abs, Max, NoiseFn, ScaleBias, ScalePoint, Simplex};
+use rand::prelude::StdRng;
+use rand::{Rng, SeedableRng};
+
+/// Caves are only carved this many full lod voxels below the terrain surface, so the bottom of
+/// the generated terrain stays closed.
+pub const CAVE_DEPTH: f32 = 48.;
+/// How far overhangs may reach above or below the heightmap, in full lod voxels.
+pub const OVERHANG_AMPLITUDE: f32 = 12.;
+
+/// Tunnels are a few voxels wide, so they would only produce noise at coarser lods.
+const CAVE_MAX_LOD: ChunkLod = ChunkLod::Quarter;
+const OVERHANG_MAX_LOD: ChunkLod = ChunkLod::Eighth;
+
+const DENSITY_GRID_STEP: usize = 4;
+/// Covers the chunk and a few voxels above it, which decide the surface blocks at its top.
+fn density_grid_size() -> usize {
+    (chunk_size() + 2 + 8) / DENSITY_GRID_STEP + 1
+}
+
+/// Positive inside of caves. Two thin noise bands crossing each other form tunnels, the peaks of
+/// a third noise form larger chambers.
+pub fn get_cave_noise(generation_options: &GenerationOptions) -> impl NoiseFn<f64, 3> {
+    let mut rng = StdRng::seed_from_u64(generation_options.seed + 6);
+
+    let tunnel_noise = |seed: u32| {
+        Abs::new(
+            ScalePoint::new(Simplex::new(seed))
+                .set_x_scale(1. / 96.)
+                .set_y_scale(1. / 48.)
+                .set_z_scale(1. / 96.),
+        )
+    };
+
+    Max::new(
+        ScaleBias::new(Max::new(
+            tunnel_noise(rng.random()),
+            tunnel_noise(rng.random()),
+        ))
+        .set_scale(-1.)
+        .set_bias(0.07),
+        ScaleBias::new(ScalePoint::new(Simplex::new(rng.random())).set_scale(1. / 80.))
+            .set_bias(-0.65),
+    )
+}
+
+/// Vertical displacement of the terrain surface, roughly in `-1..1`.
+pub fn get_overhang_noise(generation_options: &GenerationOptions) -> impl NoiseFn<f64, 3> {
+    let mut rng = StdRng::seed_from_u64(generation_options.seed + 7);
+
+    ScalePoint::new(Simplex::new(rng.random()))
+        .set_x_scale(1. / 28.)
+        .set_y_scale(1. / 20.)
+        .set_z_scale(1. / 28.)
+}
+
+/// Samples a 3D noise every few voxels of a chunk and interpolates between the samples.
+pub struct DensityGrid {
+    values: Vec<f32>,
+}
+
+impl DensityGrid {
+    pub fn new(
+        noise: &impl NoiseFn<f64, 3>,
+        chunk_origin: IVec2,
+        min_height: i32,
+        chunk_lod: ChunkLod,
+    ) -> Self {
+        let grid_size = density_grid_size();
+        let mut values = Vec::with_capacity(grid_size.pow(3));
+
+        for x in 0..grid_size {
+            for y in 0..grid_size {
+                for z in 0..grid_size {
+                    let voxel = IVec3::new(x as i32, y as i32, z as i32) * DENSITY_GRID_STEP as i32;
+                    values.push(noise.get([
+                        (chunk_origin.x + voxel.x * chunk_lod.multiplier_i32()) as f64,
+                        from_lod_height((voxel.y + min_height) as f64, chunk_lod),
+                        (chunk_origin.y + voxel.z * chunk_lod.multiplier_i32()) as f64,
+                    ]) as f32);
+                }
+            }
+        }
+
+        Self { values }
+    }
+
+    fn get_sample(&self, cell: IVec3) -> f32 {
+        let cell = cell.as_uvec3();
+        self.values[(cell.x as usize * density_grid_size() + cell.y as usize) * density_grid_size()
+            + cell.z as usize]
+    }
+
+    /// Trilinear interpolation at a voxel position inside of the chunk.
+    pub fn get(&self, position: IVec3) -> f32 {
+        let position = position.clamp(
+            IVec3::ZERO,
+            IVec3::splat(((density_grid_size() - 1) * DENSITY_GRID_STEP) as i32 - 1),
+        );
+        let cell = position / DENSITY_GRID_STEP as i32;
+        let t = (position - cell * DENSITY_GRID_STEP as i32).as_vec3() / DENSITY_GRID_STEP as f32;
+
+        let mut value = 0.;
+        for corner in 0..8 {
+            let offset = IVec3::new(corner & 1, (corner >> 1) & 1, corner >> 2);
+            let weight = Vec3::select(offset.cmpeq(IVec3::ZERO), Vec3::ONE - t, t);
+            value += self.get_sample(cell + offset) * weight.x * weight.y * weight.z;
+        }
+        value
+    }
+}
+
+/// The 3D density pass of a chunk, adding overhangs to the heightmap and carving caves into it.
+pub struct ChunkDensity {
+    min_height: i32,
+    chunk_lod: ChunkLod,
+    caves: Option<DensityGrid>,
+    overhangs: Option<DensityGrid>,
+}
+
+impl ChunkDensity {
+    pub fn new(
+        generation_options: &GenerationOptions,
+        chunk_origin: IVec2,
+        min_height: i32,
+        chunk_lod: ChunkLod,
+    ) -> Self {
+        let enabled = |max_lod: ChunkLod| {
+            generation_options.generate_density && chunk_lod.usize() <= max_lod.usize()
+        };
+
+        Self {
+            min_height,
+            chunk_lod,
+            caves: enabled(CAVE_MAX_LOD).then(|| {
+                DensityGrid::new(
+                    &get_cave_noise(generation_options),
+                    chunk_origin,
+                    min_height,
+                    chunk_lod,
+                )
+            }),
+            overhangs: enabled(OVERHANG_MAX_LOD).then(|| {
+                DensityGrid::new(
+                    &get_overhang_noise(generation_options),
+                    chunk_origin,
+                    min_height,
+                    chunk_lod,
+                )
+            }),
+        }
+    }
+
+    /// How far the terrain of a column with the full overhang strength may rise above its
+    /// height, in lod voxels.
+    pub fn get_overhang_height(&self) -> f32 {
+        match self.overhangs {
+            Some(_) => OVERHANG_AMPLITUDE / self.chunk_lod.multiplier_f32(),
+            None => 0.,
+        }
+    }
+
+    /// Whether the voxel at `y` is solid, given the heightmap height of its column. Without
+    /// overhangs this matches filling the column up to `noise_height`.
+    pub fn is_solid(
+        &self,
+        x: usize,
+        y: i32,
+        z: usize,
+        noise_height: f32,
+        overhang_strength: f32,
+    ) -> bool {
+        let mut density = noise_height.floor() - y as f32 - 0.5;
+
+        if let Some(overhangs) = &self.overhangs {
+            if overhang_strength > 0. {
+                density += overhangs.get(IVec3::new(x as i32, y - self.min_height, z as i32))
+                    * overhang_strength
+                    * self.get_overhang_height();
+            }
+        }
+
+        density > 0.
+    }
+
+    pub fn is_cave(&self, x: usize, y: i32, z: usize) -> bool {
+        self.caves.as_ref().is_some_and(|caves| {
+            caves.get(IVec3::new(x as i32, y - self.min_height, z as i32)) > 0.
+        })
+    }
+
+    /// Lowest lod height caves may reach below the given surface height.
+    pub fn get_cave_floor(&self, noise_height: f32) -> f32 {
+        noise_height - CAVE_DEPTH / self.chunk_lod.multiplier_f32()
+    }
+}
@@ -0,0 +1,488 @@
+use crate::utils::div_floor;
+use crate::world_generation::chunk_generation::structure_generator::{
+    StructureGenerator, StructureGeneratorCache,
+};
+use crate::world_generation::chunk_generation::voxel_generation::{
+    get_min_distance_to_path, ChunkTerrain,
+};
+use crate::world_generation::chunk_generation::{chunk_size, BlockType};
+use bevy::math::{IVec2, IVec3};
+use noise::NoiseFn;
+use rand::prelude::StdRng;
+use rand::{Rng, SeedableRng};
+
+use super::voxel_types::VoxelData;
+
+/// One step of turning the shaped columns of a chunk into voxels. Every pass works on top of the
+/// voxels of the passes before it in the [`GenerationPipeline`].
+pub trait GenerationPass: Send + Sync {
+    /// Identifies the pass when other passes are inserted next to it or it is removed.
+    fn name(&self) -> &str;
+
+    fn generate(&self, terrain: &ChunkTerrain, chunk: &mut GeneratingChunk);
+}
+
+/// The voxels of a chunk while its generation passes run.
+#[derive(Default)]
+pub struct GeneratingChunk {
+    pub blocks: VoxelData,
+    /// Set once anything reaches the top of the padded chunk, so the chunk above has to be
+    /// generated as well.
+    pub generate_above: bool,
+    /// Ground voxels close enough to the top of the ground to get surface blocks.
+    pub surface_voxels: Vec<SurfaceVoxel>,
+}
+
+pub struct SurfaceVoxel {
+    pub position: IVec3,
+    /// Index into [`ChunkTerrain::columns`].
+    pub column: usize,
+    /// Ground voxels above this one plus one, so the topmost voxel has a depth of 1.
+    pub depth: i32,
+}
+
+/// Ordered passes that generate the voxels of every chunk. The default pipeline fills the
+/// terrain, covers it with the surface blocks of its biome, lays out paths, places structures
+/// and finally decorates the underground with ores.
+pub struct GenerationPipeline {
+    passes: Vec<Box<dyn GenerationPass>>,
+}
+
+impl Default for GenerationPipeline {
+    fn default() -> Self {
+        Self {
+            passes: vec![
+                Box::new(HeightFillPass),
+                Box::new(SurfacePass),
+                Box::new(PathPass),
+                Box::new(StructurePass),
+                Box::new(DecorationPass),
+            ],
+        }
+    }
+}
+
+impl GenerationPipeline {
+    pub fn empty() -> Self {
+        Self { passes: vec![] }
+    }
+
+    pub fn push(&mut self, pass: impl GenerationPass + 'static) {
+        self.passes.push(Box::new(pass));
+    }
+
+    /// Inserts the pass in front of the pass with the given name. Returns false and leaves the
+    /// pipeline as is if there is no such pass.
+    pub fn insert_before(&mut self, name: &str, pass: impl GenerationPass + 'static) -> bool {
+        match self.position(name) {
+            Some(index) => {
+                self.passes.insert(index, Box::new(pass));
+                true
+            }
+            None => false,
+        }
+    }
+
+    /// Inserts the pass behind the pass with the given name. Returns false and leaves the
+    /// pipeline as is if there is no such pass.
+    pub fn insert_after(&mut self, name: &str, pass: impl GenerationPass + 'static) -> bool {
+        match self.position(name) {
+            Some(index) => {
+                self.passes.insert(index + 1, Box::new(pass));
+                true
+            }
+            None => false,
+        }
+    }
+
+    pub fn remove(&mut self, name: &str) -> Option<Box<dyn GenerationPass>> {
+        self.position(name).map(|index| self.passes.remove(index))
+    }
+
+    /// Moves the pass with the given name to a new index, e.g. to run it before another pass.
+    pub fn move_to(&mut self, name: &str, index: usize) -> bool {
+        match self.remove(name) {
+            Some(pass) => {
+                self.passes.insert(index.min(self.passes.len()), pass);
+                true
+            }
+            None => false,
+        }
+    }
+
+    pub fn position(&self, name: &str) -> Option<usize> {
+        self.passes.iter().position(|pass| pass.name() == name)
+    }
+
+    pub fn names(&self) -> impl Iterator<Item = &str> {
+        self.passes.iter().map(|pass| pass.name())
+    }
+
+    pub fn iter(&self) -> impl Iterator<Item = &dyn GenerationPass> {
+        self.passes.iter().map(|pass| pass.as_ref())
+    }
+}
+
+/// Fills every column with stone and strata up to its height, leaves out caves and fills water
+/// up to the water level.
+pub struct HeightFillPass;
+
+impl GenerationPass for HeightFillPass {
+    fn name(&self) -> &str {
+        "heightfill"
+    }
+
+    fn generate(&self, terrain: &ChunkTerrain, chunk: &mut GeneratingChunk) {
+        let min_height = terrain.min_height;
+        let chunk_top = terrain.get_chunk_top();
+
+        for (index, column) in terrain.columns.iter().enumerate() {
+            let subsurface_depth =
+                column.biome.subsurface_depth as i32 / terrain.chunk_lod.multiplier_i32();
+
+            // The column is filled top down, so surface voxels know how much ground lies above.
+            let mut depth = 0;
+            for y in
+                (min_height..(column.column_top as i32).min(chunk_top + subsurface_depth)).rev()
+            {
+                if !terrain.density.is_solid(
+                    column.x,
+                    y,
+                    column.z,
+                    column.noise_height,
+                    column.overhang_strength,
+                ) {
+                    depth = 0;
+                    continue;
+                }
+                depth += 1;
+
+                if y >= chunk_top {
+                    continue;
+                }
+                if y == chunk_top - 1 {
+                    chunk.generate_above = true;
+                }
+                if (y as f32) >= column.cave_floor
+                    && (y as f32) < column.cave_ceiling
+                    && terrain.density.is_cave(column.x, y, column.z)
+                {
+                    continue;
+                }
+
+                let position = IVec3::new(column.x as i32, y - min_height, column.z as i32);
+
+                chunk.blocks.set_block(
+                    position,
+                    terrain
+                        .strata
+                        .get_stratum(y, column.noise_height, column.strata_wobble),
+                );
+
+                if depth <= subsurface_depth.max(1) {
+                    chunk.surface_voxels.push(SurfaceVoxel {
+                        position,
+                        column: index,
+                        depth,
+                    });
+                }
+            }
+
+            if let Some(water_height) = column.water_height {
+                for y in (column.noise_height as i32).max(min_height)
+                    ..water_height.min(chunk_top as f32) as i32
+                {
+                    if y == chunk_top - 1 {
+                        chunk.generate_above = true;
+                    }
+                    chunk.blocks.set_block(
+                        [column.x as i32, y - min_height, column.z as i32],
+                        BlockType::WATER,
+                    );
+                }
+            }
+        }
+    }
+}
+
+/// Covers the ground of flat enough columns with the surface and subsurface blocks of their
+/// biome. Ground under water only gets the subsurface block.
+pub struct SurfacePass;
+
+impl GenerationPass for SurfacePass {
+    fn name(&self) -> &str {
+        "surface"
+    }
+
+    fn generate(&self, terrain: &ChunkTerrain, chunk: &mut GeneratingChunk) {
+        for surface_voxel in &chunk.surface_voxels {
+            let column = &terrain.columns[surface_voxel.column];
+
+            if column.steepness >= column.biome.max_surface_steepness {
+                continue;
+            }
+
+            chunk.blocks.set_block(
+                surface_voxel.position,
+                if surface_voxel.depth == 1 && column.water_height.is_none() {
+                    column.biome.surface
+                } else {
+                    column.biome.subsurface
+                },
+            );
+        }
+    }
+}
+
+/// Turns the ground of the columns on a path into path blocks. The terrain was already flattened
+/// along the paths while the columns were shaped.
+pub struct PathPass;
+
+impl GenerationPass for PathPass {
+    fn name(&self) -> &str {
+        "paths"
+    }
+
+    fn generate(&self, terrain: &ChunkTerrain, chunk: &mut GeneratingChunk) {
+        for column in terrain.columns.iter().filter(|column| column.is_path) {
+            for y in 0..chunk_size() as i32 + 2 {
+                let position = [column.x as i32, y, column.z as i32];
+                let block = chunk.blocks.get_block(position);
+
+                if block != BlockType::AIR && block != BlockType::WATER {
+                    chunk.blocks.set_block(position, BlockType::PATH);
+                }
+            }
+        }
+    }
+}
+
+/// Places the models of the structure generators in [`GenerationOptions`] on a grid, skipping
+/// spots that are too steep, wet, close to paths or in a biome without the structure.
+///
+/// [`GenerationOptions`]: crate::world_generation::generation_options::GenerationOptions
+pub struct StructurePass;
+
+impl GenerationPass for StructurePass {
+    fn name(&self) -> &str {
+        "structures"
+    }
+
+    fn generate(&self, terrain: &ChunkTerrain, chunk: &mut GeneratingChunk) {
+        let chunk_lod = terrain.chunk_lod;
+        let min_height = terrain.min_height;
+
+        let structure_generators: Vec<StructureGeneratorCache> = terrain
+            .generation_options
+            .structure_generators
+            .iter()
+            .map(StructureGeneratorCache::new)
+            .collect();
+
+        for column in &terrain.columns {
+            let (x, z) = (column.x, column.z);
+            let (total_x, total_z) = (column.total_x, column.total_z);
+
+            for structure_generator in &structure_generators {
+                let structure_metadata = structure_generator.get_structure_metadata();
+                let structure_offset_x = div_floor(
+                    total_x + structure_metadata.grid_offset[0],
+                    structure_metadata.generation_size[0],
+                );
+                let structure_offset_z = div_floor(
+                    total_z + structure_metadata.grid_offset[1],
+                    structure_metadata.generation_size[1],
+                );
+                let structure_value = structure_metadata
+                    .noise
+                    .get_noise_2d(structure_offset_x as f32, structure_offset_z as f32)
+                    * 0.5
+                    + 0.5;
+                if structure_metadata.generate_debug_blocks {
+                    let top_terrain = (column
+                        .noise_height
+                        .min(chunk_size() as f32 + min_height as f32)
+                        as i32
+                        - min_height.min(column.noise_height as i32))
+                    .max(1) as usize
+                        - 1;
+                    chunk
+                        .blocks
+                        .set_block([x as i32, top_terrain as i32, z as i32], BlockType::STONE);
+                }
+                let mut rand = StdRng::seed_from_u64((structure_value.abs() * 10000.) as u64);
+
+                if structure_value > 0. {
+                    let random_x = rand.random_range(
+                        0..=structure_metadata.generation_size[0]
+                            - structure_metadata.model_size[0],
+                    );
+                    let random_z = rand.random_range(
+                        0..=structure_metadata.generation_size[1]
+                            - structure_metadata.model_size[2],
+                    );
+
+                    let structure_x: i32 = (total_x + structure_metadata.grid_offset[0]
+                        - structure_offset_x * structure_metadata.generation_size[0])
+                        .abs()
+                        - random_x;
+                    let structure_z: i32 = (total_z + structure_metadata.grid_offset[1]
+                        - structure_offset_z * structure_metadata.generation_size[1])
+                        .abs()
+                        - random_z;
+
+                    if structure_x < 0
+                        || structure_z < 0
+                        || structure_x >= structure_metadata.model_size[0]
+                        || structure_z >= structure_metadata.model_size[2]
+                    {
+                        continue;
+                    }
+
+                    let structure_noise_height_x = structure_offset_x
+                        * structure_metadata.generation_size[0]
+                        + (structure_metadata.model_size[0] / 2)
+                        - structure_metadata.grid_offset[0]
+                        + random_x;
+                    let structure_noise_height_z = structure_offset_z
+                        * structure_metadata.generation_size[1]
+                        + (structure_metadata.model_size[2] / 2)
+                        - structure_metadata.grid_offset[1]
+                        + random_z;
+
+                    let structure_steepness = terrain.terrain_steepness.get([
+                        structure_noise_height_x as f64,
+                        structure_noise_height_z as f64,
+                    ]);
+
+                    if structure_steepness
+                        > terrain
+                            .generation_options
+                            .terrain_settings
+                            .structure_max_steepness
+                    {
+                        continue;
+                    }
+
+                    let structure_center: IVec2 =
+                        [structure_noise_height_x, structure_noise_height_z].into();
+
+                    if !terrain
+                        .get_biome(structure_center.as_dvec2().to_array())
+                        .structures
+                        .contains(&structure_metadata.name)
+                    {
+                        continue;
+                    }
+
+                    let (a, _, _, _) = get_min_distance_to_path(
+                        structure_center,
+                        &terrain.paths,
+                        IVec2::new(
+                            structure_metadata.model_size[0] / 2,
+                            structure_metadata.model_size[2] / 2,
+                        ) + IVec2::ONE * 10,
+                    );
+
+                    if (a as i32)
+                        < structure_metadata.model_size[0] / 2
+                            + structure_metadata.model_size[1] / 2
+                    {
+                        continue;
+                    }
+
+                    let noise_height = terrain.terrain_noise.get([
+                        structure_noise_height_x as f64,
+                        structure_noise_height_z as f64,
+                    ]);
+
+                    if !terrain
+                        .water_map
+                        .is_dry(structure_center, noise_height as f32, chunk_lod)
+                    {
+                        continue;
+                    }
+
+                    for (index, sub_structure) in structure_generator.get_structure_model(
+                        IVec2 {
+                            x: structure_offset_x,
+                            y: structure_offset_z,
+                        },
+                        chunk_lod,
+                    )[structure_x as usize]
+                        .iter()
+                        .enumerate()
+                    {
+                        if (index as i32
+                            + (noise_height * chunk_lod.multiplier_i32() as f64) as i32)
+                            % chunk_lod.multiplier_i32()
+                            != 0
+                        {
+                            continue;
+                        }
+                        let chunk_index = index / chunk_lod.multiplier_i32() as usize;
+                        if (noise_height as i32 - min_height + chunk_index as i32) < 0 {
+                            continue;
+                        }
+                        let structure_block = sub_structure[structure_z as usize];
+                        if structure_block == BlockType::AIR {
+                            continue;
+                        }
+                        if noise_height as i32 + chunk_index as i32 - min_height
+                            >= chunk_size() as i32 + 2
+                        {
+                            chunk.generate_above = true;
+                            break;
+                        }
+                        chunk.blocks.set_block(
+                            [
+                                x as i32,
+                                noise_height as i32 + chunk_index as i32 - min_height,
+                                z as i32,
+                            ],
+                            structure_block,
+                        );
+                    }
+                }
+            }
+        }
+    }
+}
+
+/// Scatters the ore veins of the biome registry through the stone and strata below the surface.
+pub struct DecorationPass;
+
+impl GenerationPass for DecorationPass {
+    fn name(&self) -> &str {
+        "decoration"
+    }
+
+    fn generate(&self, terrain: &ChunkTerrain, chunk: &mut GeneratingChunk) {
+        if !terrain.strata.generates_ores() {
+            return;
+        }
+
+        for column in &terrain.columns {
+            let column_top =
+                (column.column_top as i32 - terrain.min_height).min(chunk_size() as i32 + 2);
+
+            for y in 0..column_top {
+                let position = [column.x as i32, y, column.z as i32];
+
+                if !terrain.strata.is_stratum(chunk.blocks.get_block(position)) {
+                    continue;
+                }
+
+                if let Some(ore) = terrain.strata.get_ore(
+                    column.total_x,
+                    y + terrain.min_height,
+                    column.total_z,
+                    column.noise_height,
+                    column.biome,
+                ) {
+                    chunk.blocks.set_block(position, ore);
+                }
+            }
+        }
+    }
+}
@@ -0,0 +1,318 @@
+use crate::world_generation::chunk_generation::block_registry::{BlockRegistry, BlockRenderMode};
+use crate::world_generation::chunk_generation::{chunk_size, BlockType};
+use bevy::math::IVec3;
+use std::collections::VecDeque;
+
+use super::voxel_types::VoxelData;
+
+/// Light of open sky and of the brightest light blocks.
+pub const MAX_LIGHT: u8 = 15;
+
+fn padded_chunk_size() -> i32 {
+    chunk_size() as i32 + 2
+}
+
+const DIRECTIONS: [IVec3; 6] = [
+    IVec3::X,
+    IVec3::NEG_X,
+    IVec3::Z,
+    IVec3::NEG_Z,
+    IVec3::Y,
+    IVec3::NEG_Y,
+];
+
+/// Sky and block light of a voxel, packed into one byte with the sky light in the upper four
+/// bits.
+#[derive(Copy, Clone, Debug, Default, PartialEq, Eq, Hash)]
+pub struct Light(pub u8);
+
+impl Light {
+    pub const DARK: Self = Self(0);
+    pub const SKY: Self = Self(MAX_LIGHT << 4);
+
+    pub fn new(sky: u8, block: u8) -> Self {
+        Self((sky << 4) | block)
+    }
+
+    pub fn sky(self) -> u8 {
+        self.0 >> 4
+    }
+
+    pub fn block(self) -> u8 {
+        self.0 & 0xF
+    }
+
+    fn get(self, channel: LightChannel) -> u8 {
+        match channel {
+            LightChannel::Sky => self.sky(),
+            LightChannel::Block => self.block(),
+        }
+    }
+
+    fn with(self, channel: LightChannel, value: u8) -> Self {
+        match channel {
+            LightChannel::Sky => Self::new(value, self.block()),
+            LightChannel::Block => Self::new(self.sky(), value),
+        }
+    }
+}
+
+#[derive(Copy, Clone, PartialEq, Eq)]
+enum LightChannel {
+    Sky,
+    Block,
+}
+
+impl LightChannel {
+    const ALL: [Self; 2] = [Self::Sky, Self::Block];
+}
+
+/// Floods sky and block light through a padded chunk, as if nothing above it blocked the sky.
+/// The padding is lit as well, which is a fair guess until the neighbouring chunks are loaded and
+/// their real light is copied in with [`update_light`].
+///
+/// Only full lod chunks are lit. Everything else keeps the open sky light of unlit voxels, caves
+/// can't be seen from that far anyway.
+pub fn light_chunk(blocks: &mut VoxelData, block_registry: &BlockRegistry) {
+    if let Some(block) = blocks.get_single_block() {
+        if block == BlockType::AIR {
+            blocks.fill_light(Light::SKY);
+            return;
+        }
+
+        if !passes_light(block, block_registry) && block_registry.get(block).light == 0 {
+            blocks.fill_light(Light::DARK);
+            return;
+        }
+    }
+
+    blocks.fill_light(Light::DARK);
+
+    let mut sky_queue = VecDeque::new();
+    let mut block_queue = VecDeque::new();
+
+    for x in 0..padded_chunk_size() {
+        for z in 0..padded_chunk_size() {
+            let mut sky_light = MAX_LIGHT;
+
+            for y in (0..padded_chunk_size()).rev() {
+                let position = IVec3::new(x, y, z);
+                let block = blocks.get_block(position);
+
+                sky_light = if passes_light(block, block_registry) {
+                    get_spread_light(LightChannel::Sky, sky_light, IVec3::NEG_Y, block)
+                } else {
+                    0
+                };
+                let block_light = block_registry.get(block).light;
+
+                if sky_light > 0 || block_light > 0 {
+                    blocks.set_light(position, Light::new(sky_light, block_light));
+                }
+
+                if sky_light > 1 {
+                    sky_queue.push_back(position);
+                }
+
+                if block_light > 1 {
+                    block_queue.push_back(position);
+                }
+            }
+        }
+    }
+
+    spread_light(
+        blocks,
+        block_registry,
+        LightChannel::Sky,
+        sky_queue,
+        is_in_padded_chunk,
+    );
+    spread_light(
+        blocks,
+        block_registry,
+        LightChannel::Block,
+        block_queue,
+        is_in_padded_chunk,
+    );
+
+    blocks.compact_light();
+}
+
+/// Spreads changes to the light of single voxels through the chunk. `changes` holds every voxel
+/// whose light or block changed, with the light it had before. Their current light is kept and
+/// only the voxels inside the chunk are updated, as the padding mirrors the neighbouring chunks
+/// and is their business.
+///
+/// Light that came from a darkened voxel is removed first and then filled in again from
+/// everything still lit around it.
+pub fn update_light(
+    blocks: &mut VoxelData,
+    block_registry: &BlockRegistry,
+    changes: &[(IVec3, Light)],
+) {
+    for channel in LightChannel::ALL {
+        let mut removals = VecDeque::new();
+        let mut relight = VecDeque::new();
+
+        for (position, previous_light) in changes {
+            let previous_light = previous_light.get(channel);
+
+            if blocks.get_light(*position).get(channel) < previous_light {
+                removals.push_back((*position, previous_light));
+            }
+
+            relight.push_back(*position);
+        }
+
+        remove_light(blocks, block_registry, channel, removals, &mut relight);
+        spread_light(blocks, block_registry, channel, relight, is_in_chunk);
+    }
+}
+
+/// Updates the light after the block at the position changed. Blocks in the padding are left to
+/// the chunk they belong to.
+pub fn relight_block(blocks: &mut VoxelData, block_registry: &BlockRegistry, position: IVec3) {
+    if !is_in_chunk(position) {
+        return;
+    }
+
+    let block = blocks.get_block(position);
+    let previous_light = blocks.get_light(position);
+    blocks.set_light(position, Light::new(0, block_registry.get(block).light));
+
+    let mut changes = vec![(position, previous_light)];
+
+    // The surrounding voxels can now shine into the block.
+    if passes_light(block, block_registry) {
+        changes.extend(
+            DIRECTIONS
+                .iter()
+                .map(|direction| position + direction)
+                .filter(|neighbour| is_in_padded_chunk(*neighbour))
+                .map(|neighbour| (neighbour, blocks.get_light(neighbour))),
+        );
+    }
+
+    update_light(blocks, block_registry, &changes);
+}
+
+/// Only opaque blocks stop light, cutout and transparent blocks dim it like air does.
+fn passes_light(block: BlockType, block_registry: &BlockRegistry) -> bool {
+    block == BlockType::AIR || block_registry.get(block).render_mode != BlockRenderMode::Opaque
+}
+
+/// Light a neighbouring voxel in the direction gets. Full sky light goes straight down through air
+/// without getting weaker, every other step costs one level.
+fn get_spread_light(
+    channel: LightChannel,
+    light: u8,
+    direction: IVec3,
+    neighbour_block: BlockType,
+) -> u8 {
+    if channel == LightChannel::Sky
+        && light == MAX_LIGHT
+        && direction == IVec3::NEG_Y
+        && neighbour_block == BlockType::AIR
+    {
+        MAX_LIGHT
+    } else {
+        light.saturating_sub(1)
+    }
+}
+
+fn is_in_padded_chunk(position: IVec3) -> bool {
+    position.min_element() >= 0 && position.max_element() < padded_chunk_size()
+}
+
+fn is_in_chunk(position: IVec3) -> bool {
+    position.min_element() >= 1 && position.max_element() <= chunk_size() as i32
+}
+
+/// Breadth first flood fill from the queued voxels into every voxel `can_light` allows.
+fn spread_light(
+    blocks: &mut VoxelData,
+    block_registry: &BlockRegistry,
+    channel: LightChannel,
+    mut queue: VecDeque<IVec3>,
+    can_light: fn(IVec3) -> bool,
+) {
+    while let Some(position) = queue.pop_front() {
+        let light = blocks.get_light(position).get(channel);
+
+        if light <= 1 {
+            continue;
+        }
+
+        for direction in DIRECTIONS {
+            let neighbour = position + direction;
+
+            if !can_light(neighbour) {
+                continue;
+            }
+
+            let block = blocks.get_block(neighbour);
+            if !passes_light(block, block_registry) {
+                continue;
+            }
+
+            let spread_light = get_spread_light(channel, light, direction, block);
+            let neighbour_light = blocks.get_light(neighbour);
+
+            if spread_light > neighbour_light.get(channel) {
+                blocks.set_light(neighbour, neighbour_light.with(channel, spread_light));
+                queue.push_back(neighbour);
+            }
+        }
+    }
+}
+
+/// Darkens every voxel of the chunk, padding excluded, that could have been lit by the queued
+/// voxels, which hold the light they had before. Brighter voxels found on the way, light blocks
+/// and the padding are queued in `relight` to fill the removed light in again from the remaining
+/// sources.
+fn remove_light(
+    blocks: &mut VoxelData,
+    block_registry: &BlockRegistry,
+    channel: LightChannel,
+    mut queue: VecDeque<(IVec3, u8)>,
+    relight: &mut VecDeque<IVec3>,
+) {
+    while let Some((position, previous_light)) = queue.pop_front() {
+        for direction in DIRECTIONS {
+            let neighbour = position + direction;
+
+            if !is_in_padded_chunk(neighbour) {
+                continue;
+            }
+
+            let neighbour_light = blocks.get_light(neighbour);
+            let neighbour_channel = neighbour_light.get(channel);
+
+            if neighbour_channel == 0 {
+                continue;
+            }
+
+            let block = blocks.get_block(neighbour);
+
+            if !is_in_chunk(neighbour)
+                || neighbour_channel > get_spread_light(channel, previous_light, direction, block)
+            {
+                relight.push_back(neighbour);
+                continue;
+            }
+
+            let emitted_light = match channel {
+                LightChannel::Sky => 0,
+                LightChannel::Block => block_registry.get(block).light,
+            };
+
+            blocks.set_light(neighbour, neighbour_light.with(channel, emitted_light));
+            queue.push_back((neighbour, neighbour_channel));
+
+            if emitted_light > 0 {
+                relight.push_back(neighbour);
+            }
+        }
+    }
+}
@@ -1,42 +1,306 @@
-use crate::world_generation::array_texture::ATTRIBUTE_TEXTURE_ID;
-use crate::world_generation::chunk_generation::{CHUNK_SIZE, VOXEL_SIZE};
-use crate::world_generation::voxel_world::ChunkLod;
+use crate::world_generation::array_texture::{
+    ATTRIBUTE_AMBIENT_OCCLUSION, ATTRIBUTE_LIGHT, ATTRIBUTE_TEXTURE_ID,
+};
+use crate::world_generation::chunk_generation::block_registry::{BlockRegistry, BlockRenderMode};
+use crate::world_generation::chunk_generation::light_propagation::{Light, MAX_LIGHT};
+use crate::world_generation::chunk_generation::{
+    chunk_size, BlockType, MAX_CHUNK_SIZE, VOXEL_SIZE,
+};
+use crate::world_generation::voxel_world::{ChunkLod, NeighbourLods};
 use bevy::prelude::*;
 use bevy::render::mesh::{Indices, PrimitiveTopology};
 use bevy::render::render_asset::RenderAssetUsages;
 
 use super::voxel_types::VoxelData;
 
+/// Plain vertex and index buffers of a chunk mesh, usable without a bevy `App`.
+#[derive(Default)]
+pub struct ChunkMeshBuffers {
+    pub positions: Vec<[f32; 3]>,
+    pub normals: Vec<[f32; 3]>,
+    pub uvs: Vec<[f32; 2]>,
+    pub texture_ids: Vec<u32>,
+    pub ambient_occlusion: Vec<f32>,
+    pub light: Vec<[f32; 2]>,
+    pub indices: Vec<u32>,
+}
+
+impl ChunkMeshBuffers {
+    pub fn triangles(&self) -> Vec<[u32; 3]> {
+        self.indices
+            .chunks_exact(3)
+            .map(|triangle| [triangle[0], triangle[1], triangle[2]])
+            .collect()
+    }
+
+    pub fn into_mesh(self) -> Mesh {
+        let mut mesh = Mesh::new(
+            PrimitiveTopology::TriangleList,
+            RenderAssetUsages::RENDER_WORLD,
+        );
+
+        mesh.insert_attribute(Mesh::ATTRIBUTE_POSITION, self.positions);
+        mesh.insert_attribute(Mesh::ATTRIBUTE_NORMAL, self.normals);
+        mesh.insert_attribute(Mesh::ATTRIBUTE_UV_0, self.uvs);
+        mesh.insert_attribute(ATTRIBUTE_TEXTURE_ID, self.texture_ids);
+        mesh.insert_attribute(ATTRIBUTE_AMBIENT_OCCLUSION, self.ambient_occlusion);
+        mesh.insert_attribute(ATTRIBUTE_LIGHT, self.light);
+
+        mesh.insert_indices(Indices::U32(self.indices));
+
+        mesh
+    }
+}
+
+/// Opaque and cutout blocks share the solid mesh, transparent blocks are blended in a second one.
+pub struct ChunkMeshes {
+    pub solid: Option<ChunkMeshBuffers>,
+    pub transparent: Option<ChunkMeshBuffers>,
+}
+
+pub struct ChunkMesh {
+    pub solid: Option<Mesh>,
+    pub transparent: Option<Mesh>,
+    pub collider_positions: Vec<Vec3>,
+    pub collider_triangles: Vec<[u32; 3]>,
+}
+
 pub fn generate_mesh(
     blocks: &VoxelData,
     min_height: i32,
     chunk_lod: ChunkLod,
-) -> Option<(Mesh, Vec<Vec3>, Vec<[u32; 3]>)> {
-    let mut mesh = Mesh::new(
-        PrimitiveTopology::TriangleList,
-        RenderAssetUsages::RENDER_WORLD,
+    neighbour_lods: NeighbourLods,
+    block_registry: &BlockRegistry,
+) -> Option<ChunkMesh> {
+    let ChunkMeshes { solid, transparent } = generate_mesh_buffers(
+        blocks,
+        min_height,
+        chunk_lod,
+        neighbour_lods,
+        block_registry,
     );
 
-    let mut positions: Vec<[f32; 3]> = Vec::new();
-    let mut normals: Vec<[f32; 3]> = Vec::new();
-    let mut triangles: Vec<[u32; 3]> = Vec::new();
-    let mut uvs: Vec<[f32; 2]> = Vec::new();
-    let mut texture_ids: Vec<u32> = Vec::new();
-
-    fn rotate_into_direction<T: Vec3Swizzles>(vector: T, direction: IVec3) -> T {
-        match direction {
-            IVec3::X | IVec3::NEG_X => vector.xzy(),
-            IVec3::Y | IVec3::NEG_Y => vector.yxz(),
-            IVec3::Z | IVec3::NEG_Z => vector.zyx(),
-            _ => vector,
+    if solid.is_none() && transparent.is_none() {
+        return None;
+    }
+
+    let (collider_positions, collider_triangles) = match &solid {
+        Some(solid) if chunk_lod == ChunkLod::Full => (
+            solid
+                .positions
+                .iter()
+                .map(|position| Vec3::from_array(*position))
+                .collect(),
+            solid.triangles(),
+        ),
+        _ => (Vec::new(), Vec::new()),
+    };
+
+    Some(ChunkMesh {
+        solid: solid.map(ChunkMeshBuffers::into_mesh),
+        transparent: transparent.map(ChunkMeshBuffers::into_mesh),
+        collider_positions,
+        collider_triangles,
+    })
+}
+
+const DIRECTIONS: [IVec3; 6] = [
+    IVec3::X,
+    IVec3::NEG_X,
+    IVec3::Z,
+    IVec3::NEG_Z,
+    IVec3::Y,
+    IVec3::NEG_Y,
+];
+
+/// Everything a face looks like. Faces only merge if all of this matches, so merged quads keep the
+/// occlusion of their corners and the light in front of them.
+#[derive(Copy, Clone, PartialEq, Eq)]
+struct FaceKey {
+    texture_id: u32,
+    /// Index into [`ChunkMeshes`].
+    mesh_index: usize,
+    /// 2 bits per vertex, see [`get_ambient_occlusion`].
+    ambient_occlusion: u8,
+    light: Light,
+}
+
+/// Brightness of the four ambient occlusion levels, from a corner surrounded by three blocks to
+/// an open one.
+const AMBIENT_OCCLUSION_CURVE: [f32; 4] = [0.45, 0.65, 0.85, 1.0];
+
+/// In plane offsets of the quad vertices pushed by [`push_quad`], as height and width signs.
+const VERTEX_CORNERS: [(i32, i32); 4] = [(-1, -1), (-1, 1), (1, 1), (1, -1)];
+
+/// Greedy mesher working on bitmasks. The padded chunk is turned into one `u128` row of solid and
+/// opaque bits per y and z, which culls most hidden faces for a whole row at once. The remaining
+/// faces are sorted into one `u64` mask per row of their slice, which fits up to
+/// [`MAX_CHUNK_SIZE`] voxels, and merged into quads by scanning runs of set bits.
+pub fn generate_mesh_buffers(
+    blocks: &VoxelData,
+    min_height: i32,
+    chunk_lod: ChunkLod,
+    neighbour_lods: NeighbourLods,
+    block_registry: &BlockRegistry,
+) -> ChunkMeshes {
+    let mut meshes = [ChunkMeshBuffers::default(), ChunkMeshBuffers::default()];
+
+    if blocks.get_single_block() == Some(BlockType::AIR) {
+        return finish_meshes(meshes, min_height, chunk_lod);
+    }
+
+    let chunk_size = chunk_size();
+    let padded_chunk_size = chunk_size + 2;
+    // Bits of a row inside of the chunk, without the padding.
+    let row_mask = u64::MAX >> (MAX_CHUNK_SIZE - chunk_size);
+
+    let mut solid_rows = vec![0u128; padded_chunk_size * padded_chunk_size];
+    let mut opaque_rows = vec![0u128; padded_chunk_size * padded_chunk_size];
+
+    for z in 0..padded_chunk_size {
+        for y in 0..padded_chunk_size {
+            let row = y + z * padded_chunk_size;
+            for x in 0..padded_chunk_size {
+                let block = blocks.get_block([x as i32, y as i32, z as i32]);
+                if block == BlockType::AIR {
+                    continue;
+                }
+
+                solid_rows[row] |= 1 << x;
+                if block_registry.get(block).render_mode == BlockRenderMode::Opaque {
+                    opaque_rows[row] |= 1 << x;
+                }
+            }
         }
     }
 
+    for direction in DIRECTIONS {
+        let stitch_border = neighbour_lods.needs_stitching(direction, chunk_lod);
+
+        let slice_dir = direction.abs();
+        let width_dir = rotate_into_direction(IVec3::Z, direction);
+        let height_dir = rotate_into_direction(IVec3::Y, direction);
+
+        // For every slice along the direction the face masks per face key, with one row per
+        // width position and one bit per height position.
+        let mut slices: Vec<Vec<(FaceKey, [u64; MAX_CHUNK_SIZE])>> = vec![Vec::new(); chunk_size];
+
+        for z in 1..=chunk_size {
+            for y in 1..=chunk_size {
+                let row = y + z * padded_chunk_size;
+
+                let neighbour_opaque = match direction {
+                    IVec3::X => opaque_rows[row] >> 1,
+                    IVec3::NEG_X => opaque_rows[row] << 1,
+                    IVec3::Y => opaque_rows[row + 1],
+                    IVec3::NEG_Y => opaque_rows[row - 1],
+                    IVec3::Z => opaque_rows[row + padded_chunk_size],
+                    _ => opaque_rows[row - padded_chunk_size],
+                };
+
+                // Bit i stands for x = i + 1. These are only candidates, faces between two blocks
+                // of the same type or towards a stitched border are sorted out by `get_face`.
+                let mut candidates = ((solid_rows[row] & !neighbour_opaque) >> 1) as u64 & row_mask;
+
+                if stitch_border {
+                    candidates |= (solid_rows[row] >> 1) as u64
+                        & match direction {
+                            IVec3::X => 1 << (chunk_size - 1),
+                            IVec3::NEG_X => 1,
+                            IVec3::Z if z == chunk_size => row_mask,
+                            IVec3::NEG_Z if z == 1 => row_mask,
+                            _ => 0,
+                        };
+                }
+
+                while candidates != 0 {
+                    let x = candidates.trailing_zeros() as i32 + 1;
+                    candidates &= candidates - 1;
+
+                    let position = IVec3::new(x, y as i32, z as i32);
+
+                    let Some(face) =
+                        get_face(blocks, position, direction, stitch_border, block_registry)
+                    else {
+                        continue;
+                    };
+
+                    let slice = &mut slices[(position * slice_dir).max_element() as usize - 1];
+                    let rows = match slice.iter().position(|(key, _)| *key == face) {
+                        Some(index) => &mut slice[index].1,
+                        None => {
+                            slice.push((face, [0; MAX_CHUNK_SIZE]));
+                            &mut slice.last_mut().unwrap().1
+                        }
+                    };
+
+                    let width_pos = (position * width_dir).max_element() - 1;
+                    let height_pos = (position * height_dir).max_element() - 1;
+                    rows[width_pos as usize] |= 1 << height_pos;
+                }
+            }
+        }
+
+        for (slice_index, faces) in slices.into_iter().enumerate() {
+            for (face, mut rows) in faces {
+                for width_pos in 0..chunk_size {
+                    while rows[width_pos] != 0 {
+                        let height_pos = rows[width_pos].trailing_zeros();
+                        let height = (rows[width_pos] >> height_pos).trailing_ones();
+                        let run = (u64::MAX >> (64 - height)) << height_pos;
+
+                        rows[width_pos] &= !run;
+
+                        let mut width = 1;
+                        while width_pos + width < chunk_size && rows[width_pos + width] & run == run
+                        {
+                            rows[width_pos + width] &= !run;
+                            width += 1;
+                        }
+
+                        let position = slice_dir * (slice_index as i32 + 1)
+                            + width_dir * (width_pos as i32 + 1)
+                            + height_dir * (height_pos as i32 + 1);
+
+                        push_quad(
+                            &mut meshes[face.mesh_index],
+                            direction,
+                            position,
+                            width as i32,
+                            height as i32,
+                            face,
+                            chunk_lod,
+                        );
+                    }
+                }
+            }
+        }
+    }
+
+    finish_meshes(meshes, min_height, chunk_lod)
+}
+
+/// The previous greedy mesher, which checks every voxel once per direction. Kept as a reference
+/// for the mesh generation benchmark.
+pub fn generate_mesh_buffers_per_voxel(
+    blocks: &VoxelData,
+    min_height: i32,
+    chunk_lod: ChunkLod,
+    neighbour_lods: NeighbourLods,
+    block_registry: &BlockRegistry,
+) -> ChunkMeshes {
+    let mut meshes = [ChunkMeshBuffers::default(), ChunkMeshBuffers::default()];
+
     let mut generate_sides = |direction: IVec3| {
-        for i in 1..CHUNK_SIZE + 1 {
-            let mut done_faces = [[false; CHUNK_SIZE]; CHUNK_SIZE];
-            for j in 1..CHUNK_SIZE + 1 {
-                for k in 1..CHUNK_SIZE + 1 {
+        let stitch_border = neighbour_lods.needs_stitching(direction, chunk_lod);
+
+        let get_face =
+            |position: IVec3| get_face(blocks, position, direction, stitch_border, block_registry);
+
+        for i in 1..chunk_size() + 1 {
+            let mut done_faces = [[false; MAX_CHUNK_SIZE]; MAX_CHUNK_SIZE];
+            for j in 1..chunk_size() + 1 {
+                for k in 1..chunk_size() + 1 {
                     let current_pos =
                         rotate_into_direction(IVec3::new(i as i32, j as i32, k as i32), direction);
 
@@ -46,42 +310,34 @@ pub fn generate_mesh(
                     let width_pos = (current_pos * width_dir).max_element();
                     let height_pos = (current_pos * height_dir).max_element();
 
-                    if done_faces[width_pos as usize - 1][height_pos as usize - 1]
-                        || blocks.is_air(current_pos)
-                        || !blocks.is_air(current_pos + direction)
-                    {
+                    if done_faces[width_pos as usize - 1][height_pos as usize - 1] {
                         continue;
                     }
 
-                    let current_block = blocks.get_block(current_pos);
+                    let Some(face) = get_face(current_pos) else {
+                        continue;
+                    };
 
                     let mut height = 1;
                     let mut width = 1;
 
-                    while height_pos + height <= CHUNK_SIZE as i32
+                    while height_pos + height <= chunk_size() as i32
                         && !done_faces[width_pos as usize - 1]
                             [height_pos as usize + height as usize - 1]
-                        && blocks.get_block(current_pos + (height_dir * height)) == current_block
-                        && blocks.is_air(current_pos + (height_dir * height) + direction)
+                        && get_face(current_pos + (height_dir * height)) == Some(face)
                     {
                         height += 1;
                     }
 
-                    while width_pos + width <= CHUNK_SIZE as i32
+                    while width_pos + width <= chunk_size() as i32
                         && (0..height).all(|height| {
                             !done_faces[width_pos as usize + width as usize - 1]
                                 [height_pos as usize + height as usize - 1]
-                                && blocks.get_block(
+                                && get_face(
                                     current_pos
                                         + (width_dir * width as i32)
                                         + (height_dir * height as i32),
-                                ) == current_block
-                                && blocks.is_air(
-                                    current_pos
-                                        + (width_dir * width as i32)
-                                        + (height_dir * height as i32)
-                                        + direction,
-                                )
+                                ) == Some(face)
                         })
                     {
                         width += 1;
@@ -93,136 +349,252 @@ pub fn generate_mesh(
                         }
                     }
 
-                    let uv_start = Vec2::ZERO;
-                    let uv_end =
-                        Vec2::new(width as f32, height as f32) * chunk_lod.multiplier_f32();
-
-                    uvs.extend_from_slice(&[
-                        [uv_end.x, uv_end.y],
-                        [uv_start.x, uv_end.y],
-                        [uv_start.x, uv_start.y],
-                        [uv_end.x, uv_start.y],
-                    ]);
-
-                    let height = height as f32 - 1.;
-                    let width = width as f32 - 1.;
-
-                    let positions_count = positions.len() as u32;
-
-                    let vertex_pos = current_pos.as_vec3();
-
-                    let direction_adder = direction * (direction.min_element().abs());
-
-                    positions.extend_from_slice(&[
-                        (vertex_pos
-                            + (rotate_into_direction(Vec3::new(0.5, -0.5, -0.5), direction))
-                            + direction_adder.as_vec3())
-                        .to_array(),
-                        (vertex_pos
-                            + (rotate_into_direction(
-                                Vec3::new(0.5, -0.5, 0.5 + width),
-                                direction,
-                            ))
-                            + direction_adder.as_vec3())
-                        .to_array(),
-                        (vertex_pos
-                            + (rotate_into_direction(
-                                Vec3::new(0.5, 0.5 + height, 0.5 + width),
-                                direction,
-                            ))
-                            + direction_adder.as_vec3())
-                        .to_array(),
-                        (vertex_pos
-                            + (rotate_into_direction(
-                                Vec3::new(0.5, 0.5 + height, -0.5),
-                                direction,
-                            ))
-                            + direction_adder.as_vec3())
-                        .to_array(),
-                    ]);
-
-                    normals.extend_from_slice(&[
-                        direction.as_vec3().to_array(),
-                        direction.as_vec3().to_array(),
-                        direction.as_vec3().to_array(),
-                        direction.as_vec3().to_array(),
-                    ]);
-
-                    let texture_id = current_block.get_texture_id();
-
-                    texture_ids
-                        .extend_from_slice(&[texture_id, texture_id, texture_id, texture_id]);
-
-                    let invert = !direction.min_element() < 0;
-
-                    triangles.extend_from_slice(&[
-                        [
-                            positions_count + 0,
-                            positions_count + if invert { 1 } else { 3 },
-                            positions_count + if invert { 3 } else { 1 },
-                        ],
-                        [
-                            positions_count + 1,
-                            positions_count + if invert { 2 } else { 3 },
-                            positions_count + if invert { 3 } else { 2 },
-                        ],
-                    ]);
+                    push_quad(
+                        &mut meshes[face.mesh_index],
+                        direction,
+                        current_pos,
+                        width,
+                        height,
+                        face,
+                        chunk_lod,
+                    );
                 }
             }
         }
     };
 
-    generate_sides(IVec3::X);
-    generate_sides(IVec3::NEG_X);
-    generate_sides(IVec3::Z);
-    generate_sides(IVec3::NEG_Z);
-    generate_sides(IVec3::Y);
-    generate_sides(IVec3::NEG_Y);
+    for direction in DIRECTIONS {
+        generate_sides(direction);
+    }
+
+    finish_meshes(meshes, min_height, chunk_lod)
+}
+
+fn rotate_into_direction<T: Vec3Swizzles>(vector: T, direction: IVec3) -> T {
+    match direction {
+        IVec3::X | IVec3::NEG_X => vector.xzy(),
+        IVec3::Y | IVec3::NEG_Y => vector.yxz(),
+        IVec3::Z | IVec3::NEG_Z => vector.zyx(),
+        _ => vector,
+    }
+}
+
+/// Returns how the face looks, or None if the face is hidden. Faces are only hidden by opaque
+/// blocks and by blocks of the same type. They are lit by the voxel in front of them.
+///
+/// The padding on a side bordering another lod was sampled at our lod, not the neighbours, so
+/// border faces of stitched sides aren't culled against it. The extra faces form a skirt down the
+/// chunk side that covers the crack between the two surfaces.
+fn get_face(
+    blocks: &VoxelData,
+    position: IVec3,
+    direction: IVec3,
+    stitch_border: bool,
+    block_registry: &BlockRegistry,
+) -> Option<FaceKey> {
+    let block = blocks.get_block(position);
+    let neighbour_position = position + direction;
+    let neighbour = if stitch_border
+        && (neighbour_position.min_element() == 0
+            || neighbour_position.max_element() == chunk_size() as i32 + 1)
+        && block_registry.get(block).render_mode != BlockRenderMode::Transparent
+    {
+        BlockType::AIR
+    } else {
+        blocks.get_block(neighbour_position)
+    };
 
-    if triangles.is_empty() {
+    if block == BlockType::AIR
+        || neighbour == block
+        || (neighbour != BlockType::AIR
+            && block_registry.get(neighbour).render_mode == BlockRenderMode::Opaque)
+    {
         return None;
     }
 
-    for position in positions.iter_mut() {
-        position[0] = (position[0] - 0.5) * VOXEL_SIZE * chunk_lod.multiplier_f32() + VOXEL_SIZE;
-        position[1] =
-            (position[1] + min_height as f32 - 0.5) * VOXEL_SIZE * chunk_lod.multiplier_f32()
-                + VOXEL_SIZE;
-        position[2] = (position[2] - 0.5) * VOXEL_SIZE * chunk_lod.multiplier_f32() + VOXEL_SIZE;
+    let block = block_registry.get(block);
+    let mesh_index = match block.render_mode {
+        BlockRenderMode::Opaque | BlockRenderMode::Cutout => 0,
+        BlockRenderMode::Transparent => 1,
+    };
+
+    Some(FaceKey {
+        texture_id: block.faces.get_layer(direction),
+        mesh_index,
+        ambient_occlusion: get_ambient_occlusion(
+            blocks,
+            neighbour_position,
+            direction,
+            block_registry,
+        ),
+        light: blocks.get_light(neighbour_position),
+    })
+}
+
+/// Classic voxel corner occlusion: every vertex of the face looks at the two blocks along its
+/// edges and the one on its diagonal in front of the face, with both edges blocked counting as
+/// fully occluded. Returns a 2 bit level per vertex, 3 being unoccluded.
+fn get_ambient_occlusion(
+    blocks: &VoxelData,
+    neighbour_position: IVec3,
+    direction: IVec3,
+    block_registry: &BlockRegistry,
+) -> u8 {
+    let height_dir = rotate_into_direction(IVec3::Y, direction);
+    let width_dir = rotate_into_direction(IVec3::Z, direction);
+
+    let occludes = |offset: IVec3| {
+        let block = blocks.get_block(neighbour_position + offset);
+        block != BlockType::AIR && block_registry.get(block).render_mode == BlockRenderMode::Opaque
+    };
+
+    let mut ambient_occlusion = 0;
+
+    for (vertex, (height_sign, width_sign)) in VERTEX_CORNERS.into_iter().enumerate() {
+        let height_side = occludes(height_dir * height_sign);
+        let width_side = occludes(width_dir * width_sign);
+        let corner = occludes(height_dir * height_sign + width_dir * width_sign);
+
+        let level = if height_side && width_side {
+            0
+        } else {
+            3 - (height_side as u8 + width_side as u8 + corner as u8)
+        };
+
+        ambient_occlusion |= level << (vertex * 2);
     }
 
-    let mut mesh_triangles: Vec<u32> = Vec::new();
+    ambient_occlusion
+}
 
-    for triangle in &triangles {
-        mesh_triangles.push(triangle[0]);
-        mesh_triangles.push(triangle[1]);
-        mesh_triangles.push(triangle[2]);
+/// Adds a quad of `width` by `height` faces, starting at the face of the voxel at `position`.
+fn push_quad(
+    buffers: &mut ChunkMeshBuffers,
+    direction: IVec3,
+    position: IVec3,
+    width: i32,
+    height: i32,
+    face: FaceKey,
+    chunk_lod: ChunkLod,
+) {
+    let uv_start = Vec2::ZERO;
+    let uv_end = Vec2::new(width as f32, height as f32) * chunk_lod.multiplier_f32();
+
+    // On x faces the width runs along the world y axis, so the uvs are swapped to
+    // keep the top of side textures pointing up.
+    if direction.x != 0 {
+        buffers.uvs.extend_from_slice(&[
+            [uv_start.y, uv_end.x],
+            [uv_start.y, uv_start.x],
+            [uv_end.y, uv_start.x],
+            [uv_end.y, uv_end.x],
+        ]);
+    } else {
+        buffers.uvs.extend_from_slice(&[
+            [uv_end.x, uv_end.y],
+            [uv_start.x, uv_end.y],
+            [uv_start.x, uv_start.y],
+            [uv_end.x, uv_start.y],
+        ]);
     }
 
-    let collider_positions = if chunk_lod == ChunkLod::Full {
-        positions
-            .clone()
-            .iter()
-            .map(|position| Vec3::new(position[0], position[1], position[2]))
-            .collect()
+    let height = height as f32 - 1.;
+    let width = width as f32 - 1.;
+
+    let positions_count = buffers.positions.len() as u32;
+
+    let vertex_pos = position.as_vec3();
+
+    let direction_adder = direction * (direction.min_element().abs());
+
+    buffers.positions.extend_from_slice(&[
+        (vertex_pos
+            + (rotate_into_direction(Vec3::new(0.5, -0.5, -0.5), direction))
+            + direction_adder.as_vec3())
+        .to_array(),
+        (vertex_pos
+            + (rotate_into_direction(Vec3::new(0.5, -0.5, 0.5 + width), direction))
+            + direction_adder.as_vec3())
+        .to_array(),
+        (vertex_pos
+            + (rotate_into_direction(Vec3::new(0.5, 0.5 + height, 0.5 + width), direction))
+            + direction_adder.as_vec3())
+        .to_array(),
+        (vertex_pos
+            + (rotate_into_direction(Vec3::new(0.5, 0.5 + height, -0.5), direction))
+            + direction_adder.as_vec3())
+        .to_array(),
+    ]);
+
+    buffers.normals.extend_from_slice(&[
+        direction.as_vec3().to_array(),
+        direction.as_vec3().to_array(),
+        direction.as_vec3().to_array(),
+        direction.as_vec3().to_array(),
+    ]);
+
+    buffers.texture_ids.extend_from_slice(&[face.texture_id; 4]);
+
+    let vertex_occlusion: [u8; 4] =
+        std::array::from_fn(|vertex| (face.ambient_occlusion >> (vertex * 2)) & 0b11);
+
+    buffers
+        .ambient_occlusion
+        .extend(vertex_occlusion.map(|level| AMBIENT_OCCLUSION_CURVE[level as usize]));
+
+    let light = [
+        face.light.sky() as f32 / MAX_LIGHT as f32,
+        face.light.block() as f32 / MAX_LIGHT as f32,
+    ];
+    buffers.light.extend_from_slice(&[light; 4]);
+
+    let invert = !direction.min_element() < 0;
+
+    // The quad is split along the diagonal between its darker corners, otherwise the occlusion
+    // gets interpolated unevenly across the two triangles.
+    if vertex_occlusion[1] + vertex_occlusion[3] > vertex_occlusion[0] + vertex_occlusion[2] {
+        buffers.indices.extend_from_slice(&[
+            positions_count,
+            positions_count + if invert { 1 } else { 2 },
+            positions_count + if invert { 2 } else { 1 },
+            positions_count,
+            positions_count + if invert { 2 } else { 3 },
+            positions_count + if invert { 3 } else { 2 },
+        ]);
     } else {
-        Vec::new()
-    };
+        buffers.indices.extend_from_slice(&[
+            positions_count,
+            positions_count + if invert { 1 } else { 3 },
+            positions_count + if invert { 3 } else { 1 },
+            positions_count + 1,
+            positions_count + if invert { 2 } else { 3 },
+            positions_count + if invert { 3 } else { 2 },
+        ]);
+    }
+}
+
+fn finish_meshes(
+    meshes: [ChunkMeshBuffers; 2],
+    min_height: i32,
+    chunk_lod: ChunkLod,
+) -> ChunkMeshes {
+    let [solid, transparent] = meshes.map(|mut buffers| {
+        if buffers.indices.is_empty() {
+            return None;
+        }
 
-    mesh.insert_attribute(Mesh::ATTRIBUTE_POSITION, positions);
-    mesh.insert_attribute(Mesh::ATTRIBUTE_NORMAL, normals);
-    mesh.insert_attribute(Mesh::ATTRIBUTE_UV_0, uvs);
-    mesh.insert_attribute(ATTRIBUTE_TEXTURE_ID, texture_ids);
+        for position in buffers.positions.iter_mut() {
+            position[0] =
+                (position[0] - 0.5) * VOXEL_SIZE * chunk_lod.multiplier_f32() + VOXEL_SIZE;
+            position[1] =
+                (position[1] + min_height as f32 - 0.5) * VOXEL_SIZE * chunk_lod.multiplier_f32()
+                    + VOXEL_SIZE;
+            position[2] =
+                (position[2] - 0.5) * VOXEL_SIZE * chunk_lod.multiplier_f32() + VOXEL_SIZE;
+        }
 
-    mesh.insert_indices(Indices::U32(mesh_triangles));
+        Some(buffers)
+    });
 
-    Some((
-        mesh,
-        collider_positions,
-        if chunk_lod == ChunkLod::Full {
-            triangles
-        } else {
-            Vec::new()
-        },
-    ))
+    ChunkMeshes { solid, transparent }
 }
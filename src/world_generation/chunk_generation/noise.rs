@@ -1,8 +1,10 @@
+pub mod biome_height_modifier;
 pub mod cellular_noise;
 pub mod fractal_open_simplex;
 pub mod full_cache;
 pub mod gradient_fractal_noise;
 pub mod lod_height_adjuster;
+pub mod noise_graph;
 pub mod roughness;
 pub mod shift_n_scale;
 pub mod smooth_step;
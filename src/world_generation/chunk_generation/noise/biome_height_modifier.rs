@@ -0,0 +1,44 @@
+use std::sync::Arc;
+
+use noise::NoiseFn;
+
+use crate::world_generation::chunk_generation::biome_registry::{BiomeRegistry, ClimateNoise};
+
+/// Reshapes the terrain height with the blended height modifiers of the biomes at each point.
+pub struct BiomeHeightModifier<T, C, M> {
+    source: T,
+    climate_noise: ClimateNoise<C, M>,
+    biome_registry: Arc<BiomeRegistry>,
+    sea_level: f32,
+}
+
+impl<T, C, M> BiomeHeightModifier<T, C, M> {
+    pub fn new(
+        source: T,
+        climate_noise: ClimateNoise<C, M>,
+        biome_registry: Arc<BiomeRegistry>,
+        sea_level: f32,
+    ) -> Self {
+        Self {
+            source,
+            climate_noise,
+            biome_registry,
+            sea_level,
+        }
+    }
+}
+
+impl<T, C, M> NoiseFn<f64, 2usize> for BiomeHeightModifier<T, C, M>
+where
+    T: NoiseFn<f64, 2usize>,
+    C: NoiseFn<f64, 2usize>,
+    M: NoiseFn<f64, 2usize>,
+{
+    fn get(&self, point: [f64; 2usize]) -> f64 {
+        let height = self.source.get(point);
+        let climate = self.climate_noise.get(point, height);
+
+        self.biome_registry
+            .modify_height(height as f32, self.sea_level, climate) as f64
+    }
+}
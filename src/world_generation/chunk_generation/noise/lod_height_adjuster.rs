@@ -53,8 +53,16 @@ where
     T: NoiseFn<f64, 2usize>,
 {
     fn get(&self, point: [f64; 2usize]) -> f64 {
-        self.noise.get(point) * (1. / self.lod.multiplier_i32() as f64)
-            + 1.
-            + 10. / self.lod.multiplier_i32() as f64
+        to_lod_height(self.noise.get(point), self.lod)
     }
 }
+
+/// Converts a full lod terrain height into the height of the same surface at the given lod.
+pub fn to_lod_height(height: f64, lod: ChunkLod) -> f64 {
+    height * (1. / lod.multiplier_i32() as f64) + 1. + 10. / lod.multiplier_i32() as f64
+}
+
+/// Inverse of [`to_lod_height`].
+pub fn from_lod_height(height: f64, lod: ChunkLod) -> f64 {
+    (height - 1.) * lod.multiplier_i32() as f64 - 10.
+}
@@ -0,0 +1,403 @@
+use crate::world_generation::chunk_generation::noise::cellular_noise::Cellular;
+use crate::world_generation::chunk_generation::noise::gradient_fractal_noise::GFT;
+use crate::world_generation::chunk_generation::noise::roughness::Roughness;
+use crate::world_generation::chunk_generation::noise::smooth_step::SmoothStep;
+use fastnoise_lite::{CellularDistanceFunction, CellularReturnType};
+use noise::{Add, Constant, Max, Min, MultiFractal, Multiply, NoiseFn, ScalePoint, Simplex};
+use rand::prelude::StdRng;
+use rand::{Rng, SeedableRng};
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use std::fs;
+use std::path::Path;
+
+pub const NOISE_GRAPH_FILE: &str = "noise_graphs.ron";
+
+pub const BASE_TERRAIN_GRAPH: &str = "base_terrain";
+pub const MOUNTAIN_BIOME_GRAPH: &str = "mountain_biome";
+pub const GRASS_COLOR_GRAPH: &str = "grass_color";
+
+/// Graphs the generator builds its noise from, so every noise graph file has to define them.
+const REQUIRED_GRAPHS: [&str; 3] = [BASE_TERRAIN_GRAPH, MOUNTAIN_BIOME_GRAPH, GRASS_COLOR_GRAPH];
+
+pub type BoxedNoise = Box<dyn NoiseFn<f64, 2> + Send + Sync>;
+
+#[derive(Serialize, Deserialize)]
+struct NoiseGraphFile {
+    graphs: BTreeMap<String, NoiseGraph>,
+}
+
+/// A 2D noise function described as a tree of noise nodes. Seeded nodes take their seeds one
+/// after another from a random generator seeded with the world seed plus `seed_offset`, so
+/// reordering the nodes of a graph also changes their seeds.
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
+pub struct NoiseGraph {
+    #[serde(default)]
+    pub seed_offset: u64,
+    pub root: NoiseNode,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
+pub enum NoiseNode {
+    Constant(f64),
+    Simplex,
+    Roughness {
+        frequency: f64,
+        amplitude: f64,
+    },
+    /// Cellular noise mapped into 0..1.
+    Cellular {
+        frequency: f32,
+        #[serde(default)]
+        distance_function: CellularDistance,
+        #[serde(default)]
+        return_type: CellularReturn,
+        #[serde(default = "default_jitter")]
+        jitter: f32,
+    },
+    Add(Box<NoiseNode>, Box<NoiseNode>),
+    Multiply(Box<NoiseNode>, Box<NoiseNode>),
+    Min(Box<NoiseNode>, Box<NoiseNode>),
+    Max(Box<NoiseNode>, Box<NoiseNode>),
+    /// Multiplies the point the source is sampled at.
+    Scale {
+        source: Box<NoiseNode>,
+        scale: f64,
+    },
+    /// Maps the source to `(value + shift) / scale`.
+    ShiftNScale {
+        source: Box<NoiseNode>,
+        shift: f64,
+        scale: f64,
+    },
+    SmoothStep {
+        source: Box<NoiseNode>,
+        #[serde(default = "default_steps")]
+        steps: f64,
+        #[serde(default = "default_smoothness")]
+        smoothness: f64,
+    },
+    /// Gradient fractal noise, which flattens higher octaves on steep terrain.
+    Gft {
+        source: Box<NoiseNode>,
+        #[serde(default = "default_octaves")]
+        octaves: usize,
+        #[serde(default = "default_frequency")]
+        frequency: f64,
+        #[serde(default = "default_lacunarity")]
+        lacunarity: f64,
+        #[serde(default = "default_persistence")]
+        persistence: f64,
+        #[serde(default = "default_gradient")]
+        gradient: f64,
+        #[serde(default = "default_amplitude")]
+        amplitude: f64,
+    },
+    /// Another graph of the same file, seeded by its own `seed_offset`.
+    Graph(String),
+}
+
+#[derive(Serialize, Deserialize, Copy, Clone, Default, Debug, PartialEq, Eq)]
+pub enum CellularDistance {
+    Euclidean,
+    #[default]
+    EuclideanSq,
+    Manhattan,
+    Hybrid,
+}
+
+#[derive(Serialize, Deserialize, Copy, Clone, Default, Debug, PartialEq, Eq)]
+pub enum CellularReturn {
+    CellValue,
+    #[default]
+    Distance,
+    Distance2,
+    Distance2Add,
+    Distance2Sub,
+    Distance2Mul,
+    Distance2Div,
+}
+
+fn default_jitter() -> f32 {
+    1.
+}
+
+fn default_steps() -> f64 {
+    SmoothStep::<Simplex>::DEFAULT_STEPS
+}
+
+fn default_smoothness() -> f64 {
+    SmoothStep::<Simplex>::DEFAULT_SMOOTHNESS
+}
+
+fn default_octaves() -> usize {
+    GFT::<Simplex>::DEFAULT_OCTAVE_COUNT
+}
+
+fn default_frequency() -> f64 {
+    GFT::<Simplex>::DEFAULT_FREQUENCY
+}
+
+fn default_lacunarity() -> f64 {
+    GFT::<Simplex>::DEFAULT_LACUNARITY
+}
+
+fn default_persistence() -> f64 {
+    GFT::<Simplex>::DEFAULT_PERSISTENCE
+}
+
+fn default_gradient() -> f64 {
+    GFT::<Simplex>::DEFAULT_GRADIENT
+}
+
+fn default_amplitude() -> f64 {
+    GFT::<Simplex>::DEFAULT_AMPLITUDE
+}
+
+impl From<CellularDistance> for CellularDistanceFunction {
+    fn from(distance: CellularDistance) -> Self {
+        match distance {
+            CellularDistance::Euclidean => Self::Euclidean,
+            CellularDistance::EuclideanSq => Self::EuclideanSq,
+            CellularDistance::Manhattan => Self::Manhattan,
+            CellularDistance::Hybrid => Self::Hybrid,
+        }
+    }
+}
+
+impl From<CellularReturn> for CellularReturnType {
+    fn from(return_type: CellularReturn) -> Self {
+        match return_type {
+            CellularReturn::CellValue => Self::CellValue,
+            CellularReturn::Distance => Self::Distance,
+            CellularReturn::Distance2 => Self::Distance2,
+            CellularReturn::Distance2Add => Self::Distance2Add,
+            CellularReturn::Distance2Sub => Self::Distance2Sub,
+            CellularReturn::Distance2Mul => Self::Distance2Mul,
+            CellularReturn::Distance2Div => Self::Distance2Div,
+        }
+    }
+}
+
+impl NoiseNode {
+    /// Names of the graphs this node and its sources refer to.
+    fn get_references<'a>(&'a self, references: &mut Vec<&'a str>) {
+        match self {
+            NoiseNode::Constant(_)
+            | NoiseNode::Simplex
+            | NoiseNode::Roughness { .. }
+            | NoiseNode::Cellular { .. } => {}
+            NoiseNode::Add(a, b)
+            | NoiseNode::Multiply(a, b)
+            | NoiseNode::Min(a, b)
+            | NoiseNode::Max(a, b) => {
+                a.get_references(references);
+                b.get_references(references);
+            }
+            NoiseNode::Scale { source, .. }
+            | NoiseNode::ShiftNScale { source, .. }
+            | NoiseNode::SmoothStep { source, .. }
+            | NoiseNode::Gft { source, .. } => source.get_references(references),
+            NoiseNode::Graph(name) => references.push(name),
+        }
+    }
+}
+
+/// Named noise graphs loaded from a RON file, which the terrain noise functions are built from.
+#[derive(Clone)]
+pub struct NoiseGraphRegistry {
+    graphs: BTreeMap<String, NoiseGraph>,
+}
+
+impl NoiseGraphRegistry {
+    pub fn load(path: impl AsRef<Path>) -> Result<Self, String> {
+        let path = path.as_ref();
+        let file = fs::read_to_string(path)
+            .map_err(|error| format!("Failed to read noise graphs {path:?}: {error}"))?;
+
+        Self::parse(&file).map_err(|error| format!("Invalid noise graphs {path:?}: {error}"))
+    }
+
+    pub fn parse(file: &str) -> Result<Self, String> {
+        let file: NoiseGraphFile = ron::from_str(file).map_err(|error| error.to_string())?;
+
+        let registry = Self {
+            graphs: file.graphs,
+        };
+
+        for name in REQUIRED_GRAPHS {
+            if !registry.graphs.contains_key(name) {
+                return Err(format!("Missing noise graph {name:?}"));
+            }
+        }
+
+        for name in registry.graphs.keys() {
+            registry.check_references(name, &mut vec![])?;
+        }
+
+        Ok(registry)
+    }
+
+    pub fn get(&self, name: &str) -> Option<&NoiseGraph> {
+        self.graphs.get(name)
+    }
+
+    pub fn iter(&self) -> impl Iterator<Item = (&String, &NoiseGraph)> {
+        self.graphs.iter()
+    }
+
+    /// Graphs for tuning their parameters. Changing which graphs a graph refers to isn't checked
+    /// again, so only the values of the nodes should be touched.
+    pub fn iter_mut(&mut self) -> impl Iterator<Item = (&String, &mut NoiseGraph)> {
+        self.graphs.iter_mut()
+    }
+
+    /// The graphs in the format of the noise graph file.
+    pub fn to_ron(&self) -> Result<String, String> {
+        ron::ser::to_string_pretty(
+            &NoiseGraphFile {
+                graphs: self.graphs.clone(),
+            },
+            ron::ser::PrettyConfig::default(),
+        )
+        .map_err(|error| error.to_string())
+    }
+
+    /// Builds the noise function of a graph for the world seed.
+    ///
+    /// Panics if there is no graph with the name, which can only happen for graphs that aren't
+    /// required.
+    pub fn build(&self, name: &str, seed: u64) -> BoxedNoise {
+        let graph = self
+            .get(name)
+            .unwrap_or_else(|| panic!("Unknown noise graph {name:?}"));
+        let mut rng = StdRng::seed_from_u64(seed + graph.seed_offset);
+
+        self.build_node(&graph.root, seed, &mut rng)
+    }
+
+    fn build_node(&self, node: &NoiseNode, seed: u64, rng: &mut StdRng) -> BoxedNoise {
+        match node {
+            NoiseNode::Constant(value) => Box::new(Constant::new(*value)),
+            NoiseNode::Simplex => Box::new(Simplex::new(rng.random())),
+            NoiseNode::Roughness {
+                frequency,
+                amplitude,
+            } => Box::new(Roughness::new(rng.random(), *frequency, *amplitude)),
+            NoiseNode::Cellular {
+                frequency,
+                distance_function,
+                return_type,
+                jitter,
+            } => Box::new(
+                Cellular::new(rng.random())
+                    .set_frequency(*frequency)
+                    .set_distance_function((*distance_function).into())
+                    .set_return_type((*return_type).into())
+                    .set_jitter(*jitter),
+            ),
+            NoiseNode::Add(a, b) => {
+                let a = self.build_node(a, seed, rng);
+                Box::new(Add::new(a, self.build_node(b, seed, rng)))
+            }
+            NoiseNode::Multiply(a, b) => {
+                let a = self.build_node(a, seed, rng);
+                Box::new(Multiply::new(a, self.build_node(b, seed, rng)))
+            }
+            NoiseNode::Min(a, b) => {
+                let a = self.build_node(a, seed, rng);
+                Box::new(Min::new(a, self.build_node(b, seed, rng)))
+            }
+            NoiseNode::Max(a, b) => {
+                let a = self.build_node(a, seed, rng);
+                Box::new(Max::new(a, self.build_node(b, seed, rng)))
+            }
+            NoiseNode::Scale { source, scale } => {
+                Box::new(ScalePoint::new(self.build_node(source, seed, rng)).set_scale(*scale))
+            }
+            NoiseNode::ShiftNScale {
+                source,
+                shift,
+                scale,
+            } => Box::new(ShiftNScaleNode {
+                source: self.build_node(source, seed, rng),
+                shift: *shift,
+                scale: *scale,
+            }),
+            NoiseNode::SmoothStep {
+                source,
+                steps,
+                smoothness,
+            } => Box::new(
+                SmoothStep::new(self.build_node(source, seed, rng))
+                    .set_steps(*steps)
+                    .set_smoothness(*smoothness),
+            ),
+            NoiseNode::Gft {
+                source,
+                octaves,
+                frequency,
+                lacunarity,
+                persistence,
+                gradient,
+                amplitude,
+            } => Box::new(
+                GFT::new_with_source(self.build_node(source, seed, rng))
+                    .set_octaves(*octaves)
+                    .set_frequency(*frequency)
+                    .set_lacunarity(*lacunarity)
+                    .set_persistence(*persistence)
+                    .set_gradient(*gradient)
+                    .set_amplitude(*amplitude),
+            ),
+            NoiseNode::Graph(name) => self.build(name, seed),
+        }
+    }
+
+    /// Makes sure every graph the named graph refers to exists and doesn't refer back to it.
+    fn check_references<'a>(
+        &'a self,
+        name: &'a str,
+        path: &mut Vec<&'a str>,
+    ) -> Result<(), String> {
+        if path.contains(&name) {
+            return Err(format!(
+                "Noise graph {name:?} refers to itself through {}",
+                path.join(" -> ")
+            ));
+        }
+
+        let graph = self.graphs.get(name).ok_or_else(|| {
+            format!(
+                "Noise graph {:?} refers to unknown graph {name:?}",
+                path.last().unwrap_or(&name)
+            )
+        })?;
+
+        let mut references = vec![];
+        graph.root.get_references(&mut references);
+
+        path.push(name);
+        for reference in references {
+            self.check_references(reference, path)?;
+        }
+        path.pop();
+
+        Ok(())
+    }
+}
+
+/// Runtime counterpart of [`ShiftNScale`], whose shift and scale are const generics.
+///
+/// [`ShiftNScale`]: crate::world_generation::chunk_generation::noise::shift_n_scale::ShiftNScale
+struct ShiftNScaleNode {
+    source: BoxedNoise,
+    shift: f64,
+    scale: f64,
+}
+
+impl NoiseFn<f64, 2> for ShiftNScaleNode {
+    fn get(&self, point: [f64; 2]) -> f64 {
+        (self.source.get(point) + self.shift) / self.scale
+    }
+}
@@ -0,0 +1,108 @@
+use crate::world_generation::chunk_generation::biome_registry::{Biome, BiomeRegistry};
+use crate::world_generation::chunk_generation::noise::lod_height_adjuster::from_lod_height;
+use crate::world_generation::chunk_generation::BlockType;
+use crate::world_generation::generation_options::GenerationOptions;
+use crate::world_generation::voxel_world::ChunkLod;
+use noise::{NoiseFn, ScalePoint, Simplex};
+use rand::prelude::StdRng;
+use rand::{Rng, SeedableRng};
+
+/// Ore veins are only a few voxels large, so they are left out of coarser lods.
+const ORE_MAX_LOD: ChunkLod = ChunkLod::Half;
+/// How far the borders between strata wobble up and down, in full lod voxels.
+const STRATA_WOBBLE: f64 = 6.;
+
+/// Picks the strata and ores of the biome registry for the stone below the surface.
+pub struct ChunkStrata<'a> {
+    biome_registry: &'a BiomeRegistry,
+    chunk_lod: ChunkLod,
+    wobble_noise: ScalePoint<Simplex>,
+    ore_noises: Vec<ScalePoint<Simplex>>,
+}
+
+impl<'a> ChunkStrata<'a> {
+    pub fn new(generation_options: &'a GenerationOptions, chunk_lod: ChunkLod) -> Self {
+        let mut rng = StdRng::seed_from_u64(generation_options.seed + 8);
+        let biome_registry = generation_options.biome_registry.as_ref();
+
+        Self {
+            biome_registry,
+            chunk_lod,
+            wobble_noise: ScalePoint::new(Simplex::new(rng.random())).set_scale(1. / 120.),
+            ore_noises: biome_registry
+                .ores
+                .iter()
+                .map(|ore| {
+                    ScalePoint::new(Simplex::new(rng.random())).set_scale(1. / ore.size as f64)
+                })
+                .collect(),
+        }
+    }
+
+    /// Whether ores are generated at this lod at all.
+    pub fn generates_ores(&self) -> bool {
+        self.chunk_lod.usize() <= ORE_MAX_LOD.usize()
+    }
+
+    /// Whether ores may replace the block, which holds for stone and the strata blocks.
+    pub fn is_stratum(&self, block: BlockType) -> bool {
+        block == BlockType::STONE
+            || self
+                .biome_registry
+                .strata
+                .iter()
+                .any(|stratum| stratum.block == block)
+    }
+
+    /// The ore at the lod height `y` of the column at `total_x`, `total_z`, whose heightmap
+    /// surface is at `noise_height`, if there is one.
+    pub fn get_ore(
+        &self,
+        total_x: i32,
+        y: i32,
+        total_z: i32,
+        noise_height: f32,
+        biome: &Biome,
+    ) -> Option<BlockType> {
+        if !self.generates_ores() {
+            return None;
+        }
+
+        let depth = (noise_height - y as f32) * self.chunk_lod.multiplier_f32();
+        let point = [
+            total_x as f64,
+            from_lod_height(y as f64, self.chunk_lod),
+            total_z as f64,
+        ];
+
+        self.biome_registry
+            .ores
+            .iter()
+            .zip(&self.ore_noises)
+            .find(|(ore, noise)| {
+                depth >= ore.min_depth
+                    && depth < ore.max_depth
+                    && ore.is_in_biome(biome)
+                    && noise.get(point) as f32 > ore.threshold
+            })
+            .map(|(ore, _)| ore.block)
+    }
+
+    /// Offset of the strata borders in the column at `total_x`, `total_z`, in full lod voxels.
+    pub fn get_wobble(&self, total_x: i32, total_z: i32) -> f32 {
+        (self.wobble_noise.get([total_x as f64, total_z as f64]) * STRATA_WOBBLE) as f32
+    }
+
+    /// The stone or stratum block at the lod height `y` of a column whose heightmap surface is at
+    /// `noise_height` and whose strata borders are offset by `wobble`.
+    pub fn get_stratum(&self, y: i32, noise_height: f32, wobble: f32) -> BlockType {
+        let depth = (noise_height - y as f32) * self.chunk_lod.multiplier_f32() + wobble;
+
+        self.biome_registry
+            .strata
+            .iter()
+            .rev()
+            .find(|stratum| depth >= stratum.min_depth)
+            .map_or(BlockType::STONE, |stratum| stratum.block)
+    }
+}
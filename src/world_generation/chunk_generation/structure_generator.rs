@@ -4,6 +4,8 @@ use fastnoise_lite::FastNoiseLite;
 use std::{cell::RefCell, collections::HashMap, rc::Rc, sync::Arc};
 
 pub struct VoxelStructureMetadata {
+    /// Biomes refer to structure generators by this name.
+    pub name: String,
     pub model_size: [i32; 3],
     pub generation_size: [i32; 2],
     pub grid_offset: [i32; 2],
@@ -1,24 +1,24 @@
-use crate::utils::div_floor;
-use crate::world_generation::chunk_generation::structure_generator::{
-    StructureGenerator, StructureGeneratorCache,
-};
-use crate::world_generation::chunk_generation::{BlockType, CHUNK_SIZE, VOXEL_SIZE};
+use crate::world_generation::chunk_generation::{chunk_size, VOXEL_SIZE};
 use crate::world_generation::chunk_loading::country_cache::{CountryCache, Path, PathLine};
+use crate::world_generation::chunk_loading::water_cache::{WaterMap, MAX_RIVER_DEPTH};
 use crate::world_generation::generation_options::GenerationOptions;
 use crate::world_generation::voxel_world::ChunkLod;
 use bevy::math::{DVec2, IVec2};
 use bevy::prelude::Vec2;
-use noise::{Add, Constant, Max, Min, MultiFractal, Multiply, NoiseFn, ScalePoint, Simplex};
+use noise::{Add, Constant, Multiply, NoiseFn, ScalePoint, Simplex};
 use rand::prelude::StdRng;
 use rand::{Rng, SeedableRng};
 use std::usize;
 
+use super::biome_registry::{Biome, ClimateNoise};
+use super::density_generation::{ChunkDensity, CAVE_DEPTH, OVERHANG_AMPLITUDE};
+use super::generation_pass::GeneratingChunk;
+use super::noise::biome_height_modifier::BiomeHeightModifier;
 use super::noise::full_cache::FullCache;
-use super::noise::gradient_fractal_noise::GFT;
 use super::noise::lod_height_adjuster::LodHeightAdjuster;
-use super::noise::shift_n_scale::ShiftNScale;
-use super::noise::smooth_step::SmoothStep;
+use super::noise::noise_graph::{BASE_TERRAIN_GRAPH, GRASS_COLOR_GRAPH, MOUNTAIN_BIOME_GRAPH};
 use super::noise::steepness::Steepness;
+use super::strata_generation::ChunkStrata;
 use super::voxel_types::VoxelData;
 
 pub fn generate_voxels(
@@ -27,328 +27,290 @@ pub fn generate_voxels(
     chunk_lod: ChunkLod,
     country_cache: &CountryCache,
 ) -> (VoxelData, i32, bool) {
-    let mut blocks = VoxelData::default();
+    let terrain = ChunkTerrain::new(position, generation_options, chunk_lod, country_cache);
+    let mut chunk = GeneratingChunk::default();
 
-    let terrain_noise = FullCache::new(LodHeightAdjuster::new(
-        get_terrain_noise(generation_options),
-        chunk_lod,
-    ));
-    let terrain_steepness = FullCache::new(Steepness::new(FullCache::new(get_terrain_noise(
-        generation_options,
-    ))));
-
-    let grass_color_noise = FullCache::new(get_grass_color_noise(generation_options));
-
-    let chunk_noise_offset = DVec2::new(position[0] as f64, position[2] as f64) * CHUNK_SIZE as f64;
-
-    let min_height = (get_min_in_noise_map(&terrain_noise, chunk_noise_offset, chunk_lod) as i32)
-        - 2
-        + position[1] * CHUNK_SIZE as i32
-        - 10 / chunk_lod.multiplier_i32();
-
-    let mut generate_more: bool = false;
-
-    let all_paths = vec![
-        &country_cache.this_path_cache.paths,
-        &country_cache.bottom_path_cache.paths,
-        &country_cache.left_path_cache.paths,
-    ];
-
-    let structure_generators: Vec<StructureGeneratorCache> = generation_options
-        .structure_generators
-        .iter()
-        .map(|structure_generator| StructureGeneratorCache::new(structure_generator))
-        .collect();
-
-    for x in 0..CHUNK_SIZE + 2 {
-        for z in 0..CHUNK_SIZE + 2 {
-            let total_x = position[0] * CHUNK_SIZE as i32 + x as i32 * chunk_lod.multiplier_i32();
-            let total_z = position[2] * CHUNK_SIZE as i32 + z as i32 * chunk_lod.multiplier_i32();
+    for pass in generation_options.generation_pipeline.iter() {
+        pass.generate(&terrain, &mut chunk);
+    }
 
-            let noise_position = [total_x as f64, total_z as f64];
+    chunk.blocks.compact();
 
-            //let dryness = value_noise.get([total_x as f64, total_z as f64]);
-            //let mountain = mountain_noise.get([total_x as f64, total_z as f64]);
+    (chunk.blocks, terrain.min_height, chunk.generate_above)
+}
 
-            let steepness = terrain_steepness.get(noise_position);
+/// Everything the generation passes of a chunk share. The columns are shaped before any pass
+/// runs, so paths and rivers have already moved the heights every pass builds on.
+pub struct ChunkTerrain<'a> {
+    pub generation_options: &'a GenerationOptions,
+    pub position: [i32; 3],
+    pub chunk_lod: ChunkLod,
+    /// Lod height of the lowest voxel of the padded chunk.
+    pub min_height: i32,
+    /// Columns of the padded chunk, indexed by `x + z * (chunk_size() + 2)`.
+    pub columns: Vec<TerrainColumn<'a>>,
+    pub terrain_noise: Box<dyn NoiseFn<f64, 2> + 'a>,
+    pub terrain_steepness: Box<dyn NoiseFn<f64, 2> + 'a>,
+    pub paths: Vec<&'a Vec<Path>>,
+    pub density: ChunkDensity,
+    pub strata: ChunkStrata<'a>,
+    pub water_map: WaterMap,
+    biome_lookup: Box<dyn Fn([f64; 2]) -> &'a Biome + 'a>,
+}
 
-            let mut noise_height = terrain_noise.get(noise_position) as f32;
+/// Shape of one column of a chunk, with heights in lod voxels.
+pub struct TerrainColumn<'a> {
+    pub x: usize,
+    pub z: usize,
+    pub total_x: i32,
+    pub total_z: i32,
+    /// Heightmap surface, with paths and rivers blended in.
+    pub noise_height: f32,
+    pub steepness: f64,
+    pub biome: &'a Biome,
+    pub is_path: bool,
+    pub water_height: Option<f32>,
+    pub overhang_strength: f32,
+    /// Top of the ground, overhangs included.
+    pub column_top: f32,
+    /// Caves are only carved between these heights.
+    pub cave_floor: f32,
+    pub cave_ceiling: f32,
+    /// Offset of the strata borders, in full lod voxels.
+    pub strata_wobble: f32,
+}
 
-            let grass_color = (grass_color_noise.get(noise_position) * 255.) as u8;
+impl<'a> ChunkTerrain<'a> {
+    pub fn new(
+        position: [i32; 3],
+        generation_options: &'a GenerationOptions,
+        chunk_lod: ChunkLod,
+        country_cache: &'a CountryCache,
+    ) -> Self {
+        let terrain_noise = FullCache::new(LodHeightAdjuster::new(
+            get_terrain_noise(generation_options),
+            chunk_lod,
+        ));
+        let terrain_steepness = FullCache::new(Steepness::new(FullCache::new(get_terrain_noise(
+            generation_options,
+        ))));
+
+        let base_terrain_noise = FullCache::new(get_base_terrain_noise(generation_options));
+        let climate_noise = get_climate_noise(generation_options);
+        let biome_lookup = move |point: [f64; 2]| {
+            generation_options
+                .biome_registry
+                .get_biome(climate_noise.get(point, base_terrain_noise.get(point)))
+        };
+
+        let chunk_noise_offset =
+            DVec2::new(position[0] as f64, position[2] as f64) * chunk_size() as f64;
+
+        let min_height =
+            (get_min_in_noise_map(&terrain_noise, chunk_noise_offset, chunk_lod) as i32) - 2
+                + position[1] * chunk_size() as i32
+                - (10
+                    + MAX_RIVER_DEPTH.ceil() as i32
+                    + if generation_options.generate_density {
+                        (CAVE_DEPTH + OVERHANG_AMPLITUDE) as i32
+                    } else {
+                        0
+                    })
+                    / chunk_lod.multiplier_i32();
+
+        let paths = vec![
+            &country_cache.this_path_cache.paths,
+            &country_cache.bottom_path_cache.paths,
+            &country_cache.left_path_cache.paths,
+        ];
+
+        let density = ChunkDensity::new(
+            generation_options,
+            IVec2::new(position[0], position[2]) * chunk_size() as i32,
+            min_height,
+            chunk_lod,
+        );
+
+        let water_map = WaterMap::new(
+            IVec2::new(position[0], position[2]) * chunk_size() as i32,
+            generation_options,
+        );
+
+        let strata = ChunkStrata::new(generation_options, chunk_lod);
+
+        let settings = &generation_options.terrain_settings;
+        let path_margin = IVec2::splat(settings.path_blend_distance.max(15.).ceil() as i32);
+
+        let mut columns = Vec::with_capacity((chunk_size() + 2) * (chunk_size() + 2));
+
+        for z in 0..chunk_size() + 2 {
+            for x in 0..chunk_size() + 2 {
+                let total_x =
+                    position[0] * chunk_size() as i32 + x as i32 * chunk_lod.multiplier_i32();
+                let total_z =
+                    position[2] * chunk_size() as i32 + z as i32 * chunk_lod.multiplier_i32();
+
+                let noise_position = [total_x as f64, total_z as f64];
+
+                let steepness = terrain_steepness.get(noise_position);
+
+                let mut noise_height = terrain_noise.get(noise_position) as f32;
+
+                let (mut path_distance, closest_point_on_path, _, line) =
+                    get_min_distance_to_path(IVec2::new(total_x, total_z), &paths, path_margin);
+                let is_path = path_distance <= settings.path_width;
+
+                // Scaled so the flattening ends at the path blend distance.
+                path_distance *= 1.65 / settings.path_blend_distance;
+
+                if path_distance <= 1.65 {
+                    let path_start_height =
+                        terrain_noise.get(line.unwrap().start.as_dvec2().to_array()) as f32;
+                    let path_end_height =
+                        terrain_noise.get(line.unwrap().end.as_dvec2().to_array()) as f32;
+                    let path_height = lerp(
+                        path_start_height,
+                        path_end_height,
+                        line.unwrap().get_progress_on_line(closest_point_on_path),
+                    );
 
-            let is_snow = noise_height * chunk_lod.multiplier_f32() > 3500. / VOXEL_SIZE;
-            let is_grass_steep = if is_snow {
-                steepness < 1.2
-            } else {
-                steepness < 0.8
-            };
+                    let closest_point_height =
+                        terrain_noise.get(closest_point_on_path.as_dvec2().to_array()) as f32;
+                    let closest_point_height = lerp(closest_point_height, noise_height, 0.5);
 
-            let (mut path_distance, closest_point_on_path, _, line) =
-                get_min_distance_to_path(IVec2::new(total_x, total_z), &all_paths, IVec2::ONE * 15);
-            let is_path = path_distance <= 8.75;
+                    let path_height = lerp(closest_point_height, path_height, 0.5);
 
-            path_distance /= 10.;
+                    noise_height = lerp(
+                        noise_height,
+                        path_height,
+                        (1.65 - path_distance.powi(2)).clamp(0., 1.),
+                    )
+                    .max(noise_height - 10.);
+                }
 
-            if path_distance <= 1.65 {
-                let path_start_height =
-                    terrain_noise.get(line.unwrap().start.as_dvec2().to_array()) as f32;
-                let path_end_height =
-                    terrain_noise.get(line.unwrap().end.as_dvec2().to_array()) as f32;
-                let path_height = lerp(
-                    path_start_height,
-                    path_end_height,
-                    line.unwrap().get_progress_on_line(closest_point_on_path),
+                let water_height = water_map.carve_column(
+                    IVec2::new(total_x, total_z),
+                    &mut noise_height,
+                    chunk_lod,
                 );
 
-                let closest_point_height =
-                    terrain_noise.get(closest_point_on_path.as_dvec2().to_array()) as f32;
-                let closest_point_height = lerp(closest_point_height, noise_height, 0.5);
-
-                let path_height = lerp(closest_point_height, path_height, 0.5);
-
-                noise_height = lerp(
+                // Overhangs only grow out of steep terrain that isn't shaped by paths or water.
+                let overhang_strength = if path_distance <= 1.65 || water_height.is_some() {
+                    0.
+                } else {
+                    ((steepness - settings.overhang_steepness) / 0.8).clamp(0., 1.) as f32
+                };
+                let column_top = noise_height + density.get_overhang_height() * overhang_strength;
+
+                // Caves keep a few voxels of ground below water, so it doesn't drain into them.
+                let cave_ceiling = if water_height.is_some() {
+                    noise_height - (4 / chunk_lod.multiplier_i32()).max(1) as f32
+                } else {
+                    f32::MAX
+                };
+                let cave_floor = density.get_cave_floor(noise_height);
+
+                columns.push(TerrainColumn {
+                    x,
+                    z,
+                    total_x,
+                    total_z,
                     noise_height,
-                    path_height,
-                    (1.65 - path_distance.powi(2)).clamp(0., 1.),
-                )
-                .max(noise_height - 10.);
+                    steepness,
+                    biome: biome_lookup(noise_position),
+                    is_path,
+                    water_height,
+                    overhang_strength,
+                    column_top,
+                    cave_floor,
+                    cave_ceiling,
+                    strata_wobble: strata.get_wobble(total_x, total_z),
+                });
             }
+        }
 
-            for y in
-                min_height..noise_height.min((CHUNK_SIZE as i32 + 2 + min_height) as f32) as i32
-            {
-                if y == CHUNK_SIZE as i32 + 1 + min_height {
-                    generate_more = true;
-                }
-                blocks.set_block(
-                    [x as i32, y as i32 - min_height, z as i32],
-                    // BlockType::Gray((biome_noise.get([total_x as f64, total_z as f64]) * 255.) as u8)
-                    if is_path {
-                        BlockType::Path
-                    } else {
-                        if is_grass_steep && y + 1 == noise_height.floor() as i32 {
-                            if is_snow {
-                                BlockType::Snow
-                            } else {
-                                BlockType::Grass
-                            }
-                        } else {
-                            BlockType::Stone
-                        }
-                    },
-                );
-            }
-
-            for structure_generator in &structure_generators {
-                let structure_metadata = structure_generator.get_structure_metadata();
-                let structure_offset_x = div_floor(
-                    total_x + structure_metadata.grid_offset[0],
-                    structure_metadata.generation_size[0],
-                );
-                let structure_offset_z = div_floor(
-                    total_z + structure_metadata.grid_offset[1],
-                    structure_metadata.generation_size[1],
-                );
-                let structure_value = structure_metadata
-                    .noise
-                    .get_noise_2d(structure_offset_x as f32, structure_offset_z as f32)
-                    * 0.5
-                    + 0.5;
-                if structure_metadata.generate_debug_blocks {
-                    let top_terrain = (noise_height.min(CHUNK_SIZE as f32 + min_height as f32)
-                        as i32
-                        - min_height.min(noise_height as i32))
-                    .max(1) as usize
-                        - 1;
-                    let current_color =
-                        match blocks.get_block([x as i32, top_terrain as i32, z as i32]) {
-                            // BlockType::StructureDebug(r, g, b) => (r, g, b),
-                            _ => (0u8, 0u8, 0u8),
-                        };
-                    blocks.set_block([x as i32, top_terrain as i32, z as i32], BlockType::Stone);
-                }
-                let mut rand = StdRng::seed_from_u64((structure_value.abs() * 10000.) as u64);
-
-                if structure_value > 0. {
-                    let random_x = rand.random_range(
-                        0..=structure_metadata.generation_size[0]
-                            - structure_metadata.model_size[0],
-                    );
-                    let random_z = rand.random_range(
-                        0..=structure_metadata.generation_size[1]
-                            - structure_metadata.model_size[2],
-                    );
-
-                    let structure_x: i32 = (total_x + structure_metadata.grid_offset[0]
-                        - structure_offset_x * structure_metadata.generation_size[0])
-                        .abs()
-                        - random_x;
-                    let structure_z: i32 = (total_z + structure_metadata.grid_offset[1]
-                        - structure_offset_z * structure_metadata.generation_size[1])
-                        .abs()
-                        - random_z;
-
-                    if structure_x < 0
-                        || structure_z < 0
-                        || structure_x >= structure_metadata.model_size[0]
-                        || structure_z >= structure_metadata.model_size[2]
-                    {
-                        continue;
-                    }
-
-                    let structure_noise_height_x = structure_offset_x
-                        * structure_metadata.generation_size[0]
-                        + (structure_metadata.model_size[0] / 2)
-                        - structure_metadata.grid_offset[0]
-                        + random_x;
-                    let structure_noise_height_z = structure_offset_z
-                        * structure_metadata.generation_size[1]
-                        + (structure_metadata.model_size[2] / 2)
-                        - structure_metadata.grid_offset[1]
-                        + random_z;
-
-                    let structure_steepness = terrain_steepness.get([
-                        structure_noise_height_x as f64,
-                        structure_noise_height_z as f64,
-                    ]);
-
-                    if structure_steepness > 0.8 {
-                        continue;
-                    }
-
-                    let structure_center: IVec2 =
-                        [structure_noise_height_x, structure_noise_height_z].into();
-
-                    let (a, _, _, _) = get_min_distance_to_path(
-                        structure_center,
-                        &all_paths,
-                        IVec2::new(
-                            structure_metadata.model_size[0] / 2,
-                            structure_metadata.model_size[2] / 2,
-                        ) + IVec2::ONE * 10,
-                    );
-
-                    if (a as i32)
-                        < structure_metadata.model_size[0] / 2
-                            + structure_metadata.model_size[1] / 2
-                    {
-                        continue;
-                    }
-
-                    let noise_height = terrain_noise.get([
-                        structure_noise_height_x as f64,
-                        structure_noise_height_z as f64,
-                    ]);
-
-                    for (index, sub_structure) in structure_generator.get_structure_model(
-                        IVec2 {
-                            x: structure_offset_x,
-                            y: structure_offset_z,
-                        },
-                        chunk_lod,
-                    )[structure_x as usize]
-                        .iter()
-                        .enumerate()
-                    {
-                        if (index as i32
-                            + (noise_height * chunk_lod.multiplier_i32() as f64) as i32)
-                            % chunk_lod.multiplier_i32()
-                            != 0
-                        {
-                            continue;
-                        }
-                        let chunk_index = index / chunk_lod.multiplier_i32() as usize;
-                        if (noise_height as i32 - min_height + chunk_index as i32) < 0 {
-                            continue;
-                        }
-                        let structure_block = sub_structure[structure_z as usize];
-                        if structure_block == BlockType::Air {
-                            continue;
-                        }
-                        if noise_height as i32 + chunk_index as i32 - min_height
-                            >= CHUNK_SIZE as i32 + 2
-                        {
-                            generate_more = true;
-                            break;
-                        }
-                        blocks.set_block(
-                            [
-                                x as i32,
-                                noise_height as i32 + chunk_index as i32 - min_height as i32,
-                                z as i32,
-                            ],
-                            structure_block,
-                        );
-                    }
-                }
-            }
+        Self {
+            generation_options,
+            position,
+            chunk_lod,
+            min_height,
+            columns,
+            terrain_noise: Box::new(terrain_noise),
+            terrain_steepness: Box::new(terrain_steepness),
+            paths,
+            density,
+            strata,
+            water_map,
+            biome_lookup: Box::new(biome_lookup),
         }
     }
 
-    (blocks, min_height, generate_more)
+    /// Biome at a full lod position, which doesn't have to lie inside the chunk.
+    pub fn get_biome(&self, point: [f64; 2]) -> &'a Biome {
+        (self.biome_lookup)(point)
+    }
+
+    /// Lod height of the padded chunk voxels top, the first height another chunk has to
+    /// generate.
+    pub fn get_chunk_top(&self) -> i32 {
+        chunk_size() as i32 + 2 + self.min_height
+    }
 }
 
 pub fn get_grass_color_noise(generation_options: &GenerationOptions) -> impl NoiseFn<f64, 2> {
-    let mut rng = StdRng::seed_from_u64(generation_options.seed + 3);
-    SmoothStep::new(Min::new(
+    generation_options
+        .noise_graphs
+        .build(GRASS_COLOR_GRAPH, generation_options.seed)
+}
+
+pub fn get_mountain_biome_noise(generation_options: &GenerationOptions) -> impl NoiseFn<f64, 2> {
+    generation_options
+        .noise_graphs
+        .build(MOUNTAIN_BIOME_GRAPH, generation_options.seed)
+}
+
+pub fn get_climate_noise(
+    generation_options: &GenerationOptions,
+) -> ClimateNoise<impl NoiseFn<f64, 2>, impl NoiseFn<f64, 2>> {
+    let mut rng = StdRng::seed_from_u64(generation_options.seed + 4);
+    ClimateNoise::new(
         Multiply::new(
             Add::new(
-                ScalePoint::new(Simplex::new(rng.random())).set_scale(0.5f64.powi(14)),
+                ScalePoint::new(Simplex::new(rng.random())).set_scale(0.5f64.powi(13)),
                 Constant::new(1.),
             ),
             Constant::new(0.5),
         ),
         Multiply::new(
             Add::new(
-                ScalePoint::new(Simplex::new(rng.random())).set_scale(0.5f64.powi(14)),
+                ScalePoint::new(Simplex::new(rng.random())).set_scale(0.5f64.powi(13)),
                 Constant::new(1.),
             ),
             Constant::new(0.5),
         ),
-    ))
-    .set_steps(6.)
-    .set_smoothness(0.5)
-}
-
-pub fn get_mountain_biome_noise(generation_options: &GenerationOptions) -> impl NoiseFn<f64, 2> {
-    let mut rng = StdRng::seed_from_u64(generation_options.seed + 2);
-    Multiply::new(
-        SmoothStep::new(Multiply::new(
-            Add::new(
-                ScalePoint::new(Simplex::new(rng.random())).set_scale(0.5f64.powi(15)),
-                Constant::new(1.),
-            ),
-            Constant::new(0.5),
-        ))
-        .set_steps(4.)
-        .set_smoothness(0.5),
-        GFT::new_with_source(Max::new(
-            ShiftNScale::<_, 2, 1>::new(Simplex::new(rng.random())),
-            Constant::new(0.),
-        ))
-        .set_frequency(0.5f64.powi(13))
-        .set_amplitude(3750.)
-        .set_octaves(11)
-        .set_gradient(1.),
+        generation_options.sea_level,
+        generation_options.terrain_settings.snow_height,
     )
 }
 
 pub fn get_terrain_noise(generation_options: &GenerationOptions) -> impl NoiseFn<f64, 2> {
-    let mut rng = StdRng::seed_from_u64(generation_options.seed + 1);
+    BiomeHeightModifier::new(
+        get_base_terrain_noise(generation_options),
+        get_climate_noise(generation_options),
+        generation_options.biome_registry.clone(),
+        generation_options.sea_level,
+    )
+}
 
+/// Terrain height before the biome height modifiers are applied.
+pub fn get_base_terrain_noise(generation_options: &GenerationOptions) -> impl NoiseFn<f64, 2> {
     Add::new(
         Multiply::new(
-            ScalePoint::new(Add::new(
-                get_mountain_biome_noise(generation_options),
-                GFT::new_with_source(Max::new(
-                    ShiftNScale::<_, 2, 1>::new(Simplex::new(rng.random())),
-                    Constant::new(0.),
-                ))
-                .set_frequency(0.5f64.powi(13))
-                .set_amplitude(500.)
-                .set_octaves(11)
-                .set_gradient(1.),
-            ))
+            ScalePoint::new(
+                generation_options
+                    .noise_graphs
+                    .build(BASE_TERRAIN_GRAPH, generation_options.seed),
+            )
             .set_scale(VOXEL_SIZE as f64),
             Constant::new(1. / VOXEL_SIZE as f64),
         ),
@@ -390,8 +352,8 @@ fn get_min_in_noise_map(
 ) -> f64 {
     let mut min = noise.get(chunk_offset.to_array());
 
-    for x in 0..CHUNK_SIZE {
-        for z in 0..CHUNK_SIZE {
+    for x in 0..chunk_size() {
+        for z in 0..chunk_size() {
             let current = noise.get([
                 x as f64 * chunk_lod.multiplier_i32() as f64 + chunk_offset.x,
                 z as f64 * chunk_lod.multiplier_i32() as f64 + chunk_offset.y,
@@ -405,7 +367,7 @@ fn get_min_in_noise_map(
     min
 }
 
-fn get_min_distance_to_path<'a>(
+pub fn get_min_distance_to_path<'a>(
     pos: IVec2,
     paths_list: &'a Vec<&'a Vec<Path>>,
     margin: IVec2,
@@ -10,7 +10,8 @@ use bevy::{
     render::render_resource::{ShaderSize, ShaderType},
 };
 
-use super::{BlockType, CHUNK_SIZE};
+use super::light_propagation::Light;
+use super::{chunk_size, BlockType};
 
 #[derive(Debug, Clone, ShaderType, Default, Copy)]
 pub struct Vec4<T: ShaderSize> {
@@ -46,42 +47,232 @@ impl<T: ShaderSize> IndexMut<usize> for Vec4<T> {
     }
 }
 
-pub type VoxelArray = [BlockType; (CHUNK_SIZE + 2) * (CHUNK_SIZE + 2) * (CHUNK_SIZE + 2)];
 pub type VoxelPalette = [Vec4<u32>; 128];
 
+fn voxel_count() -> usize {
+    (chunk_size() + 2).pow(3)
+}
+
+/// Blocks of a padded chunk. Chunks made of a single block, like the air above the terrain or the
+/// stone below it, only store that block. Everything else stores a palette of the used blocks and
+/// bit packed indices into it.
+///
+/// Next to the blocks every voxel has a [`Light`], stored the same way. Most chunks only see a few
+/// light levels, so their light takes a few bits per voxel. Voxels that were never lit count as
+/// open sky.
+#[derive(Clone)]
 pub struct VoxelData {
-    pub array: VoxelArray,
+    storage: VoxelStorage<BlockType>,
+    light: VoxelStorage<Light>,
 }
 
-impl Default for VoxelData {
-    fn default() -> Self {
+#[derive(Clone)]
+enum VoxelStorage<T> {
+    Single(T),
+    Paletted(PalettedVoxels<T>),
+}
+
+/// Palette indices packed into words with 1, 2, 4 or 8 bits per voxel, so no index spans two
+/// words.
+#[derive(Clone)]
+struct PalettedVoxels<T> {
+    palette: Vec<T>,
+    bits: u32,
+    words: Vec<u64>,
+}
+
+impl<T: Copy + PartialEq> PalettedVoxels<T> {
+    fn new(palette: Vec<T>, bits: u32) -> Self {
         Self {
-            array: [BlockType::Air; (CHUNK_SIZE + 2) * (CHUNK_SIZE + 2) * (CHUNK_SIZE + 2)],
+            palette,
+            bits,
+            words: vec![0; voxel_count().div_ceil(64 / bits as usize)],
+        }
+    }
+
+    fn get_bits(palette_size: usize) -> u32 {
+        match palette_size {
+            0..=2 => 1,
+            3..=4 => 2,
+            5..=16 => 4,
+            _ => 8,
+        }
+    }
+
+    fn get_palette_index(&self, index: usize) -> usize {
+        let voxels_per_word = 64 / self.bits as usize;
+        let shift = (index % voxels_per_word) as u32 * self.bits;
+        let mask = (1u64 << self.bits) - 1;
+
+        ((self.words[index / voxels_per_word] >> shift) & mask) as usize
+    }
+
+    fn set_palette_index(&mut self, index: usize, palette_index: usize) {
+        let voxels_per_word = 64 / self.bits as usize;
+        let shift = (index % voxels_per_word) as u32 * self.bits;
+        let mask = (1u64 << self.bits) - 1;
+
+        let word = &mut self.words[index / voxels_per_word];
+        *word = (*word & !(mask << shift)) | ((palette_index as u64) << shift);
+    }
+
+    fn get_or_insert(&mut self, value: T) -> usize {
+        if let Some(palette_index) = self.palette.iter().position(|entry| *entry == value) {
+            return palette_index;
+        }
+
+        if self.palette.len() == 1 << self.bits {
+            let bits = Self::get_bits(self.palette.len() + 1);
+            self.repack(self.palette.clone(), bits, |palette_index| palette_index);
+        }
+
+        self.palette.push(value);
+        self.palette.len() - 1
+    }
+
+    /// Rewrites every voxel for a new palette and index size.
+    fn repack(&mut self, palette: Vec<T>, bits: u32, remap: impl Fn(usize) -> usize) {
+        let mut repacked = Self::new(palette, bits);
+
+        for index in 0..voxel_count() {
+            repacked.set_palette_index(index, remap(self.get_palette_index(index)));
         }
+
+        *self = repacked;
+    }
+}
+
+impl<T: Copy + PartialEq> VoxelStorage<T> {
+    fn get(&self, index: usize) -> T {
+        match self {
+            VoxelStorage::Single(value) => *value,
+            VoxelStorage::Paletted(voxels) => voxels.palette[voxels.get_palette_index(index)],
+        }
+    }
+
+    fn set(&mut self, index: usize, value: T) {
+        if let VoxelStorage::Single(current_value) = *self {
+            if current_value == value {
+                return;
+            }
+
+            *self = VoxelStorage::Paletted(PalettedVoxels::new(vec![current_value], 1));
+        }
+
+        if let VoxelStorage::Paletted(voxels) = self {
+            let palette_index = voxels.get_or_insert(value);
+            voxels.set_palette_index(index, palette_index);
+        }
+    }
+
+    /// Drops palette entries no voxel uses anymore, going back to a single value if only one is
+    /// left.
+    fn compact(&mut self) {
+        let VoxelStorage::Paletted(voxels) = self else {
+            return;
+        };
+
+        let mut used = vec![false; voxels.palette.len()];
+        for index in 0..voxel_count() {
+            used[voxels.get_palette_index(index)] = true;
+        }
+
+        let used_values = voxels
+            .palette
+            .iter()
+            .zip(&used)
+            .filter(|(_, used)| **used)
+            .map(|(value, _)| *value)
+            .collect::<Vec<_>>();
+
+        if used_values.len() == 1 {
+            *self = VoxelStorage::Single(used_values[0]);
+            return;
+        }
+
+        if used_values.len() == voxels.palette.len() {
+            return;
+        }
+
+        let remapped_indices = used
+            .iter()
+            .scan(0, |next_index, used| {
+                let index = *next_index;
+                *next_index += *used as usize;
+                Some(index)
+            })
+            .collect::<Vec<_>>();
+
+        let bits = PalettedVoxels::<T>::get_bits(used_values.len());
+        voxels.repack(used_values, bits, |palette_index| {
+            remapped_indices[palette_index]
+        });
+    }
+}
+
+impl Default for VoxelData {
+    fn default() -> Self {
+        Self::filled(BlockType::AIR)
     }
 }
 
 impl VoxelData {
+    pub fn filled(block: BlockType) -> Self {
+        Self {
+            storage: VoxelStorage::Single(block),
+            light: VoxelStorage::Single(Light::SKY),
+        }
+    }
+
     pub fn is_air<T: Into<IVec3>>(&self, position: T) -> bool {
-        let index = Self::position_to_indexes(position);
-        self.array[index] == BlockType::Air
+        self.get_block(position) == BlockType::AIR
     }
 
     pub fn get_block<T: Into<IVec3>>(&self, position: T) -> BlockType {
-        let index = Self::position_to_indexes(position);
-        self.array[index]
+        self.storage.get(Self::position_to_indexes(position))
     }
 
     pub fn set_block<T: Into<IVec3>>(&mut self, position: T, block: BlockType) {
-        let index = Self::position_to_indexes(position);
-        self.array[index] = block;
+        self.storage.set(Self::position_to_indexes(position), block);
+    }
+
+    /// Whether every voxel is the same block, without looking at the voxels one by one.
+    pub fn get_single_block(&self) -> Option<BlockType> {
+        match self.storage {
+            VoxelStorage::Single(block) => Some(block),
+            VoxelStorage::Paletted(_) => None,
+        }
+    }
+
+    /// Drops palette entries no voxel uses anymore, going back to a single block if only one is
+    /// left. Worth calling once a chunk is done being filled.
+    pub fn compact(&mut self) {
+        self.storage.compact();
+    }
+
+    pub fn get_light<T: Into<IVec3>>(&self, position: T) -> Light {
+        self.light.get(Self::position_to_indexes(position))
+    }
+
+    pub fn set_light<T: Into<IVec3>>(&mut self, position: T, light: Light) {
+        self.light.set(Self::position_to_indexes(position), light);
+    }
+
+    pub fn fill_light(&mut self, light: Light) {
+        self.light = VoxelStorage::Single(light);
+    }
+
+    /// Drops light levels no voxel has anymore, going back to a single light value if every voxel
+    /// has the same one.
+    pub fn compact_light(&mut self) {
+        self.light.compact();
     }
 
     fn position_to_indexes<T: Into<IVec3>>(position: T) -> usize {
         let position: IVec3 = position.into();
         let index = position.x as usize
-            + (position.y as usize * (CHUNK_SIZE + 2))
-            + (position.z as usize * (CHUNK_SIZE + 2) * (CHUNK_SIZE + 2));
+            + (position.y as usize * (chunk_size() + 2))
+            + (position.z as usize * (chunk_size() + 2) * (chunk_size() + 2));
         index
     }
 }
@@ -1,3 +1,6 @@
 pub mod chunk_loader;
+pub mod chunk_scheduler;
 pub mod country_cache;
+pub mod country_disk_cache;
 pub mod quad_tree_data;
+pub mod water_cache;
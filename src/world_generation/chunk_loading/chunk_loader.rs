@@ -1,55 +1,172 @@
 use crate::animations::DespawnAnimation;
 use crate::world_generation::chunk_generation::{
-    ChunkGenerationTask, ChunkGenerator, ChunkParent, CHUNK_SIZE, VOXEL_SIZE,
+    chunk_size, get_lod_area, mark_chunks_around, regenerate_chunk, CacheGenerationTask, Chunk,
+    ChunkGenerationTask, ChunkGenerator, ChunkLodInfo, ChunkParent, ChunkTaskGenerator, VOXEL_SIZE,
 };
-use crate::world_generation::voxel_world::{ChunkLod, QuadTreeVoxelWorld, VoxelWorld, MAX_LOD};
+use crate::world_generation::chunk_settings::chunk_settings;
+use crate::world_generation::generation_options::GenerationOptionsResource;
+use crate::world_generation::voxel_world::{max_lod, ChunkLod, QuadTreeVoxelWorld, VoxelWorld};
+use crate::world_generation::world_save::world_save_loaded;
 use bevy::ecs::schedule::IntoScheduleConfigs;
 use bevy::log::info;
+use bevy::math::{IVec2, Vec2, Vec3Swizzles};
 use bevy::prelude::{
-    App, Commands, Component, Entity, Plugin, Query, ResMut, Transform, Update, Vec3,
+    App, ChildOf, Commands, Component, Entity, Event, EventReader, Plugin, Query, ResMut,
+    Transform, Update, Vec3, With, Without,
 };
+use std::ops::Range;
 
 pub struct ChunkLoaderPlugin;
 
 impl Plugin for ChunkLoaderPlugin {
     fn build(&self, app: &mut App) {
-        app.add_systems(
+        app.add_event::<RegenerateWorld>().add_systems(
             Update,
-            (load_chunks, unload_chunks).after_ignore_deferred(
-                crate::world_generation::chunk_generation::upgrade_quad_trees,
-            ),
+            (
+                regenerate_chunks.before(load_chunks),
+                load_chunks.run_if(world_save_loaded),
+                unload_chunks,
+            )
+                .after_ignore_deferred(
+                    crate::world_generation::chunk_generation::upgrade_quad_trees,
+                ),
         );
     }
 }
 
+/// Drops every country cache and generates every chunk again from the current generation options.
+/// The chunks keep their meshes until the new ones arrive, and edits are applied to them again.
+#[derive(Event)]
+pub struct RegenerateWorld;
+
+/// Shape of the area around a loader, used for its load, unload and lod ranges.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum ChunkLoaderShape {
+    Square,
+    Circle,
+    /// Circle stretched towards the forward direction of the loader, reaching up to
+    /// `1 + forward_bias` times its ranges straight ahead and its plain ranges behind it.
+    Frustum {
+        forward_bias: f32,
+    },
+}
+
+impl ChunkLoaderShape {
+    /// Distance in chunks from the loader to a chunk `offset` chunks away, which is compared
+    /// against the ranges of the loader.
+    pub fn distance(self, offset: Vec2, forward: Vec2) -> f32 {
+        match self {
+            ChunkLoaderShape::Square => offset.abs().max_element(),
+            ChunkLoaderShape::Circle => offset.length(),
+            ChunkLoaderShape::Frustum { forward_bias } => {
+                let alignment = offset
+                    .normalize_or_zero()
+                    .dot(forward.normalize_or_zero())
+                    .max(0.0);
+                offset.length() / (1.0 + forward_bias * alignment)
+            }
+        }
+    }
+
+    /// How many times its ranges the shape reaches at most.
+    pub fn max_stretch(self) -> f32 {
+        match self {
+            ChunkLoaderShape::Square | ChunkLoaderShape::Circle => 1.0,
+            ChunkLoaderShape::Frustum { forward_bias } => 1.0 + forward_bias.max(0.0),
+        }
+    }
+}
+
 #[derive(Component)]
 pub struct ChunkLoader {
+    pub shape: ChunkLoaderShape,
+    /// Range in max lod chunks that is loaded around the loader.
     pub load_range: i32,
+    /// Max lod chunks this far or farther from every loader are unloaded.
     pub unload_range: i32,
-    pub lod_range: [i32; MAX_LOD.usize() - 1],
+    /// Range in chunks of each lod, starting at the half lod, within which chunks of that lod are
+    /// divided into finer ones. Lods past the end use the last range.
+    pub lod_range: Vec<i32>,
+    /// Heights in voxels relative to the loader, from below to above it, between which chunks are
+    /// stacked onto each other, without a limit when `None`. Columns stack from the bottom, so a
+    /// column waits at its first chunk outside of the window until the loader comes closer.
+    pub vertical_range: Option<Range<f32>>,
+    /// Weight of the loader. Its lod ranges are scaled by it and the scheduler divides the
+    /// distance to its chunks by it, so loaders below 1 keep their chunks loaded with less detail
+    /// and after the other loaders. At 0 the chunks stay at the coarsest lod.
+    pub priority: f32,
 }
 
 impl Default for ChunkLoader {
     fn default() -> Self {
         Self {
-            load_range: 8,
-            unload_range: 10,
-            lod_range: [2, 2, 2, 2, 2, 2, 2],
+            shape: ChunkLoaderShape::Square,
+            load_range: chunk_settings().load_range,
+            unload_range: chunk_settings().unload_range,
+            lod_range: vec![chunk_settings().lod_range; max_lod().usize() - 1],
+            vertical_range: None,
+            priority: 1.0,
         }
     }
 }
 
+impl ChunkLoader {
+    /// Distance in chunks of `lod` from the loader to the chunk at `chunk_pos`.
+    pub fn get_distance(&self, transform: &Transform, lod: ChunkLod, chunk_pos: [i32; 2]) -> f32 {
+        let loader_chunk_pos = get_chunk_position(transform.translation, lod);
+        let offset = IVec2::from_array(chunk_pos) - IVec2::from_array(loader_chunk_pos);
+
+        self.shape
+            .distance(offset.as_vec2(), transform.forward().as_vec3().xz())
+    }
+
+    pub fn get_lod_range(&self, lod: ChunkLod) -> i32 {
+        self.lod_range
+            .get(lod.usize() - 2)
+            .or(self.lod_range.last())
+            .copied()
+            .unwrap_or(0)
+    }
+
+    /// Whether the chunk of `lod` at `chunk_pos` is divided into chunks of the next finer lod.
+    pub fn divides(&self, transform: &Transform, lod: ChunkLod, chunk_pos: [i32; 2]) -> bool {
+        self.get_distance(transform, lod, chunk_pos)
+            < (self.get_lod_range(lod) + 1) as f32 * self.priority
+    }
+
+    /// Whether the loader keeps the max lod chunk at `chunk_pos` from being unloaded.
+    pub fn keeps_loaded(&self, transform: &Transform, chunk_pos: [i32; 2]) -> bool {
+        self.get_distance(transform, max_lod(), chunk_pos) < self.unload_range as f32
+    }
+
+    /// Whether chunks reaching from `bottom` to `top` voxels overlap the vertical range.
+    pub fn reaches_heights(&self, transform: &Transform, bottom: f32, top: f32) -> bool {
+        self.vertical_range.as_ref().is_none_or(|vertical_range| {
+            bottom <= transform.translation.y + vertical_range.end
+                && top >= transform.translation.y + vertical_range.start
+        })
+    }
+}
+
 fn load_chunks(
     mut voxel_world: ResMut<QuadTreeVoxelWorld>,
     mut commands: Commands,
     chunk_loaders: Query<(&ChunkLoader, &Transform)>,
 ) {
     for (chunk_loader, transform) in &chunk_loaders {
-        let loader_chunk_pos = get_chunk_position(transform.translation, MAX_LOD);
+        let loader_chunk_pos = get_chunk_position(transform.translation, max_lod());
+        let reach =
+            (chunk_loader.load_range as f32 * chunk_loader.shape.max_stretch()).ceil() as i32;
 
-        for x in -chunk_loader.load_range..chunk_loader.load_range + 1 {
-            for z in -chunk_loader.load_range..chunk_loader.load_range + 1 {
+        for x in -reach..reach + 1 {
+            for z in -reach..reach + 1 {
                 let chunk_pos = [loader_chunk_pos[0] + x, loader_chunk_pos[1] + z];
+                if chunk_loader.get_distance(transform, max_lod(), chunk_pos)
+                    > chunk_loader.load_range as f32
+                {
+                    continue;
+                }
+
                 if !voxel_world.has_chunk(chunk_pos) {
                     commands.spawn((ChunkGenerator(chunk_pos), ChunkParent(chunk_pos)));
                     if !voxel_world.add_chunk(chunk_pos, None) {
@@ -74,10 +191,7 @@ fn unload_chunks(
         let chunk_position = chunk_parent.0;
 
         for (chunk_loader, chunk_loader_transform) in &chunk_loaders {
-            let loader_chunk_pos = get_chunk_position(chunk_loader_transform.translation, MAX_LOD);
-            if (chunk_position[0] - loader_chunk_pos[0]).abs() < chunk_loader.unload_range
-                && (chunk_position[1] - loader_chunk_pos[1]).abs() < chunk_loader.unload_range
-            {
+            if chunk_loader.keeps_loaded(chunk_loader_transform, chunk_position) {
                 should_unload = false;
                 break;
             }
@@ -88,12 +202,18 @@ fn unload_chunks(
         }
 
         if voxel_world.remove_chunk(chunk_position) {
+            mark_chunks_around(
+                &mut commands,
+                &voxel_world,
+                get_lod_area(chunk_position, max_lod(), [0, 0]),
+            );
+
             let mut chunk_owner = commands.entity(entity);
             chunk_owner
                 .remove::<ChunkParent>()
                 .insert(DespawnAnimation::default());
             for child in &children {
-                if child.1 .1 == entity {
+                if child.1 .1 .4 == entity {
                     info!("Cancelled Child!");
                     commands.entity(child.0).remove::<ChunkGenerationTask>();
                 }
@@ -102,11 +222,52 @@ fn unload_chunks(
     }
 }
 
+/// Queues every generated chunk to be generated again in place, so the quadtree and the meshes stay
+/// as they are until the results arrive. Running generation tasks use the old options and start
+/// over, chunks that haven't been generated yet pick up the new options on their own.
+fn regenerate_chunks(
+    mut regenerate_events: EventReader<RegenerateWorld>,
+    mut generation_options: ResMut<GenerationOptionsResource>,
+    mut commands: Commands,
+    generated_chunks: Query<(Entity, &Chunk, &ChunkLodInfo, &ChildOf)>,
+    chunk_tasks: Query<(Entity, &ChunkGenerationTask), Without<Chunk>>,
+    cache_tasks: Query<Entity, With<CacheGenerationTask>>,
+) {
+    if regenerate_events.read().last().is_none() {
+        return;
+    }
+
+    generation_options.1.clear();
+    for entity in &cache_tasks {
+        commands.entity(entity).despawn();
+    }
+
+    for (entity, chunk, lod_info, child_of) in &generated_chunks {
+        regenerate_chunk(
+            &mut commands.entity(entity),
+            ChunkTaskGenerator(
+                IVec2::new(chunk.0[0], chunk.0[2]),
+                lod_info.lod,
+                lod_info.lod_position,
+                chunk.0[1],
+                child_of.parent(),
+            ),
+        );
+    }
+
+    for (entity, task) in &chunk_tasks {
+        commands
+            .entity(entity)
+            .remove::<ChunkGenerationTask>()
+            .insert(task.1);
+    }
+}
+
 pub fn get_chunk_position(global_position: Vec3, lod: ChunkLod) -> [i32; 2] {
     [
-        (global_position.x / (CHUNK_SIZE as f32 * VOXEL_SIZE * lod.multiplier_f32())).floor()
+        (global_position.x / (chunk_size() as f32 * VOXEL_SIZE * lod.multiplier_f32())).floor()
             as i32,
-        (global_position.z / (CHUNK_SIZE as f32 * VOXEL_SIZE * lod.multiplier_f32())).floor()
+        (global_position.z / (chunk_size() as f32 * VOXEL_SIZE * lod.multiplier_f32())).floor()
             as i32,
     ]
 }
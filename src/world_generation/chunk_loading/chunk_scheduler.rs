@@ -0,0 +1,114 @@
+use crate::world_generation::chunk_generation::{chunk_size, ChunkTaskGenerator, VOXEL_SIZE};
+use crate::world_generation::chunk_loading::chunk_loader::ChunkLoader;
+use bevy::math::{Affine3A, Vec2, Vec3, Vec3Swizzles};
+use bevy::prelude::{Reflect, ReflectResource, Resource, Transform};
+use bevy::render::primitives::{Aabb, Frustum};
+
+/// Half the height of the column a pending chunk is tested against the camera frustums with, as
+/// its vertical position is only known once it is generated.
+const CHUNK_COLUMN_HALF_HEIGHT: f32 = 8192.0;
+
+/// Decides how many chunks generate at the same time and which pending chunks go first.
+#[derive(Resource, Reflect)]
+#[reflect(Resource)]
+pub struct ChunkScheduler {
+    /// Chunk generation tasks that run at the same time.
+    pub max_tasks: usize,
+    /// Multiplies the distance of chunks that no active camera can see.
+    pub outside_view_penalty: f32,
+    /// Voxels added to the distance per lod level, so finer chunks go first at the same distance.
+    pub lod_penalty: f32,
+    /// A running task is cancelled and queued again once a pending chunk has a priority this many
+    /// times better, which happens when the loaders move away from it. Priorities below the size
+    /// of a chunk count as a chunk, so chunks right next to a loader don't cancel each other.
+    pub cancel_ratio: f32,
+}
+
+impl Default for ChunkScheduler {
+    fn default() -> Self {
+        Self {
+            max_tasks: 5,
+            outside_view_penalty: 4.0,
+            lod_penalty: chunk_size() as f32 * VOXEL_SIZE,
+            cancel_ratio: 8.0,
+        }
+    }
+}
+
+impl ChunkScheduler {
+    /// Whether a running task with `running_priority` should make room for a pending chunk with
+    /// `pending_priority`.
+    pub fn should_cancel(&self, running_priority: f32, pending_priority: f32) -> bool {
+        running_priority
+            > pending_priority.max(chunk_size() as f32 * VOXEL_SIZE) * self.cancel_ratio
+    }
+}
+
+/// Positions and priorities of the loaders and frustums of the cameras of the current frame, to
+/// rank chunks by.
+pub struct ChunkPriorities<'a> {
+    scheduler: &'a ChunkScheduler,
+    loaders: Vec<(Vec2, f32)>,
+    frustums: Vec<&'a Frustum>,
+}
+
+impl<'a> ChunkPriorities<'a> {
+    pub fn new(
+        scheduler: &'a ChunkScheduler,
+        loaders: impl IntoIterator<Item = (&'a ChunkLoader, &'a Transform)>,
+        frustums: impl IntoIterator<Item = &'a Frustum>,
+    ) -> Self {
+        Self {
+            scheduler,
+            loaders: loaders
+                .into_iter()
+                .map(|(chunk_loader, transform)| {
+                    (transform.translation.xz(), chunk_loader.priority)
+                })
+                .collect(),
+            frustums: frustums.into_iter().collect(),
+        }
+    }
+
+    /// Priority of a chunk, lower values are generated first. Made of the distance from the
+    /// closest loader to the chunk divided by the priority of the loader, penalized when no camera
+    /// sees the chunk, and its lod.
+    pub fn get(&self, chunk_task_generator: &ChunkTaskGenerator) -> f32 {
+        let chunk_lod = chunk_task_generator.1;
+        let chunk_size = chunk_size() as f32 * VOXEL_SIZE * chunk_lod.multiplier_f32();
+        let chunk_min = (chunk_task_generator.0 * chunk_lod.inverse_multiplier_i32()
+            + chunk_task_generator.2)
+            .as_vec2()
+            * chunk_size;
+        let chunk_max = chunk_min + chunk_size;
+
+        let distance = self
+            .loaders
+            .iter()
+            .map(|(loader, priority)| {
+                loader.clamp(chunk_min, chunk_max).distance(*loader) / priority.max(f32::EPSILON)
+            })
+            .fold(f32::INFINITY, f32::min);
+
+        let visible = self.frustums.is_empty()
+            || self.frustums.iter().any(|frustum| {
+                frustum.intersects_obb(
+                    &Aabb::from_min_max(
+                        Vec3::new(chunk_min.x, -CHUNK_COLUMN_HALF_HEIGHT, chunk_min.y),
+                        Vec3::new(chunk_max.x, CHUNK_COLUMN_HALF_HEIGHT, chunk_max.y),
+                    ),
+                    &Affine3A::IDENTITY,
+                    false,
+                    false,
+                )
+            });
+
+        let view_factor = if visible {
+            1.0
+        } else {
+            self.scheduler.outside_view_penalty
+        };
+
+        distance * view_factor + (chunk_lod.usize() - 1) as f32 * self.scheduler.lod_penalty
+    }
+}
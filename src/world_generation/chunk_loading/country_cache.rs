@@ -1,8 +1,9 @@
+use crate::world_generation::chunk_generation::chunk_size;
 use crate::world_generation::chunk_generation::noise::full_cache::FullCache;
 use crate::world_generation::chunk_generation::noise::lod_height_adjuster::LodHeightAdjuster;
 use crate::world_generation::chunk_generation::voxel_generation::get_terrain_noise;
 use crate::world_generation::generation_options::{GenerationCacheItem, GenerationOptions};
-use crate::world_generation::voxel_world::ChunkLod;
+use crate::world_generation::voxel_world::{max_lod, ChunkLod};
 use bevy::log::info;
 use bevy::math::{IVec2, Vec2};
 use noise::NoiseFn;
@@ -217,10 +218,31 @@ impl GenerationCacheItem<IVec2> for CountryCache {
                 .get_cache_entry(key + IVec2::NEG_Y, generation_options),
         }
     }
+
+    /// Counts the path and structure caches as well, as they stay alive as long as the country
+    /// cache does, even once the path and structure caches evicted them.
+    fn memory_size(&self) -> usize {
+        size_of::<Self>()
+            + self.structure_cache.memory_size()
+            + self.this_path_cache.memory_size()
+            + self.bottom_path_cache.memory_size()
+            + self.left_path_cache.memory_size()
+    }
 }
 
 impl GenerationCacheItem<IVec2> for StructureCache {
     fn generate(key: IVec2, generation_options: &GenerationOptions) -> Self {
+        match generation_options.disk_cache() {
+            Some(disk_cache) => {
+                disk_cache.read_or_generate(key, || Self::generate_city(key, generation_options))
+            }
+            None => Self::generate_city(key, generation_options),
+        }
+    }
+}
+
+impl StructureCache {
+    fn generate_city(key: IVec2, generation_options: &GenerationOptions) -> Self {
         let mut rng = StdRng::seed_from_u64(if key.x < 0 {
             generation_options.seed.wrapping_sub(key.x.abs() as u64)
         } else {
@@ -249,6 +271,36 @@ impl GenerationCacheItem<IVec2> for PathCache {
             return Self { paths: vec![] };
         }
 
+        match generation_options.disk_cache() {
+            Some(disk_cache) => {
+                disk_cache.read_or_generate(key, || Self::generate_paths(key, generation_options))
+            }
+            None => Self::generate_paths(key, generation_options),
+        }
+    }
+
+    fn memory_size(&self) -> usize {
+        size_of::<Self>()
+            + self
+                .paths
+                .iter()
+                .map(|path| {
+                    size_of::<Path>()
+                        + path
+                            .lines
+                            .iter()
+                            .map(|line| {
+                                size_of::<PathLine>()
+                                    + line.sample_points.capacity() * size_of::<IVec2>()
+                            })
+                            .sum::<usize>()
+                })
+                .sum::<usize>()
+    }
+}
+
+impl PathCache {
+    fn generate_paths(key: IVec2, generation_options: &GenerationOptions) -> Self {
         let top_country_pos = key + IVec2::X;
         let right_country_pos = key + IVec2::Y;
 
@@ -283,9 +335,7 @@ impl GenerationCacheItem<IVec2> for PathCache {
             ],
         }
     }
-}
 
-impl PathCache {
     fn generate_path(
         mut start_pos: IVec2,
         mut end_pos: IVec2,
@@ -514,3 +564,10 @@ impl PathCache {
 }
 
 pub const COUNTRY_SIZE: usize = 2usize.pow(15);
+
+pub fn get_country_position(parent_pos: IVec2) -> IVec2 {
+    (parent_pos.as_vec2()
+        / (COUNTRY_SIZE as f32 / (max_lod().multiplier_f32() * chunk_size() as f32)))
+        .floor()
+        .as_ivec2()
+}
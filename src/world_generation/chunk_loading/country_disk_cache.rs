@@ -0,0 +1,289 @@
+use crate::world_generation::chunk_loading::country_cache::{
+    Path, PathCache, PathLine, StructureCache, COUNTRY_SIZE,
+};
+use crate::world_generation::generation_options::GenerationOptions;
+use crate::world_generation::world_save::{invalid_data, read_f32, read_i32, read_u32, read_u64};
+use bevy::log::{error, warn};
+use bevy::math::{IVec2, Vec2};
+use flate2::read::ZlibDecoder;
+use flate2::write::ZlibEncoder;
+use flate2::Compression;
+use std::fs;
+use std::io::{self, Read, Write};
+use std::path::PathBuf;
+
+pub const GENERATION_CACHE_DIRECTORY: &str = "cache/generation";
+/// Bumped whenever the path or structure generation changes, so caches written by older versions
+/// are generated again.
+pub const GENERATION_CACHE_VERSION: u32 = 1;
+
+const PATH_CACHE_MAGIC: &[u8; 4] = b"OTPC";
+const STRUCTURE_CACHE_MAGIC: &[u8; 4] = b"OTSC";
+
+/// Country caches that are stored on disk, as paths take long to generate.
+pub trait DiskCacheItem: Sized {
+    const NAME: &'static str;
+    const MAGIC: &'static [u8; 4];
+
+    fn write_to(&self, data: &mut Vec<u8>);
+
+    fn read_from(reader: &mut impl Read) -> io::Result<Self>;
+}
+
+/// Generated country caches of one seed on disk. Every file holds the fingerprint of the
+/// generation parameters it was generated with, files of other parameters are ignored and
+/// overwritten.
+pub struct CountryDiskCache {
+    directory: PathBuf,
+    seed: u64,
+    fingerprint: u64,
+}
+
+impl CountryDiskCache {
+    pub fn new(directory: impl Into<PathBuf>, generation_options: &GenerationOptions) -> Self {
+        let seed = generation_options.seed;
+
+        Self {
+            directory: directory.into().join(format!("{seed:016x}")),
+            seed,
+            fingerprint: get_fingerprint(generation_options),
+        }
+    }
+
+    fn file<T: DiskCacheItem>(&self, key: IVec2) -> PathBuf {
+        self.directory
+            .join(format!("{}.{}.{}.dat", T::NAME, key.x, key.y))
+    }
+
+    /// Reads the item if it was generated with the same parameters. Broken files are treated
+    /// like missing ones, so they are generated and written again.
+    pub fn read<T: DiskCacheItem>(&self, key: IVec2) -> Option<T> {
+        let path = self.file::<T>(key);
+        let mut file = match fs::File::open(&path) {
+            Ok(file) => file,
+            Err(error) if error.kind() == io::ErrorKind::NotFound => return None,
+            Err(error) => {
+                warn!("Failed to open generation cache {path:?}: {error}");
+                return None;
+            }
+        };
+
+        let read_header = |file: &mut fs::File| -> io::Result<bool> {
+            let mut magic = [0u8; 4];
+            file.read_exact(&mut magic)?;
+            Ok(&magic == T::MAGIC
+                && read_u32(file)? == GENERATION_CACHE_VERSION
+                && read_u64(file)? == self.seed
+                && read_u64(file)? == self.fingerprint)
+        };
+
+        match read_header(&mut file) {
+            Ok(true) => {}
+            Ok(false) => return None,
+            Err(error) => {
+                warn!("Invalid generation cache {path:?}: {error}");
+                return None;
+            }
+        }
+
+        match T::read_from(&mut ZlibDecoder::new(file)) {
+            Ok(item) => Some(item),
+            Err(error) => {
+                warn!("Invalid generation cache {path:?}: {error}");
+                None
+            }
+        }
+    }
+
+    pub fn write<T: DiskCacheItem>(&self, key: IVec2, item: &T) -> io::Result<()> {
+        fs::create_dir_all(&self.directory)?;
+
+        let mut data = Vec::new();
+        item.write_to(&mut data);
+
+        // Written next to the file first, so readers never see half a file and options with
+        // other parameters can write the same item at the same time.
+        let path = self.file::<T>(key);
+        let temporary_path = path.with_extension(format!("{:016x}.tmp", self.fingerprint));
+
+        let mut file = fs::File::create(&temporary_path)?;
+        file.write_all(T::MAGIC)?;
+        file.write_all(&GENERATION_CACHE_VERSION.to_le_bytes())?;
+        file.write_all(&self.seed.to_le_bytes())?;
+        file.write_all(&self.fingerprint.to_le_bytes())?;
+
+        let mut encoder = ZlibEncoder::new(file, Compression::default());
+        encoder.write_all(&data)?;
+        encoder.finish()?;
+
+        fs::rename(temporary_path, path)
+    }
+
+    /// Reads the item from disk or generates and stores it.
+    pub fn read_or_generate<T: DiskCacheItem>(
+        &self,
+        key: IVec2,
+        generate: impl FnOnce() -> T,
+    ) -> T {
+        if let Some(item) = self.read(key) {
+            return item;
+        }
+
+        let item = generate();
+        if let Err(error) = self.write(key, &item) {
+            error!(
+                "Failed to write generation cache {:?}: {error}",
+                self.file::<T>(key)
+            );
+        }
+        item
+    }
+}
+
+/// Hash of everything the terrain used for the paths depends on besides the seed. Uses FNV-1a, as
+/// the hashers of the standard library may change between builds.
+fn get_fingerprint(generation_options: &GenerationOptions) -> u64 {
+    let mut data = Vec::new();
+    data.extend_from_slice(&(COUNTRY_SIZE as u64).to_le_bytes());
+    data.extend_from_slice(&generation_options.sea_level.to_le_bytes());
+    data.extend_from_slice(
+        &generation_options
+            .terrain_settings
+            .snow_height
+            .to_le_bytes(),
+    );
+
+    for biome in generation_options.biome_registry.iter() {
+        for value in [
+            biome.temperature,
+            biome.moisture,
+            biome.height_multiplier,
+            biome.height_offset,
+        ] {
+            data.extend_from_slice(&value.to_le_bytes());
+        }
+    }
+
+    match generation_options.noise_graphs.to_ron() {
+        Ok(noise_graphs) => data.extend_from_slice(noise_graphs.as_bytes()),
+        Err(error) => error!("Failed to write noise graphs for the generation cache: {error}"),
+    }
+
+    data.iter().fold(0xcbf29ce484222325, |hash, byte| {
+        (hash ^ *byte as u64).wrapping_mul(0x100000001b3)
+    })
+}
+
+fn write_ivec2(data: &mut Vec<u8>, value: IVec2) {
+    data.extend_from_slice(&value.x.to_le_bytes());
+    data.extend_from_slice(&value.y.to_le_bytes());
+}
+
+fn write_vec2(data: &mut Vec<u8>, value: Vec2) {
+    data.extend_from_slice(&value.x.to_le_bytes());
+    data.extend_from_slice(&value.y.to_le_bytes());
+}
+
+fn read_ivec2(reader: &mut impl Read) -> io::Result<IVec2> {
+    Ok(IVec2::new(read_i32(reader)?, read_i32(reader)?))
+}
+
+fn read_vec2(reader: &mut impl Read) -> io::Result<Vec2> {
+    Ok(Vec2::new(read_f32(reader)?, read_f32(reader)?))
+}
+
+fn read_count(reader: &mut impl Read) -> io::Result<usize> {
+    let count = read_u32(reader)? as usize;
+    // Counts come from the file, so a broken one must not allocate all memory.
+    if count > 1 << 20 {
+        return Err(invalid_data("Count too large"));
+    }
+    Ok(count)
+}
+
+impl DiskCacheItem for StructureCache {
+    const NAME: &'static str = "structures";
+    const MAGIC: &'static [u8; 4] = STRUCTURE_CACHE_MAGIC;
+
+    fn write_to(&self, data: &mut Vec<u8>) {
+        write_ivec2(data, self.city_location);
+    }
+
+    fn read_from(reader: &mut impl Read) -> io::Result<Self> {
+        Ok(Self {
+            city_location: read_ivec2(reader)?,
+        })
+    }
+}
+
+impl DiskCacheItem for PathCache {
+    const NAME: &'static str = "paths";
+    const MAGIC: &'static [u8; 4] = PATH_CACHE_MAGIC;
+
+    fn write_to(&self, data: &mut Vec<u8>) {
+        data.extend_from_slice(&(self.paths.len() as u32).to_le_bytes());
+        for path in &self.paths {
+            write_ivec2(data, path.box_pos_start);
+            write_ivec2(data, path.box_pos_end);
+            data.extend_from_slice(&(path.lines.len() as u32).to_le_bytes());
+
+            for line in &path.lines {
+                write_ivec2(data, line.start);
+                write_ivec2(data, line.end);
+                write_vec2(data, line.spline_one);
+                write_vec2(data, line.spline_two);
+                write_ivec2(data, line.box_pos_start);
+                write_ivec2(data, line.box_pos_end);
+                data.extend_from_slice(&line.estimated_length.to_le_bytes());
+                data.extend_from_slice(&(line.sample_points.len() as u32).to_le_bytes());
+                for sample_point in &line.sample_points {
+                    write_ivec2(data, *sample_point);
+                }
+            }
+        }
+    }
+
+    fn read_from(reader: &mut impl Read) -> io::Result<Self> {
+        let mut paths = Vec::new();
+        for _ in 0..read_count(reader)? {
+            let box_pos_start = read_ivec2(reader)?;
+            let box_pos_end = read_ivec2(reader)?;
+
+            let mut lines = Vec::new();
+            for _ in 0..read_count(reader)? {
+                let start = read_ivec2(reader)?;
+                let end = read_ivec2(reader)?;
+                let spline_one = read_vec2(reader)?;
+                let spline_two = read_vec2(reader)?;
+                let box_pos_start = read_ivec2(reader)?;
+                let box_pos_end = read_ivec2(reader)?;
+                let estimated_length = read_f32(reader)?;
+
+                let sample_count = read_count(reader)?;
+                let mut sample_points = Vec::with_capacity(sample_count);
+                for _ in 0..sample_count {
+                    sample_points.push(read_ivec2(reader)?);
+                }
+
+                lines.push(PathLine {
+                    start,
+                    end,
+                    spline_one,
+                    spline_two,
+                    box_pos_start,
+                    box_pos_end,
+                    estimated_length,
+                    sample_points,
+                });
+            }
+
+            paths.push(Path {
+                lines,
+                box_pos_start,
+                box_pos_end,
+            });
+        }
+
+        Ok(Self { paths })
+    }
+}
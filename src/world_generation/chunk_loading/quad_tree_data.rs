@@ -1,5 +1,6 @@
 use crate::world_generation::chunk_loading::quad_tree_data::QuadTreeNode::Node;
-use bevy::prelude::{Commands, Entity};
+use crate::world_generation::voxel_world::ChunkLod;
+use bevy::prelude::{Commands, Entity, IVec2};
 use std::sync::{Arc, Mutex};
 
 #[derive(Debug)]
@@ -128,4 +129,104 @@ impl<T> QuadTreeNode<T> {
             }
         };
     }
+
+    /// Returns the lod and the data of the data node containing the full lod chunk at `position`,
+    /// relative to the corner of this node.
+    pub fn get_data_at_mut(
+        &mut self,
+        lod: ChunkLod,
+        position: IVec2,
+    ) -> Option<(ChunkLod, &mut T)> {
+        let size = lod.multiplier_i32();
+
+        if position.cmplt(IVec2::ZERO).any() || position.cmpge(IVec2::splat(size)).any() {
+            return None;
+        }
+
+        match self {
+            QuadTreeNode::Data(data, _) => Some((lod, data)),
+            QuadTreeNode::Node(a, b, c, d, _, _) => {
+                let half_size = size / 2;
+
+                let (child, offset) = match (position.x >= half_size, position.y >= half_size) {
+                    (false, false) => (a, IVec2::ZERO),
+                    (true, false) => (b, IVec2::new(half_size, 0)),
+                    (false, true) => (c, IVec2::new(0, half_size)),
+                    (true, true) => (d, IVec2::splat(half_size)),
+                };
+
+                child.get_data_at_mut(lod.previous(), position - offset)
+            }
+        }
+    }
+
+    /// Returns the finest lod of the data nodes overlapping the area between `min` and `max`
+    /// (exclusive), with `origin` being the corner of this node. Everything is in full lod chunks.
+    pub fn get_finest_lod(
+        &self,
+        lod: ChunkLod,
+        origin: IVec2,
+        min: IVec2,
+        max: IVec2,
+    ) -> Option<ChunkLod> {
+        let size = lod.multiplier_i32();
+
+        if (origin + size).cmple(min).any() || origin.cmpge(max).any() {
+            return None;
+        }
+
+        match self {
+            QuadTreeNode::Data(_, _) => Some(lod),
+            QuadTreeNode::Node(a, b, c, d, _, _) => {
+                let half_size = size / 2;
+
+                [
+                    (a, IVec2::ZERO),
+                    (b, IVec2::new(half_size, 0)),
+                    (c, IVec2::new(0, half_size)),
+                    (d, IVec2::splat(half_size)),
+                ]
+                .into_iter()
+                .filter_map(|(child, offset)| {
+                    child.get_finest_lod(lod.previous(), origin + offset, min, max)
+                })
+                .min_by_key(|lod| lod.usize())
+            }
+        }
+    }
+
+    /// Runs the closure on the data nodes overlapping the area between `min` and `max`
+    /// (exclusive), with `origin` being the corner of this node. Everything is in full lod chunks.
+    pub fn run_on_data_in_area<F>(
+        &self,
+        lod: ChunkLod,
+        origin: IVec2,
+        min: IVec2,
+        max: IVec2,
+        closure: &mut F,
+    ) where
+        F: FnMut(&T),
+    {
+        let size = lod.multiplier_i32();
+
+        if (origin + size).cmple(min).any() || origin.cmpge(max).any() {
+            return;
+        }
+
+        match self {
+            QuadTreeNode::Data(data, _) => closure(data),
+            QuadTreeNode::Node(a, b, c, d, _, _) => {
+                let half_size = size / 2;
+
+                for (child, offset) in [
+                    (a, IVec2::ZERO),
+                    (b, IVec2::new(half_size, 0)),
+                    (c, IVec2::new(0, half_size)),
+                    (d, IVec2::splat(half_size)),
+                ] {
+                    child.run_on_data_in_area(lod.previous(), origin + offset, min, max, closure);
+                }
+            }
+        }
+    }
 }
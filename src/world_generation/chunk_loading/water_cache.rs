@@ -0,0 +1,430 @@
+use crate::world_generation::chunk_generation::noise::lod_height_adjuster::to_lod_height;
+use crate::world_generation::chunk_generation::voxel_generation::get_terrain_noise;
+use crate::world_generation::generation_options::{GenerationCacheItem, GenerationOptions};
+use crate::world_generation::voxel_world::ChunkLod;
+use bevy::math::{IVec2, Vec2};
+use noise::NoiseFn;
+use rand::prelude::StdRng;
+use rand::{Rng, SeedableRng};
+use std::cmp::Ordering;
+use std::collections::BinaryHeap;
+use std::sync::Arc;
+
+/// Water is planned per region, which is the same for every lod and chunk setting, so they all
+/// sample the same lakes and rivers.
+pub const WATER_REGION_SIZE: i32 = 8192;
+const WATER_CELL_SIZE: i32 = 64;
+/// Extra cells around a region, so lakes crossing the border and rivers leaving the region are
+/// planned the same way by both neighbours.
+const WATER_REGION_PADDING: i32 = 32;
+const WATER_GRID_SIZE: i32 = WATER_REGION_SIZE / WATER_CELL_SIZE + 2 * WATER_REGION_PADDING + 1;
+
+const RIVER_SOURCE_HEIGHT: f32 = 2200.;
+const RIVER_SOURCE_ATTEMPTS: usize = 24;
+const RIVER_MIN_WIDTH: f32 = 3.;
+const RIVER_MAX_WIDTH: f32 = 14.;
+const RIVER_DEPTH_RATIO: f32 = 0.4;
+pub const MAX_RIVER_DEPTH: f32 = RIVER_MAX_WIDTH * RIVER_DEPTH_RATIO;
+
+pub struct WaterCache {
+    pub region_pos: IVec2,
+    lake_levels: Vec<Option<f32>>,
+    pub rivers: Vec<River>,
+}
+
+/// A river flowing from `points[0]` downhill. Levels, widths and positions are in full lod voxels.
+pub struct River {
+    pub points: Vec<Vec2>,
+    pub levels: Vec<f32>,
+    pub widths: Vec<f32>,
+    pub box_pos_start: Vec2,
+    pub box_pos_end: Vec2,
+}
+
+pub struct RiverSample {
+    pub distance: f32,
+    pub level: f32,
+    pub width: f32,
+}
+
+impl River {
+    fn new(points: Vec<Vec2>, levels: Vec<f32>, widths: Vec<f32>) -> Self {
+        let (box_pos_start, box_pos_end) = points.iter().fold(
+            (Vec2::splat(f32::MAX), Vec2::splat(f32::MIN)),
+            |(min, max), point| (min.min(*point), max.max(*point)),
+        );
+
+        Self {
+            points,
+            levels,
+            widths,
+            box_pos_start,
+            box_pos_end,
+        }
+    }
+
+    pub fn is_in_box(&self, point: Vec2, margin: f32) -> bool {
+        point.cmpge(self.box_pos_start - margin).all()
+            && point.cmple(self.box_pos_end + margin).all()
+    }
+
+    pub fn get_closest_sample(&self, point: Vec2) -> Option<RiverSample> {
+        let mut closest: Option<RiverSample> = None;
+
+        for i in 1..self.points.len() {
+            let start = self.points[i - 1];
+            let end = self.points[i];
+            let line = end - start;
+
+            let t = ((point - start).dot(line) / line.length_squared()).clamp(0., 1.);
+            let distance = point.distance(start + line * t);
+
+            if closest
+                .as_ref()
+                .is_none_or(|closest| distance < closest.distance)
+            {
+                closest = Some(RiverSample {
+                    distance,
+                    level: lerp(self.levels[i - 1], self.levels[i], t),
+                    width: lerp(self.widths[i - 1], self.widths[i], t),
+                });
+            }
+        }
+
+        closest
+    }
+
+    /// Cuts the corners of the grid aligned path, keeping the levels monotonic.
+    fn smooth(self) -> Self {
+        fn chaikin<T: Copy>(values: &[T], lerp: impl Fn(T, T, f32) -> T) -> Vec<T> {
+            let mut result = vec![values[0]];
+            for pair in values.windows(2) {
+                result.push(lerp(pair[0], pair[1], 0.25));
+                result.push(lerp(pair[0], pair[1], 0.75));
+            }
+            result.push(values[values.len() - 1]);
+            result
+        }
+
+        Self::new(
+            chaikin(&self.points, Vec2::lerp),
+            chaikin(&self.levels, lerp),
+            chaikin(&self.widths, lerp),
+        )
+    }
+}
+
+#[derive(PartialEq)]
+struct FloodCandidate {
+    level: f32,
+    index: usize,
+}
+
+impl Eq for FloodCandidate {}
+
+impl PartialOrd for FloodCandidate {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl Ord for FloodCandidate {
+    fn cmp(&self, other: &Self) -> Ordering {
+        other.level.total_cmp(&self.level)
+    }
+}
+
+impl GenerationCacheItem<IVec2> for WaterCache {
+    fn generate(key: IVec2, generation_options: &GenerationOptions) -> Self {
+        let terrain_noise = get_terrain_noise(generation_options);
+        let grid_origin =
+            key * WATER_REGION_SIZE - IVec2::splat(WATER_REGION_PADDING * WATER_CELL_SIZE);
+
+        let cell_count = (WATER_GRID_SIZE * WATER_GRID_SIZE) as usize;
+        let get_cell = |index: usize| {
+            IVec2::new(
+                index as i32 / WATER_GRID_SIZE,
+                index as i32 % WATER_GRID_SIZE,
+            )
+        };
+        let get_index = |cell: IVec2| (cell.x * WATER_GRID_SIZE + cell.y) as usize;
+        let get_position = |cell: IVec2| grid_origin + cell * WATER_CELL_SIZE;
+        let is_border = |cell: IVec2| {
+            cell.x == 0
+                || cell.y == 0
+                || cell.x == WATER_GRID_SIZE - 1
+                || cell.y == WATER_GRID_SIZE - 1
+        };
+        let neighbours = |cell: IVec2| {
+            [
+                IVec2::new(1, 0),
+                IVec2::new(0, 1),
+                IVec2::new(-1, 0),
+                IVec2::new(0, -1),
+                IVec2::new(1, 1),
+                IVec2::new(-1, 1),
+                IVec2::new(-1, -1),
+                IVec2::new(1, -1),
+            ]
+            .map(|direction| cell + direction)
+            .into_iter()
+            .filter(|cell| {
+                cell.cmpge(IVec2::ZERO).all() && cell.cmplt(IVec2::splat(WATER_GRID_SIZE)).all()
+            })
+        };
+
+        let heights: Vec<f32> = (0..cell_count)
+            .map(|index| {
+                terrain_noise.get(get_position(get_cell(index)).as_dvec2().to_array()) as f32
+            })
+            .collect();
+
+        // Priority flood: every depression is filled up to the height where it spills over.
+        let mut levels = heights.clone();
+        let mut visited = vec![false; cell_count];
+        let mut queue = BinaryHeap::new();
+
+        for index in 0..cell_count {
+            if is_border(get_cell(index)) {
+                visited[index] = true;
+                queue.push(FloodCandidate {
+                    level: heights[index],
+                    index,
+                });
+            }
+        }
+
+        while let Some(FloodCandidate { level, index }) = queue.pop() {
+            for neighbour in neighbours(get_cell(index)) {
+                let neighbour = get_index(neighbour);
+                if visited[neighbour] {
+                    continue;
+                }
+                visited[neighbour] = true;
+                levels[neighbour] = heights[neighbour].max(level);
+                queue.push(FloodCandidate {
+                    level: levels[neighbour],
+                    index: neighbour,
+                });
+            }
+        }
+
+        let lake_levels: Vec<Option<f32>> = (0..cell_count)
+            .map(|index| {
+                (levels[index] > heights[index] && levels[index] > generation_options.sea_level)
+                    .then_some(levels[index])
+            })
+            .collect();
+
+        let mut rng = StdRng::seed_from_u64(
+            generation_options
+                .seed
+                .wrapping_add((key.x as u64).wrapping_mul(73856093))
+                .wrapping_add((key.y as u64).wrapping_mul(19349663)),
+        );
+
+        let mut rivers = vec![];
+        let mut river_cells = vec![false; cell_count];
+        let inner_cells = WATER_REGION_SIZE / WATER_CELL_SIZE;
+
+        for _ in 0..RIVER_SOURCE_ATTEMPTS {
+            let source = IVec2::new(
+                rng.random_range(0..inner_cells),
+                rng.random_range(0..inner_cells),
+            ) + IVec2::splat(WATER_REGION_PADDING);
+            let source_index = get_index(source);
+
+            if heights[source_index] < RIVER_SOURCE_HEIGHT
+                || lake_levels[source_index].is_some()
+                || river_cells[source_index]
+            {
+                continue;
+            }
+
+            // Follows the steepest descent until the river reaches the sea, a lake, another river
+            // or leaves the padded region.
+            let mut cells = vec![source];
+            let mut current = source;
+            loop {
+                let current_index = get_index(current);
+                if river_cells[current_index] && current != source {
+                    break;
+                }
+                river_cells[current_index] = true;
+
+                if is_border(current)
+                    || levels[current_index] <= generation_options.sea_level
+                    || lake_levels[current_index].is_some()
+                {
+                    break;
+                }
+
+                let Some(next) = neighbours(current)
+                    .min_by(|a, b| levels[get_index(*a)].total_cmp(&levels[get_index(*b)]))
+                    .filter(|next| levels[get_index(*next)] < levels[current_index])
+                else {
+                    break;
+                };
+
+                cells.push(next);
+                current = next;
+            }
+
+            if cells.len() < 3 {
+                continue;
+            }
+
+            let river = River::new(
+                cells
+                    .iter()
+                    .map(|cell| get_position(*cell).as_vec2())
+                    .collect(),
+                cells.iter().map(|cell| levels[get_index(*cell)]).collect(),
+                (0..cells.len())
+                    .map(|i| (RIVER_MIN_WIDTH + i as f32 * 0.4).min(RIVER_MAX_WIDTH))
+                    .collect(),
+            );
+
+            rivers.push(river.smooth().smooth());
+        }
+
+        Self {
+            region_pos: key,
+            lake_levels,
+            rivers,
+        }
+    }
+
+    fn memory_size(&self) -> usize {
+        size_of::<Self>()
+            + self.lake_levels.capacity() * size_of::<Option<f32>>()
+            + self
+                .rivers
+                .iter()
+                .map(|river| {
+                    size_of::<River>()
+                        + river.points.capacity() * size_of::<Vec2>()
+                        + (river.levels.capacity() + river.widths.capacity()) * size_of::<f32>()
+                })
+                .sum::<usize>()
+    }
+}
+
+impl WaterCache {
+    /// The highest lake level of the four grid cells around the position.
+    pub fn get_lake_level(&self, pos: IVec2) -> Option<f32> {
+        let grid_origin = self.region_pos * WATER_REGION_SIZE
+            - IVec2::splat(WATER_REGION_PADDING * WATER_CELL_SIZE);
+        let cell = (pos - grid_origin).div_euclid(IVec2::splat(WATER_CELL_SIZE));
+
+        [IVec2::ZERO, IVec2::X, IVec2::Y, IVec2::ONE]
+            .into_iter()
+            .map(|offset| cell + offset)
+            .filter(|cell| {
+                cell.cmpge(IVec2::ZERO).all() && cell.cmplt(IVec2::splat(WATER_GRID_SIZE)).all()
+            })
+            .filter_map(|cell| self.lake_levels[(cell.x * WATER_GRID_SIZE + cell.y) as usize])
+            .max_by(f32::total_cmp)
+    }
+}
+
+pub fn get_water_region_position(pos: IVec2) -> IVec2 {
+    pos.div_euclid(IVec2::splat(WATER_REGION_SIZE))
+}
+
+/// The water caches around a chunk, used to fill the sea, lakes and rivers into its columns.
+pub struct WaterMap {
+    sea_level: f32,
+    region_pos: IVec2,
+    water_caches: Vec<Arc<WaterCache>>,
+}
+
+impl WaterMap {
+    pub fn new(chunk_pos: IVec2, generation_options: &GenerationOptions) -> Self {
+        let region_pos = get_water_region_position(chunk_pos);
+
+        let mut water_caches = Vec::with_capacity(9);
+        for x in -1..=1 {
+            for z in -1..=1 {
+                water_caches.push(
+                    generation_options
+                        .water_cache
+                        .get_cache_entry(region_pos + IVec2::new(x, z), generation_options),
+                );
+            }
+        }
+
+        Self {
+            sea_level: generation_options.sea_level,
+            region_pos,
+            water_caches,
+        }
+    }
+
+    fn get_region(&self, pos: IVec2) -> Option<&WaterCache> {
+        let offset = get_water_region_position(pos) - self.region_pos + IVec2::ONE;
+        if offset.cmplt(IVec2::ZERO).any() || offset.cmpgt(IVec2::splat(2)).any() {
+            return None;
+        }
+        Some(&self.water_caches[(offset.x * 3 + offset.y) as usize])
+    }
+
+    pub fn get_closest_river(&self, pos: IVec2) -> Option<RiverSample> {
+        let point = pos.as_vec2();
+
+        self.water_caches
+            .iter()
+            .flat_map(|water_cache| &water_cache.rivers)
+            .filter(|river| river.is_in_box(point, RIVER_MAX_WIDTH * 2.))
+            .filter_map(|river| river.get_closest_sample(point))
+            .filter(|sample| sample.distance < sample.width * 2.)
+            .min_by(|a, b| a.distance.total_cmp(&b.distance))
+    }
+
+    /// Carves rivers into the column and returns the water surface above it, both in lod heights.
+    pub fn carve_column(
+        &self,
+        pos: IVec2,
+        noise_height: &mut f32,
+        chunk_lod: ChunkLod,
+    ) -> Option<f32> {
+        let mut level = self.sea_level;
+
+        if let Some(river) = self.get_closest_river(pos) {
+            let depth = river.width
+                * RIVER_DEPTH_RATIO
+                * (1. - (river.distance / river.width).min(1.).powi(2));
+            let bed = to_lod_height((river.level - depth) as f64, chunk_lod) as f32;
+            let blend = ((river.distance - river.width) / river.width).clamp(0., 1.);
+
+            *noise_height = noise_height.min(lerp(bed, *noise_height, blend));
+
+            if river.distance < river.width {
+                level = level.max(river.level);
+            }
+        }
+
+        if let Some(lake_level) = self
+            .get_region(pos)
+            .and_then(|region| region.get_lake_level(pos))
+        {
+            level = level.max(lake_level);
+        }
+
+        let level = to_lod_height(level as f64, chunk_lod) as f32;
+        (level > *noise_height).then_some(level)
+    }
+
+    /// Whether the position lies outside of any water body and river bank.
+    pub fn is_dry(&self, pos: IVec2, noise_height: f32, chunk_lod: ChunkLod) -> bool {
+        let mut noise_height = noise_height;
+        self.get_closest_river(pos).is_none()
+            && self
+                .carve_column(pos, &mut noise_height, chunk_lod)
+                .is_none()
+    }
+}
+
+fn lerp(a: f32, b: f32, f: f32) -> f32 {
+    a + f * (b - a)
+}
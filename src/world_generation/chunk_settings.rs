@@ -0,0 +1,164 @@
+use crate::world_generation::chunk_generation::MAX_CHUNK_SIZE;
+use crate::world_generation::chunk_loading::country_cache::COUNTRY_SIZE;
+use crate::world_generation::voxel_world::ChunkLod;
+use bevy::log::{error, info};
+use serde::Deserialize;
+use std::fs;
+use std::path::Path;
+use std::sync::OnceLock;
+
+pub const CHUNK_SETTINGS_PATH: &str = "assets/chunk_settings.ron";
+
+static CHUNK_SETTINGS: OnceLock<ChunkSettings> = OnceLock::new();
+
+#[derive(Copy, Clone, Debug, Default, PartialEq, Eq, Deserialize)]
+pub enum ChunkPreset {
+    /// Smaller chunks and a shorter view distance for laptops.
+    Low,
+    #[default]
+    Medium,
+    /// Quadtrees up to [`ChunkLod::TwoFiftySix`] and more detail around the loaders.
+    High,
+}
+
+/// Size of the chunks and the quadtrees along with the ranges of the chunk loaders. Every chunk
+/// position depends on them, so they are chosen once at startup and can't change afterwards.
+#[derive(Clone, Debug)]
+pub struct ChunkSettings {
+    /// Voxels along each side of a chunk, a power of two up to [`MAX_CHUNK_SIZE`].
+    pub chunk_size: usize,
+    /// Lod of the quadtree root chunks.
+    pub max_lod: ChunkLod,
+    /// Default load range of the chunk loaders, in root chunks.
+    pub load_range: i32,
+    /// Default unload range of the chunk loaders, in root chunks.
+    pub unload_range: i32,
+    /// Default range of the chunk loaders for every lod.
+    pub lod_range: i32,
+}
+
+#[derive(Deserialize)]
+struct ChunkSettingsFile {
+    #[serde(default)]
+    preset: ChunkPreset,
+    #[serde(default)]
+    chunk_size: Option<usize>,
+    #[serde(default)]
+    max_lod: Option<ChunkLod>,
+    #[serde(default)]
+    load_range: Option<i32>,
+    #[serde(default)]
+    unload_range: Option<i32>,
+    #[serde(default)]
+    lod_range: Option<i32>,
+}
+
+impl ChunkSettings {
+    pub fn from_preset(preset: ChunkPreset) -> Self {
+        match preset {
+            ChunkPreset::Low => Self {
+                chunk_size: 32,
+                max_lod: ChunkLod::OneTwentyEight,
+                load_range: 4,
+                unload_range: 6,
+                lod_range: 2,
+            },
+            ChunkPreset::Medium => Self {
+                chunk_size: 64,
+                max_lod: ChunkLod::OneTwentyEight,
+                load_range: 8,
+                unload_range: 10,
+                lod_range: 2,
+            },
+            ChunkPreset::High => Self {
+                chunk_size: 64,
+                max_lod: ChunkLod::TwoFiftySix,
+                load_range: 8,
+                unload_range: 10,
+                lod_range: 3,
+            },
+        }
+    }
+
+    pub fn load(path: impl AsRef<Path>) -> Result<Self, String> {
+        let path = path.as_ref();
+        let file = fs::read_to_string(path)
+            .map_err(|error| format!("Failed to read chunk settings {path:?}: {error}"))?;
+
+        Self::parse(&file).map_err(|error| format!("Invalid chunk settings {path:?}: {error}"))
+    }
+
+    /// Parses a preset along with the values that override it.
+    pub fn parse(file: &str) -> Result<Self, String> {
+        let file: ChunkSettingsFile = ron::from_str(file).map_err(|error| error.to_string())?;
+        let preset = Self::from_preset(file.preset);
+
+        let settings = Self {
+            chunk_size: file.chunk_size.unwrap_or(preset.chunk_size),
+            max_lod: file.max_lod.unwrap_or(preset.max_lod),
+            load_range: file.load_range.unwrap_or(preset.load_range),
+            unload_range: file.unload_range.unwrap_or(preset.unload_range),
+            lod_range: file.lod_range.unwrap_or(preset.lod_range),
+        };
+
+        settings.validate()?;
+        Ok(settings)
+    }
+
+    fn validate(&self) -> Result<(), String> {
+        if !self.chunk_size.is_power_of_two() || !(8..=MAX_CHUNK_SIZE).contains(&self.chunk_size) {
+            return Err(format!(
+                "Chunk size {} is no power of two between 8 and {MAX_CHUNK_SIZE}",
+                self.chunk_size
+            ));
+        }
+
+        // Root chunks have to fit into a country, as every chunk is generated from one country
+        // cache.
+        let root_chunk_size = self.chunk_size * self.max_lod.multiplier_i32() as usize;
+        if root_chunk_size > COUNTRY_SIZE {
+            return Err(format!(
+                "Root chunks of {root_chunk_size} voxels are larger than a country of \
+                 {COUNTRY_SIZE} voxels"
+            ));
+        }
+
+        if self.unload_range <= self.load_range {
+            return Err(format!(
+                "Unload range {} has to be larger than the load range {}",
+                self.unload_range, self.load_range
+            ));
+        }
+
+        Ok(())
+    }
+
+    /// Makes these the settings of the running game. Fails if the settings were already set or
+    /// used, handing them back.
+    pub fn install(self) -> Result<(), Self> {
+        CHUNK_SETTINGS.set(self)
+    }
+}
+
+/// Settings of the running game, the medium preset unless others were installed before the first
+/// use.
+pub fn chunk_settings() -> &'static ChunkSettings {
+    CHUNK_SETTINGS.get_or_init(|| ChunkSettings::from_preset(ChunkPreset::default()))
+}
+
+/// Installs the settings of the settings file. The medium preset is used if the file is missing
+/// or invalid.
+pub fn load_chunk_settings() {
+    let settings = match ChunkSettings::load(CHUNK_SETTINGS_PATH) {
+        Ok(settings) => settings,
+        Err(error) => {
+            error!("{error}");
+            ChunkSettings::from_preset(ChunkPreset::default())
+        }
+    };
+
+    match settings.install() {
+        Ok(()) => info!("Chunk settings: {:?}", chunk_settings()),
+        Err(_) => info!("Chunk settings were already set: {:?}", chunk_settings()),
+    }
+}
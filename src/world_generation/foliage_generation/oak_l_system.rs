@@ -42,8 +42,8 @@ impl LSystem<OakEntryType> for OakLSystem {
 
     fn get_block_from_entry(entry: &LSystemEntry<OakEntryType>) -> BlockType {
         match entry.entry_type {
-            OakEntryType::Leaf => BlockType::Grass,
-            _ => BlockType::Path,
+            OakEntryType::Leaf => BlockType::LEAVES,
+            _ => BlockType::PATH,
         }
     }
 
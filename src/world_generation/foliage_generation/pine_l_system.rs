@@ -86,8 +86,8 @@ impl LSystem<PineEntryType> for PineLSystem {
 
     fn get_block_from_entry(entry: &LSystemEntry<PineEntryType>) -> BlockType {
         match entry.entry_type {
-            PineEntryType::Needle => BlockType::Grass,
-            _ => BlockType::Path,
+            PineEntryType::Needle => BlockType::LEAVES,
+            _ => BlockType::PATH,
         }
     }
 
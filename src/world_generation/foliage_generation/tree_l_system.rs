@@ -35,7 +35,7 @@ pub trait LSystem<EntryEnum: Clone + Copy> {
             for y in 0..YSIZE {
                 voxel_grid[x].push(vec![]);
                 for _ in 0..XSIZE {
-                    voxel_grid[x][y].push(BlockType::Air);
+                    voxel_grid[x][y].push(BlockType::AIR);
                 }
             }
         }
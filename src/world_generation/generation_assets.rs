@@ -1,50 +1,75 @@
 use bevy::{
-    asset::{AssetServer, Assets, Handle},
+    asset::{AssetServer, Assets, Handle, LoadState, RenderAssetUsages},
     color::Color,
     ecs::{
         resource::Resource,
         system::{Commands, Res, ResMut},
     },
-    image::{Image, ImageAddressMode, ImageLoaderSettings, ImageSampler, ImageSamplerDescriptor},
+    image::{Image, ImageAddressMode, ImageSampler, ImageSamplerDescriptor},
+    log::{error, warn},
     pbr::{ExtendedMaterial, StandardMaterial},
+    render::alpha::AlphaMode,
+    render::render_resource::{Extent3d, TextureDimension, TextureFormat},
     state::state::{NextState, States},
     utils::default,
 };
 
+use image::imageops::FilterType;
+
 use crate::world_generation::array_texture::ArrayTextureMaterial;
+use crate::world_generation::generation_options::GenerationOptionsResource;
 
 #[derive(Resource)]
 pub struct GenerationAssets {
     pub material: Handle<ExtendedMaterial<StandardMaterial, ArrayTextureMaterial>>,
+    pub transparent_material: Handle<ExtendedMaterial<StandardMaterial, ArrayTextureMaterial>>,
     pub texture_handle: Handle<Image>,
+    /// One image per array texture layer, in the order of the block registry.
+    pub layer_handles: Vec<Handle<Image>>,
 }
 
 pub fn load_generation_assets(
     mut commands: Commands,
     asset_server: Res<AssetServer>,
+    images: Res<Assets<Image>>,
+    generation_options: Res<GenerationOptionsResource>,
     mut materials: ResMut<Assets<ExtendedMaterial<StandardMaterial, ArrayTextureMaterial>>>,
     mut generation_asset_state: ResMut<NextState<GenerationAssetState>>,
 ) {
-    let texture_handle = asset_server.load_with_settings("array_texture.png", |s: &mut _| {
-        *s = ImageLoaderSettings {
-            sampler: ImageSampler::Descriptor(ImageSamplerDescriptor {
-                // rewriting mode to repeat image,
-                address_mode_u: ImageAddressMode::Repeat,
-                address_mode_v: ImageAddressMode::Repeat,
-                ..default()
-            }),
-            ..default()
-        }
-    });
+    // The array texture is stacked together once all layers are loaded.
+    let texture_handle = images.reserve_handle();
+
+    let layer_handles = generation_options
+        .0
+        .block_registry
+        .get_texture_paths()
+        .into_iter()
+        .map(|path| asset_server.load(path))
+        .collect();
 
     commands.insert_resource(GenerationAssets {
         material: materials.add(ExtendedMaterial {
-            base: StandardMaterial::from_color(Color::WHITE),
+            base: StandardMaterial {
+                alpha_mode: AlphaMode::Mask(0.5),
+                ..StandardMaterial::from_color(Color::WHITE)
+            },
+            extension: ArrayTextureMaterial {
+                array_texture: texture_handle.clone(),
+            },
+        }),
+        transparent_material: materials.add(ExtendedMaterial {
+            base: StandardMaterial {
+                alpha_mode: AlphaMode::Blend,
+                cull_mode: None,
+                double_sided: true,
+                ..StandardMaterial::from_color(Color::WHITE)
+            },
             extension: ArrayTextureMaterial {
                 array_texture: texture_handle.clone(),
             },
         }),
         texture_handle,
+        layer_handles,
     });
 
     generation_asset_state.set(GenerationAssetState::Loading);
@@ -56,6 +81,8 @@ pub enum GenerationAssetState {
     Unloaded,
     Loading,
     Loaded,
+    /// A block texture couldn't be loaded, so there are no textures to render the chunks with.
+    Failed,
 }
 
 pub fn setup_array_texture(
@@ -64,17 +91,88 @@ pub fn setup_array_texture(
     mut generation_asset_state: ResMut<NextState<GenerationAssetState>>,
     asset_server: Res<AssetServer>,
 ) {
-    if !asset_server
-        .load_state(generation_assets.texture_handle.id())
-        .is_loaded()
-    {
-        return;
+    for handle in &generation_assets.layer_handles {
+        match asset_server.load_state(handle.id()) {
+            LoadState::Loaded => {}
+            LoadState::Failed(error) => {
+                error!("Failed to load block texture: {error}");
+                generation_asset_state.set(GenerationAssetState::Failed);
+                return;
+            }
+            _ => return,
+        }
     }
 
-    let image = images.get_mut(&generation_assets.texture_handle).unwrap();
+    let first_layer = images.get(&generation_assets.layer_handles[0]).unwrap();
+    let size = first_layer.texture_descriptor.size;
+    let format = first_layer.texture_descriptor.format;
 
-    let array_layers = 4;
-    image.reinterpret_stacked_2d_as_array(array_layers);
+    let mut data = Vec::new();
+    for handle in &generation_assets.layer_handles {
+        let image = images.get(handle).unwrap();
+        let path = asset_server.get_path(handle.id()).unwrap();
+        if image.texture_descriptor.size == size && image.texture_descriptor.format == format {
+            data.extend_from_slice(image.data.as_deref().unwrap_or_default());
+            continue;
+        }
+
+        match resize_layer(image, size, format) {
+            Ok(image) => {
+                warn!(
+                    "Block texture {path} was resized to {}x{} to match the first block texture",
+                    size.width, size.height
+                );
+                data.extend_from_slice(image.data.as_deref().unwrap_or_default());
+            }
+            Err(message) => {
+                error!("Block texture {path} can't be used: {message}");
+                generation_asset_state.set(GenerationAssetState::Failed);
+                return;
+            }
+        }
+    }
+
+    let layer_count = generation_assets.layer_handles.len() as u32;
+    let mut array_texture = Image::new(
+        Extent3d {
+            width: size.width,
+            height: size.height * layer_count,
+            depth_or_array_layers: 1,
+        },
+        TextureDimension::D2,
+        data,
+        format,
+        RenderAssetUsages::RENDER_WORLD,
+    );
+    array_texture.sampler = ImageSampler::Descriptor(ImageSamplerDescriptor {
+        // rewriting mode to repeat image,
+        address_mode_u: ImageAddressMode::Repeat,
+        address_mode_v: ImageAddressMode::Repeat,
+        ..default()
+    });
+    array_texture.reinterpret_stacked_2d_as_array(layer_count);
+
+    images.insert(&generation_assets.texture_handle, array_texture);
 
     generation_asset_state.set(GenerationAssetState::Loaded);
 }
+
+/// Converts a block texture to the size and format of the first one, so all of them fit into the
+/// array texture.
+fn resize_layer(image: &Image, size: Extent3d, format: TextureFormat) -> Result<Image, String> {
+    let resized = image
+        .clone()
+        .try_into_dynamic()
+        .map_err(|error| error.to_string())?
+        .resize_exact(size.width, size.height, FilterType::Nearest);
+    let resized = Image::from_dynamic(resized, true, RenderAssetUsages::RENDER_WORLD);
+
+    if resized.texture_descriptor.format != format {
+        return Err(format!(
+            "its format {:?} can't be converted to {format:?}",
+            image.texture_descriptor.format
+        ));
+    }
+
+    Ok(resized)
+}
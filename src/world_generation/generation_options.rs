@@ -1,90 +1,56 @@
+use crate::world_generation::chunk_generation::biome_registry::{
+    BiomeRegistry, BIOME_REGISTRY_FILE,
+};
+use crate::world_generation::chunk_generation::block_registry::{
+    BlockRegistry, BLOCK_REGISTRY_FILE,
+};
+use crate::world_generation::chunk_generation::generation_pass::GenerationPipeline;
+use crate::world_generation::chunk_generation::noise::noise_graph::{
+    NoiseGraphRegistry, NOISE_GRAPH_FILE,
+};
 use crate::world_generation::chunk_generation::oak_structure_generator::OakStructureGenerator;
+use crate::world_generation::chunk_generation::pine_structure_generator::PineStructureGenerator;
 use crate::world_generation::chunk_generation::structure_generator::{
     FixedStructureGenerator, StructureGenerator, VoxelStructureMetadata,
 };
 use crate::world_generation::chunk_generation::tree_structure_generator::TreeStructureGenerator;
 use crate::world_generation::chunk_generation::BlockType;
+use crate::world_generation::chunk_loading::chunk_loader::RegenerateWorld;
 use crate::world_generation::chunk_loading::country_cache::{
     CountryCache, PathCache, StructureCache,
 };
-use bevy::prelude::{IVec2, Resource};
+use crate::world_generation::chunk_loading::country_disk_cache::{
+    CountryDiskCache, GENERATION_CACHE_DIRECTORY,
+};
+use crate::world_generation::chunk_loading::water_cache::WaterCache;
+use bevy::log::{error, info};
+use bevy::prelude::{EventWriter, IVec2, Res, ResMut, Resource, Time, Timer, TimerMode};
 use fastnoise_lite::FastNoiseLite;
 use rand::prelude::StdRng;
 use rand::{Rng, SeedableRng};
 use std::collections::HashMap;
+use std::fs;
 use std::hash::Hash;
 use std::ops::Deref;
-use std::sync::{Arc, RwLock};
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicU64, AtomicUsize, Ordering};
+use std::sync::{Arc, OnceLock, RwLock};
+use std::time::SystemTime;
 use vox_format::{from_file, VoxData};
 
 #[derive(Resource)]
-pub struct GenerationOptionsResource(
-    pub Arc<GenerationOptions>,
-    pub HashMap<IVec2, GenerationState<CountryCache>>,
-);
+pub struct GenerationOptionsResource(pub Arc<GenerationOptions>, pub CountryCaches);
 
 impl GenerationOptionsResource {
     pub fn from_seed(seed: u64) -> Self {
-        let tree_house = vox_data_to_structure_data(&from_file("assets/tree_house.vox").unwrap());
-        let box_structure = vox_data_to_structure_data(&from_file("assets/box.vox").unwrap());
-
-        let mut rng = StdRng::seed_from_u64(seed);
+        Self::new(seed, false)
+    }
 
-        Self {
-            0: Arc::new(GenerationOptions {
-                seed,
-                generate_paths: false,
-                path_cache: GenerationCache::new(),
-                structure_cache: GenerationCache::new(),
-                structure_generators: vec![
-                    Arc::new(Box::new(OakStructureGenerator::new(
-                        VoxelStructureMetadata {
-                            model_size: [27, 27, 27],
-                            generation_size: [64, 64],
-                            grid_offset: [24, 16],
-                            generate_debug_blocks: false,
-                            debug_rgb_multiplier: [1., 1., 1.],
-                            noise: get_seeded_white_noise(rng.random()),
-                        },
-                    ))),
-                    Arc::new(Box::new(OakStructureGenerator::new(
-                        VoxelStructureMetadata {
-                            model_size: [27, 27, 27],
-                            generation_size: [64, 64],
-                            grid_offset: [43, 52],
-                            generate_debug_blocks: false,
-                            debug_rgb_multiplier: [1., 1., 1.],
-                            noise: get_seeded_white_noise(rng.random()),
-                        },
-                    ))),
-                    Arc::new(Box::new(OakStructureGenerator::new(
-                        VoxelStructureMetadata {
-                            model_size: [27, 27, 27],
-                            generation_size: [64, 64],
-                            grid_offset: [10, 4],
-                            generate_debug_blocks: false,
-                            debug_rgb_multiplier: [1., 1., 1.],
-                            noise: get_seeded_white_noise(rng.random()),
-                        },
-                    ))),
-                    Arc::new(Box::new(FixedStructureGenerator {
-                        fixed_structure_model: tree_house.0.clone(),
-                        fixed_structure_metadata: VoxelStructureMetadata {
-                            model_size: tree_house.1,
-                            generation_size: [1000, 1000],
-                            grid_offset: [7, 11],
-                            generate_debug_blocks: false,
-                            debug_rgb_multiplier: [1., 1., 1.],
-                            noise: get_seeded_white_noise(rng.random()),
-                        },
-                    })),
-                ],
-                structure_assets: vec![StructureAsset {
-                    _blocks: (*box_structure.0).clone(),
-                }],
-            }),
-            1: HashMap::new(),
-        }
+    pub fn new(seed: u64, generate_paths: bool) -> Self {
+        Self(
+            Arc::new(GenerationOptions::new(seed, generate_paths)),
+            CountryCaches::new(COUNTRY_CACHE_MEMORY),
+        )
     }
 }
 
@@ -112,21 +78,353 @@ pub struct GenerationOptions {
     pub structure_assets: Vec<StructureAsset>,
     pub path_cache: GenerationCache<IVec2, PathCache>,
     pub structure_cache: GenerationCache<IVec2, StructureCache>,
+    pub water_cache: GenerationCache<IVec2, WaterCache>,
     pub generate_paths: bool,
+    /// Adds a 3D density pass on top of the heightmap for caves, overhangs and arches. Off by
+    /// default, as it costs generation time and makes every chunk reach deeper.
+    pub generate_density: bool,
+    /// Height of the sea surface in full lod voxels.
+    pub sea_level: f32,
+    pub block_registry: Arc<BlockRegistry>,
+    pub biome_registry: Arc<BiomeRegistry>,
+    pub noise_graphs: Arc<NoiseGraphRegistry>,
+    pub terrain_settings: TerrainSettings,
+    /// Passes that turn the shaped terrain of a chunk into voxels, in the order they run. Shared
+    /// with the options retuned from these.
+    pub generation_pipeline: Arc<GenerationPipeline>,
+    /// Directory the path and structure caches are stored in, so they aren't generated again on
+    /// the next start. `None` keeps them in memory only.
+    pub generation_cache_directory: Option<PathBuf>,
+    /// Directory the registries, noise graphs and structure models were loaded from, which
+    /// retuning reloads them from.
+    pub asset_directory: PathBuf,
+    disk_cache: OnceLock<Option<CountryDiskCache>>,
+}
+
+/// Terrain parameters outside of the noise graphs and registries, which can be tuned while the
+/// game runs.
+#[derive(Clone, Debug, PartialEq)]
+pub struct TerrainSettings {
+    /// Voxels above the sea level over which the temperature drops by one. Lower values bring
+    /// the cold and snowy biomes further down the mountains.
+    pub snow_height: f32,
+    /// Columns closer to a path than this many voxels are covered with path blocks.
+    pub path_width: f32,
+    /// Distance from a path in voxels up to which the terrain is flattened towards it.
+    pub path_blend_distance: f32,
+    /// Structures aren't placed on terrain steeper than this.
+    pub structure_max_steepness: f64,
+    /// Overhangs start to grow out of terrain steeper than this.
+    pub overhang_steepness: f64,
+}
+
+impl Default for TerrainSettings {
+    fn default() -> Self {
+        Self {
+            snow_height: 4600.,
+            path_width: 8.75,
+            path_blend_distance: 16.5,
+            structure_max_steepness: 0.8,
+            overhang_steepness: 0.6,
+        }
+    }
+}
+
+impl GenerationOptions {
+    /// Options with the assets of the [`ASSET_DIRECTORY`]. Panics if they can't be loaded.
+    pub fn new(seed: u64, generate_paths: bool) -> Self {
+        Self::load(ASSET_DIRECTORY, seed, generate_paths).unwrap()
+    }
+
+    /// Options with the noise graphs, registries and structure models of the asset directory.
+    pub fn load(
+        asset_directory: impl AsRef<Path>,
+        seed: u64,
+        generate_paths: bool,
+    ) -> Result<Self, String> {
+        let asset_directory = asset_directory.as_ref();
+        let noise_graphs = NoiseGraphRegistry::load(asset_directory.join(NOISE_GRAPH_FILE))?;
+
+        Self::load_with_noise_graphs(
+            asset_directory,
+            seed,
+            generate_paths,
+            Arc::new(noise_graphs),
+        )
+    }
+
+    /// New options with the same seed, generation passes and density setting but other noise
+    /// graphs, biomes and settings. Nothing of the generation caches is kept, as all of it
+    /// depends on the terrain, only their budgets.
+    pub fn retune(
+        &self,
+        noise_graphs: Arc<NoiseGraphRegistry>,
+        biome_registry: Arc<BiomeRegistry>,
+        terrain_settings: TerrainSettings,
+    ) -> Self {
+        let mut generation_options = Self::load_with_noise_graphs(
+            &self.asset_directory,
+            self.seed,
+            self.generate_paths,
+            noise_graphs,
+        )
+        .unwrap();
+        generation_options.biome_registry = biome_registry;
+        generation_options.terrain_settings = terrain_settings;
+        generation_options.generate_density = self.generate_density;
+        generation_options.generation_pipeline = self.generation_pipeline.clone();
+        generation_options.generation_cache_directory = self.generation_cache_directory.clone();
+        generation_options
+            .path_cache
+            .set_max_memory(self.path_cache.max_memory());
+        generation_options
+            .structure_cache
+            .set_max_memory(self.structure_cache.max_memory());
+        generation_options
+            .water_cache
+            .set_max_memory(self.water_cache.max_memory());
+        generation_options
+    }
+
+    fn load_with_noise_graphs(
+        asset_directory: &Path,
+        seed: u64,
+        generate_paths: bool,
+        noise_graphs: Arc<NoiseGraphRegistry>,
+    ) -> Result<Self, String> {
+        let load_vox = |name: &str| {
+            let path = asset_directory.join(name);
+            from_file(&path)
+                .map(|vox_data| vox_data_to_structure_data(&vox_data))
+                .map_err(|error| format!("Failed to read structure model {path:?}: {error}"))
+        };
+        let tree_house = load_vox("tree_house.vox")?;
+        let box_structure = load_vox("box.vox")?;
+
+        let mut rng = StdRng::seed_from_u64(seed);
+
+        let block_registry = Arc::new(BlockRegistry::load(
+            asset_directory.join(BLOCK_REGISTRY_FILE),
+        )?);
+
+        let structure_generators: Vec<Arc<Box<dyn StructureGenerator + Send + Sync>>> = vec![
+            Arc::new(Box::new(OakStructureGenerator::new(
+                VoxelStructureMetadata {
+                    name: "oak".into(),
+                    model_size: [27, 27, 27],
+                    generation_size: [64, 64],
+                    grid_offset: [24, 16],
+                    generate_debug_blocks: false,
+                    debug_rgb_multiplier: [1., 1., 1.],
+                    noise: get_seeded_white_noise(rng.random()),
+                },
+            ))),
+            Arc::new(Box::new(OakStructureGenerator::new(
+                VoxelStructureMetadata {
+                    name: "forest_oak".into(),
+                    model_size: [27, 27, 27],
+                    generation_size: [64, 64],
+                    grid_offset: [43, 52],
+                    generate_debug_blocks: false,
+                    debug_rgb_multiplier: [1., 1., 1.],
+                    noise: get_seeded_white_noise(rng.random()),
+                },
+            ))),
+            Arc::new(Box::new(OakStructureGenerator::new(
+                VoxelStructureMetadata {
+                    name: "forest_oak".into(),
+                    model_size: [27, 27, 27],
+                    generation_size: [64, 64],
+                    grid_offset: [10, 4],
+                    generate_debug_blocks: false,
+                    debug_rgb_multiplier: [1., 1., 1.],
+                    noise: get_seeded_white_noise(rng.random()),
+                },
+            ))),
+            Arc::new(Box::new(FixedStructureGenerator {
+                fixed_structure_model: tree_house.0.clone(),
+                fixed_structure_metadata: VoxelStructureMetadata {
+                    name: "tree_house".into(),
+                    model_size: tree_house.1,
+                    generation_size: [1000, 1000],
+                    grid_offset: [7, 11],
+                    generate_debug_blocks: false,
+                    debug_rgb_multiplier: [1., 1., 1.],
+                    noise: get_seeded_white_noise(rng.random()),
+                },
+            })),
+            Arc::new(Box::new(PineStructureGenerator::new(
+                VoxelStructureMetadata {
+                    name: "pine".into(),
+                    model_size: [32, 70, 32],
+                    generation_size: [48, 48],
+                    grid_offset: [5, 29],
+                    generate_debug_blocks: false,
+                    debug_rgb_multiplier: [1., 1., 1.],
+                    noise: get_seeded_white_noise(rng.random()),
+                },
+            ))),
+        ];
+
+        let structure_names: Vec<&str> = structure_generators
+            .iter()
+            .map(|structure_generator| structure_generator.get_structure_metadata().name.as_str())
+            .collect();
+        let biome_registry = Arc::new(BiomeRegistry::load(
+            asset_directory.join(BIOME_REGISTRY_FILE),
+            &block_registry,
+            &structure_names,
+        )?);
+
+        Ok(Self {
+            seed,
+            generate_paths,
+            generate_density: false,
+            block_registry,
+            biome_registry,
+            noise_graphs,
+            terrain_settings: TerrainSettings::default(),
+            generation_pipeline: Arc::new(GenerationPipeline::default()),
+            path_cache: GenerationCache::new(PATH_CACHE_MEMORY),
+            structure_cache: GenerationCache::new(STRUCTURE_CACHE_MEMORY),
+            water_cache: GenerationCache::new(WATER_CACHE_MEMORY),
+            generation_cache_directory: Some(GENERATION_CACHE_DIRECTORY.into()),
+            disk_cache: OnceLock::new(),
+            sea_level: 1200.,
+            structure_generators,
+            structure_assets: vec![StructureAsset {
+                _blocks: (*box_structure.0).clone(),
+            }],
+            asset_directory: asset_directory.to_path_buf(),
+        })
+    }
+
+    /// Disk cache of the path and structure caches. Created on first use, as its fingerprint
+    /// has to include the changes made to the options after they were created.
+    pub fn disk_cache(&self) -> Option<&CountryDiskCache> {
+        self.disk_cache
+            .get_or_init(|| {
+                self.generation_cache_directory
+                    .as_ref()
+                    .map(|directory| CountryDiskCache::new(directory.clone(), self))
+            })
+            .as_ref()
+    }
+}
+
+/// Polls the noise graph file for changes and regenerates the world with the new graphs.
+#[derive(Resource)]
+pub struct NoiseGraphWatcher {
+    path: PathBuf,
+    modified: Option<SystemTime>,
+    timer: Timer,
+}
+
+impl NoiseGraphWatcher {
+    pub fn new(path: impl Into<PathBuf>) -> Self {
+        let path = path.into();
+
+        Self {
+            modified: get_modified_time(&path),
+            path,
+            timer: Timer::from_seconds(1., TimerMode::Repeating),
+        }
+    }
+}
+
+impl Default for NoiseGraphWatcher {
+    fn default() -> Self {
+        Self::new(Path::new(ASSET_DIRECTORY).join(NOISE_GRAPH_FILE))
+    }
+}
+
+fn get_modified_time(path: &Path) -> Option<SystemTime> {
+    fs::metadata(path)
+        .and_then(|metadata| metadata.modified())
+        .ok()
+}
+
+pub fn reload_noise_graphs(
+    time: Res<Time>,
+    mut watcher: ResMut<NoiseGraphWatcher>,
+    mut generation_options: ResMut<GenerationOptionsResource>,
+    mut regenerate_world: EventWriter<RegenerateWorld>,
+) {
+    if !watcher.timer.tick(time.delta()).just_finished() {
+        return;
+    }
+
+    let modified = get_modified_time(&watcher.path);
+    if modified == watcher.modified {
+        return;
+    }
+    watcher.modified = modified;
+
+    // Broken graphs keep the world as it is, so typos don't end the game while tuning.
+    match NoiseGraphRegistry::load(&watcher.path) {
+        Ok(noise_graphs) => {
+            info!("Reloaded noise graphs from {:?}", watcher.path);
+
+            let generation_options = &mut generation_options.0;
+            *generation_options = Arc::new(generation_options.retune(
+                Arc::new(noise_graphs),
+                generation_options.biome_registry.clone(),
+                generation_options.terrain_settings.clone(),
+            ));
+            regenerate_world.write(RegenerateWorld);
+        }
+        Err(error) => error!("{error}"),
+    }
 }
 
-pub trait GenerationCacheItem<K: Copy + Eq + Hash> {
+pub trait GenerationCacheItem<K: Copy + Eq + Hash>: Sized {
     fn generate(key: K, generation_options: &GenerationOptions) -> Self;
+
+    /// Estimated bytes the item keeps alive, including its heap allocations.
+    fn memory_size(&self) -> usize {
+        size_of::<Self>()
+    }
+}
+
+/// Directory the generation assets are loaded from by default, relative to the working directory.
+pub const ASSET_DIRECTORY: &str = "assets";
+
+/// Bytes the generation caches may keep before the least recently used entries are evicted.
+/// Evicted entries are generated again from the seed when they are needed, which gives the same
+/// result.
+pub const PATH_CACHE_MEMORY: usize = 64 << 20;
+pub const STRUCTURE_CACHE_MEMORY: usize = 1 << 20;
+pub const WATER_CACHE_MEMORY: usize = 256 << 20;
+pub const COUNTRY_CACHE_MEMORY: usize = 64 << 20;
+
+struct GenerationCacheEntry<T> {
+    item: RwLock<Option<Arc<T>>>,
+    last_access: AtomicU64,
 }
 
+/// Generated items by key, which are evicted in least recently used order once they take more
+/// memory than the budget.
 pub struct GenerationCache<K: Copy + Eq + Hash, T: GenerationCacheItem<K>> {
-    cache_lock: RwLock<HashMap<K, Arc<RwLock<Option<Arc<T>>>>>>,
+    cache_lock: RwLock<HashMap<K, Arc<GenerationCacheEntry<T>>>>,
+    access_counter: AtomicU64,
+    memory: AtomicUsize,
+    max_memory: AtomicUsize,
+}
+
+#[derive(Copy, Clone, Debug, Default)]
+pub struct GenerationCacheStats {
+    pub entries: usize,
+    pub memory: usize,
+    pub max_memory: usize,
 }
 
 impl<K: Copy + Eq + Hash, T: GenerationCacheItem<K>> GenerationCache<K, T> {
-    pub fn new() -> Self {
+    pub fn new(max_memory: usize) -> Self {
         Self {
             cache_lock: RwLock::new(HashMap::new()),
+            access_counter: AtomicU64::new(0),
+            memory: AtomicUsize::new(0),
+            max_memory: AtomicUsize::new(max_memory),
         }
     }
 
@@ -138,10 +436,13 @@ impl<K: Copy + Eq + Hash, T: GenerationCacheItem<K>> GenerationCache<K, T> {
         match self.cache_lock.try_read() {
             Ok(read) => {
                 let entry = read.get(&key)?;
-                match entry.try_read() {
+                match entry.item.try_read() {
                     Ok(read) => match read.deref() {
                         None => None,
-                        Some(t) => Some(t.clone()),
+                        Some(t) => {
+                            self.touch(entry);
+                            Some(t.clone())
+                        }
                     },
                     Err(_) => None,
                 }
@@ -150,16 +451,44 @@ impl<K: Copy + Eq + Hash, T: GenerationCacheItem<K>> GenerationCache<K, T> {
         }
     }
 
-    fn get_hash_lock_entry(&self, key: K) -> Arc<RwLock<Option<Arc<T>>>> {
+    pub fn max_memory(&self) -> usize {
+        self.max_memory.load(Ordering::Relaxed)
+    }
+
+    /// Changes the memory budget, evicting entries right away if it shrinks below the cached ones.
+    pub fn set_max_memory(&self, max_memory: usize) {
+        self.max_memory.store(max_memory, Ordering::Relaxed);
+        self.evict();
+    }
+
+    pub fn stats(&self) -> GenerationCacheStats {
+        GenerationCacheStats {
+            entries: self.cache_lock.read().unwrap().len(),
+            memory: self.memory.load(Ordering::Relaxed),
+            max_memory: self.max_memory(),
+        }
+    }
+
+    fn touch(&self, entry: &GenerationCacheEntry<T>) {
+        entry.last_access.store(
+            self.access_counter.fetch_add(1, Ordering::Relaxed),
+            Ordering::Relaxed,
+        );
+    }
+
+    fn get_hash_lock_entry(&self, key: K) -> Arc<GenerationCacheEntry<T>> {
         let read = self.cache_lock.read().unwrap();
-        match read.get(&key) {
+        let entry = match read.get(&key) {
             None => {
                 drop(read);
                 let mut write = self.cache_lock.write().unwrap();
                 let result = match write.get(&key) {
                     None => {
-                        let lock = Arc::new(RwLock::new(None));
-                        write.insert(key, lock);
+                        let entry = Arc::new(GenerationCacheEntry {
+                            item: RwLock::new(None),
+                            last_access: AtomicU64::new(0),
+                        });
+                        write.insert(key, entry);
                         write.get(&key).unwrap().clone()
                     }
                     Some(cache) => cache.clone(),
@@ -168,30 +497,157 @@ impl<K: Copy + Eq + Hash, T: GenerationCacheItem<K>> GenerationCache<K, T> {
                 result
             }
             Some(cache) => cache.clone(),
-        }
+        };
+        self.touch(&entry);
+        entry
     }
 
     fn get_generated_cache_entry(
         &self,
-        hash_lock_entry: Arc<RwLock<Option<Arc<T>>>>,
+        hash_lock_entry: Arc<GenerationCacheEntry<T>>,
         key: K,
         generation_options: &GenerationOptions,
     ) -> Arc<T> {
-        let read = hash_lock_entry.read().unwrap();
+        let read = hash_lock_entry.item.read().unwrap();
         match read.deref() {
             None => {
                 drop(read);
-                let mut write = hash_lock_entry.write().unwrap();
+                let mut write = hash_lock_entry.item.write().unwrap();
                 match write.deref() {
-                    None => write
-                        .insert(Arc::new(T::generate(key, generation_options)))
-                        .clone(),
+                    None => {
+                        let item = Arc::new(T::generate(key, generation_options));
+                        // Counted while the entry is still locked, so eviction never sees a
+                        // generated entry that isn't counted yet.
+                        self.memory.fetch_add(item.memory_size(), Ordering::Relaxed);
+                        let item = write.insert(item).clone();
+                        drop(write);
+                        self.evict();
+                        item
+                    }
                     Some(country_cache) => country_cache.clone(),
                 }
             }
             Some(country_cache) => country_cache.clone(),
         }
     }
+
+    /// Removes the least recently used generated entries until the cache fits into its budget.
+    /// Entries that are still generating are kept, as others already wait for them.
+    fn evict(&self) {
+        if self.memory.load(Ordering::Relaxed) <= self.max_memory() {
+            return;
+        }
+
+        let mut write = self.cache_lock.write().unwrap();
+        let mut candidates: Vec<(u64, K, usize)> = write
+            .iter()
+            .filter_map(|(key, entry)| {
+                let item = entry.item.try_read().ok()?;
+                let memory_size = item.as_ref()?.memory_size();
+                Some((entry.last_access.load(Ordering::Relaxed), *key, memory_size))
+            })
+            .collect();
+        candidates.sort_unstable_by_key(|(last_access, ..)| *last_access);
+
+        for (_, key, memory_size) in candidates {
+            if self.memory.load(Ordering::Relaxed) <= self.max_memory() {
+                break;
+            }
+
+            write.remove(&key);
+            self.memory.fetch_sub(memory_size, Ordering::Relaxed);
+        }
+    }
+}
+
+/// Country caches of the chunks the game generates, evicted in least recently used order once
+/// they take more memory than `max_memory`, like the entries of a [`GenerationCache`].
+pub struct CountryCaches {
+    caches: HashMap<IVec2, (GenerationState<CountryCache>, u64)>,
+    access_counter: u64,
+    memory: usize,
+    pub max_memory: usize,
+}
+
+impl CountryCaches {
+    pub fn new(max_memory: usize) -> Self {
+        Self {
+            caches: HashMap::new(),
+            access_counter: 0,
+            memory: 0,
+            max_memory,
+        }
+    }
+
+    /// Gets the country cache and marks it as recently used.
+    pub fn get(&mut self, country_pos: &IVec2) -> Option<&GenerationState<CountryCache>> {
+        let (state, last_access) = self.caches.get_mut(country_pos)?;
+        self.access_counter += 1;
+        *last_access = self.access_counter;
+        Some(state)
+    }
+
+    /// Gets the country cache without changing the eviction order.
+    pub fn peek(&self, country_pos: &IVec2) -> Option<&GenerationState<CountryCache>> {
+        self.caches.get(country_pos).map(|(state, _)| state)
+    }
+
+    pub fn insert(&mut self, country_pos: IVec2, state: GenerationState<CountryCache>) {
+        self.access_counter += 1;
+        self.memory += Self::memory_size(&state);
+        if let Some((replaced_state, _)) = self
+            .caches
+            .insert(country_pos, (state, self.access_counter))
+        {
+            self.memory -= Self::memory_size(&replaced_state);
+        }
+        self.evict();
+    }
+
+    pub fn clear(&mut self) {
+        self.caches.clear();
+        self.memory = 0;
+    }
+
+    pub fn len(&self) -> usize {
+        self.caches.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.caches.is_empty()
+    }
+
+    pub fn memory(&self) -> usize {
+        self.memory
+    }
+
+    /// Removes the least recently used country caches that are done generating until the rest
+    /// fits into the budget, as a cache task still inserts its result and a missing entry would
+    /// start another one.
+    pub fn evict(&mut self) {
+        while self.memory > self.max_memory {
+            let Some(country_pos) = self
+                .caches
+                .iter()
+                .filter(|(_, (state, _))| matches!(state, GenerationState::Some(_)))
+                .min_by_key(|(_, (_, last_access))| *last_access)
+                .map(|(country_pos, _)| *country_pos)
+            else {
+                return;
+            };
+
+            if let Some((state, _)) = self.caches.remove(&country_pos) {
+                self.memory -= Self::memory_size(&state);
+            }
+        }
+    }
+
+    fn memory_size(state: &GenerationState<CountryCache>) -> usize {
+        match state {
+            GenerationState::Generating => size_of::<GenerationState<CountryCache>>(),
+            GenerationState::Some(country_cache) => country_cache.memory_size(),
+        }
+    }
 }
 
 pub struct StructureAsset {
@@ -206,7 +662,7 @@ fn vox_data_to_blocks(vox_data: &VoxData) -> Vec<Vec<Vec<BlockType>>> {
         for y in 0..model.size.z {
             result[x as usize].push(Vec::with_capacity(model.size.y as usize));
             for _ in 0..model.size.y {
-                result[x as usize][y as usize].push(BlockType::Air);
+                result[x as usize][y as usize].push(BlockType::AIR);
             }
         }
     }
@@ -214,7 +670,7 @@ fn vox_data_to_blocks(vox_data: &VoxData) -> Vec<Vec<Vec<BlockType>>> {
     for voxel in model.voxels.iter() {
         let color = vox_data.palette.colors[voxel.color_index.0 as usize];
         result[voxel.point.x as usize][voxel.point.z as usize][voxel.point.y as usize] =
-            BlockType::Stone;
+            BlockType::STONE;
     }
 
     result
@@ -1,20 +1,26 @@
+use crate::world_generation::chunk_generation::chunk_edits::ChunkEdits;
+use crate::world_generation::chunk_generation::light_propagation::light_chunk;
 use crate::world_generation::chunk_generation::mesh_generation::generate_mesh;
 use crate::world_generation::chunk_generation::voxel_generation::generate_voxels;
-use crate::world_generation::chunk_generation::{ChunkTaskData, CHUNK_SIZE, VOXEL_SIZE};
+use crate::world_generation::chunk_generation::ChunkTaskData;
 use crate::world_generation::chunk_loading::country_cache::CountryCache;
 use crate::world_generation::chunk_loading::quad_tree_data::QuadTreeNode;
+use crate::world_generation::chunk_settings::chunk_settings;
 use crate::world_generation::generation_options::GenerationOptions;
-use bevy::math::{IVec3, Vec3};
-use bevy::prelude::{Entity, IVec2, Resource, Transform};
-use bevy_rapier3d::prelude::Collider;
+use bevy::math::{IVec3, Vec3Swizzles};
+use bevy::prelude::{Entity, IVec2, Resource};
+use serde::Deserialize;
 use std::collections::HashMap;
 use std::sync::Arc;
 
 use super::chunk_generation::voxel_types::VoxelData;
 
-pub const MAX_LOD: ChunkLod = ChunkLod::OneTwentyEight;
+/// Lod of the quadtree root chunks, as chosen in the [`chunk_settings`].
+pub fn max_lod() -> ChunkLod {
+    chunk_settings().max_lod
+}
 
-#[derive(Copy, Clone, Debug, PartialEq)]
+#[derive(Copy, Clone, Debug, PartialEq, Deserialize)]
 pub enum ChunkLod {
     Full = 1,
     Half = 2,
@@ -55,14 +61,14 @@ impl ChunkLod {
     pub const fn multiplier_f32(self) -> f32 {
         self.multiplier_i32() as f32
     }
-    pub const fn inverse_multiplier_i32(self) -> i32 {
-        2i32.pow(MAX_LOD as u32 - self as u32)
+    pub fn inverse_multiplier_i32(self) -> i32 {
+        2i32.pow(max_lod() as u32 - self as u32)
     }
     pub fn previous(self) -> Self {
         ChunkLod::from_u8(self as u8 - 1).expect("Mapping doesn't exist!")
     }
 
-    fn from_u8(number: u8) -> Option<Self> {
+    pub fn from_u8(number: u8) -> Option<Self> {
         match number {
             1 => Some(Self::Full),
             2 => Some(Self::Half),
@@ -78,6 +84,37 @@ impl ChunkLod {
     }
 }
 
+/// Lods of the chunks bordering a chunk along x and z. A side touching several chunks stores the
+/// finest of them, and `None` if nothing is loaded there yet.
+#[derive(Copy, Clone, Debug, Default, PartialEq)]
+pub struct NeighbourLods(pub [Option<ChunkLod>; 4]);
+
+impl NeighbourLods {
+    pub const DIRECTIONS: [IVec3; 4] = [IVec3::X, IVec3::NEG_X, IVec3::Z, IVec3::NEG_Z];
+
+    pub fn get(&self, direction: IVec3) -> Option<ChunkLod> {
+        Self::DIRECTIONS
+            .iter()
+            .position(|neighbour_direction| *neighbour_direction == direction)
+            .and_then(|index| self.0[index])
+    }
+
+    /// Whether the border in the direction has to be stitched, because the neighbour is meshed at
+    /// another lod and its surface doesn't line up with ours.
+    pub fn needs_stitching(&self, direction: IVec3, chunk_lod: ChunkLod) -> bool {
+        self.get(direction)
+            .is_some_and(|neighbour_lod| neighbour_lod != chunk_lod)
+    }
+
+    /// Whether a chunk meshed against these or the other neighbour lods ends up with the same mesh.
+    pub fn stitches_like(&self, other: &Self, chunk_lod: ChunkLod) -> bool {
+        Self::DIRECTIONS.iter().all(|direction| {
+            self.needs_stitching(*direction, chunk_lod)
+                == other.needs_stitching(*direction, chunk_lod)
+        })
+    }
+}
+
 pub struct QuadTreeVoxelWorld {
     chunk_trees: HashMap<[i32; 2], Box<Option<QuadTreeNode<HashMap<i32, Entity>>>>>,
 }
@@ -98,6 +135,8 @@ pub trait VoxelWorld {
         generation_options: Arc<GenerationOptions>,
         chunk_height: i32,
         country_cache: &CountryCache,
+        chunk_edits: &[Arc<ChunkEdits>],
+        neighbour_lods: NeighbourLods,
     ) -> ChunkGenerationResult;
     fn has_chunk(&self, chunk_position: [i32; 2]) -> bool;
     fn add_chunk(
@@ -110,6 +149,23 @@ pub trait VoxelWorld {
         &mut self,
         chunk_position: [i32; 2],
     ) -> Option<&mut Box<Option<QuadTreeNode<HashMap<i32, Entity>>>>>;
+    /// Lod of the finest quadtree leaf covering the chunk, which matches `chunk_lod` as long as
+    /// the chunk is still part of the tree.
+    fn get_leaf_lod(
+        &self,
+        parent_pos: IVec2,
+        chunk_lod: ChunkLod,
+        lod_position: IVec2,
+    ) -> Option<ChunkLod>;
+    fn get_neighbour_lods(
+        &self,
+        parent_pos: IVec2,
+        chunk_lod: ChunkLod,
+        lod_position: IVec2,
+    ) -> NeighbourLods;
+    /// Chunks of the quadtree leaves overlapping the area between `min` and `max` (exclusive) or
+    /// bordering it, in full lod chunks.
+    fn get_chunks_around(&self, min: IVec2, max: IVec2) -> Vec<Entity>;
 }
 
 impl Resource for QuadTreeVoxelWorld {}
@@ -124,6 +180,7 @@ pub struct ChunkGenerationResult {
     pub voxel_data: VoxelData,
     pub chunk_pos: IVec3,
     pub min_height: i32,
+    pub neighbour_lods: NeighbourLods,
 }
 
 impl VoxelWorld for QuadTreeVoxelWorld {
@@ -134,42 +191,52 @@ impl VoxelWorld for QuadTreeVoxelWorld {
         generation_options: Arc<GenerationOptions>,
         chunk_height: i32,
         country_cache: &CountryCache,
+        chunk_edits: &[Arc<ChunkEdits>],
+        neighbour_lods: NeighbourLods,
     ) -> ChunkGenerationResult {
         let new_chunk_pos = [
-            parent_pos.x * MAX_LOD.multiplier_i32() + lod_position.x * chunk_lod.multiplier_i32(),
+            parent_pos.x * max_lod().multiplier_i32() + lod_position.x * chunk_lod.multiplier_i32(),
             chunk_height,
-            parent_pos.y * MAX_LOD.multiplier_i32() + lod_position.y * chunk_lod.multiplier_i32(),
+            parent_pos.y * max_lod().multiplier_i32() + lod_position.y * chunk_lod.multiplier_i32(),
         ];
 
-        let (data, min_height, more) = generate_voxels(
+        let (mut data, min_height, more) = generate_voxels(
             new_chunk_pos,
             &generation_options,
             chunk_lod,
             &country_cache,
         );
 
-        let mesh = generate_mesh(&data, min_height, chunk_lod);
+        for edits in chunk_edits {
+            edits.apply(
+                &mut data,
+                IVec3::from_array(new_chunk_pos),
+                min_height,
+                chunk_lod,
+            );
+        }
 
-        let chunk_transform_pos = Vec3::new(
-            new_chunk_pos[0] as f32 * CHUNK_SIZE as f32 * VOXEL_SIZE,
-            0.0,
-            new_chunk_pos[2] as f32 * CHUNK_SIZE as f32 * VOXEL_SIZE,
+        if chunk_lod == ChunkLod::Full {
+            light_chunk(&mut data, &generation_options.block_registry);
+        }
+
+        let mesh = generate_mesh(
+            &data,
+            min_height,
+            chunk_lod,
+            neighbour_lods,
+            &generation_options.block_registry,
         );
 
+        // Blocks placed above the terrain need the chunks up to them as well.
+        let generate_above = more
+            || chunk_edits.iter().any(|edits| {
+                edits.reaches_above(IVec3::from_array(new_chunk_pos), min_height, chunk_lod)
+            });
+
         return ChunkGenerationResult {
-            task_data: match mesh {
-                None => None,
-                Some(mesh) => Some(ChunkTaskData {
-                    transform: Transform::from_translation(chunk_transform_pos),
-                    collider: if chunk_lod == ChunkLod::Full {
-                        Some(Collider::trimesh(mesh.1, mesh.2).expect("Failed to build trimesh"))
-                    } else {
-                        None
-                    },
-                    mesh: mesh.0,
-                }),
-            },
-            generate_above: more,
+            task_data: ChunkTaskData::from_mesh(mesh, IVec3::from_array(new_chunk_pos), chunk_lod),
+            generate_above,
             parent_pos,
             lod: chunk_lod,
             lod_position,
@@ -177,6 +244,7 @@ impl VoxelWorld for QuadTreeVoxelWorld {
             voxel_data: data,
             chunk_pos: IVec3::from_array(new_chunk_pos),
             min_height,
+            neighbour_lods,
         };
     }
 
@@ -209,4 +277,97 @@ impl VoxelWorld for QuadTreeVoxelWorld {
     ) -> Option<&mut Box<Option<QuadTreeNode<HashMap<i32, Entity>>>>> {
         self.chunk_trees.get_mut(&chunk_position)
     }
+
+    fn get_leaf_lod(
+        &self,
+        parent_pos: IVec2,
+        chunk_lod: ChunkLod,
+        lod_position: IVec2,
+    ) -> Option<ChunkLod> {
+        let size = chunk_lod.multiplier_i32();
+        let chunk_min = parent_pos * max_lod().multiplier_i32() + lod_position * size;
+
+        self.get_finest_lod(chunk_min, chunk_min + size)
+    }
+
+    fn get_neighbour_lods(
+        &self,
+        parent_pos: IVec2,
+        chunk_lod: ChunkLod,
+        lod_position: IVec2,
+    ) -> NeighbourLods {
+        let size = chunk_lod.multiplier_i32();
+        let chunk_min = parent_pos * max_lod().multiplier_i32() + lod_position * size;
+
+        NeighbourLods(NeighbourLods::DIRECTIONS.map(|direction| {
+            // A one full lod chunk wide strip running along the side of the chunk.
+            let direction = direction.xz();
+            let strip_min =
+                chunk_min + direction.max(IVec2::ZERO) * size + direction.min(IVec2::ZERO);
+            let strip_max = strip_min + direction.abs() + (IVec2::ONE - direction.abs()) * size;
+
+            self.get_finest_lod(strip_min, strip_max)
+        }))
+    }
+
+    fn get_chunks_around(&self, min: IVec2, max: IVec2) -> Vec<Entity> {
+        let (min, max) = (min - 1, max + 1);
+        let mut chunks = vec![];
+
+        for parent in Self::get_parents_in_area(min, max) {
+            let Some(tree) = self
+                .chunk_trees
+                .get(&parent.to_array())
+                .and_then(|tree| (**tree).as_ref())
+            else {
+                continue;
+            };
+
+            tree.run_on_data_in_area(
+                max_lod(),
+                parent * max_lod().multiplier_i32(),
+                min,
+                max,
+                &mut |entities| chunks.extend(entities.values()),
+            );
+        }
+
+        chunks
+    }
+}
+
+impl QuadTreeVoxelWorld {
+    /// Quadtree leaf containing the full lod chunk at `chunk_pos`, as its lod, its position in
+    /// chunks of that lod inside its root and its chunks by height.
+    pub fn get_leaf_mut(
+        &mut self,
+        chunk_pos: IVec2,
+    ) -> Option<(ChunkLod, IVec2, &mut HashMap<i32, Entity>)> {
+        let parent_pos = chunk_pos.div_euclid(IVec2::splat(max_lod().multiplier_i32()));
+        let local_pos = chunk_pos - parent_pos * max_lod().multiplier_i32();
+        let tree = (**self.chunk_trees.get_mut(&parent_pos.to_array())?).as_mut()?;
+        let (lod, chunks) = tree.get_data_at_mut(max_lod(), local_pos)?;
+
+        Some((lod, local_pos / lod.multiplier_i32(), chunks))
+    }
+
+    /// Finest lod loaded in the area between `min` and `max` (exclusive), in full lod chunks.
+    fn get_finest_lod(&self, min: IVec2, max: IVec2) -> Option<ChunkLod> {
+        Self::get_parents_in_area(min, max)
+            .filter_map(|parent| {
+                let tree = (**self.chunk_trees.get(&parent.to_array())?).as_ref()?;
+                tree.get_finest_lod(max_lod(), parent * max_lod().multiplier_i32(), min, max)
+            })
+            .min_by_key(|lod| lod.usize())
+    }
+
+    /// Positions of the quadtree roots overlapping the area between `min` and `max` (exclusive),
+    /// in full lod chunks.
+    fn get_parents_in_area(min: IVec2, max: IVec2) -> impl Iterator<Item = IVec2> {
+        let parent_min = min.div_euclid(IVec2::splat(max_lod().multiplier_i32()));
+        let parent_max = (max - 1).div_euclid(IVec2::splat(max_lod().multiplier_i32()));
+
+        (parent_min.x..=parent_max.x)
+            .flat_map(move |x| (parent_min.y..=parent_max.y).map(move |z| IVec2::new(x, z)))
+    }
 }
@@ -0,0 +1,140 @@
+use crate::world_generation::chunk_generation::block_registry::BlockRegistry;
+use crate::world_generation::chunk_generation::chunk_edits::ChunkEdits;
+use crate::world_generation::chunk_generation::light_propagation::light_chunk;
+use crate::world_generation::chunk_generation::mesh_generation::{
+    generate_mesh_buffers, ChunkMeshes,
+};
+use crate::world_generation::chunk_generation::voxel_generation::generate_voxels;
+use crate::world_generation::chunk_generation::voxel_types::VoxelData;
+use crate::world_generation::chunk_loading::country_cache::{get_country_position, CountryCache};
+use crate::world_generation::generation_options::{
+    GenerationCache, GenerationOptions, ASSET_DIRECTORY, COUNTRY_CACHE_MEMORY,
+};
+use crate::world_generation::voxel_world::{max_lod, ChunkLod, NeighbourLods};
+use bevy::math::{IVec2, IVec3, Vec3Swizzles};
+use std::path::Path;
+use std::sync::Arc;
+
+/// Generates terrain without a bevy `App`, e.g. for tools, servers or tests.
+///
+/// Chunk positions are given in full lod chunk units, so a chunk at a coarser lod covers
+/// `chunk_lod.multiplier_i32()` chunks along x and z. The y component is the index of the chunk
+/// inside its column, starting at 0 for the lowest chunk.
+pub struct WorldGenerator {
+    generation_options: Arc<GenerationOptions>,
+    country_caches: GenerationCache<IVec2, CountryCache>,
+}
+
+pub struct GeneratedChunk {
+    pub chunk_pos: IVec3,
+    pub chunk_lod: ChunkLod,
+    pub voxel_data: VoxelData,
+    pub min_height: i32,
+    pub generate_above: bool,
+}
+
+impl GeneratedChunk {
+    pub fn generate_mesh(&self, block_registry: &BlockRegistry) -> ChunkMeshes {
+        self.generate_stitched_mesh(NeighbourLods::default(), block_registry)
+    }
+
+    /// Meshes the chunk with skirts on the sides bordering chunks of another lod.
+    pub fn generate_stitched_mesh(
+        &self,
+        neighbour_lods: NeighbourLods,
+        block_registry: &BlockRegistry,
+    ) -> ChunkMeshes {
+        generate_mesh_buffers(
+            &self.voxel_data,
+            self.min_height,
+            self.chunk_lod,
+            neighbour_lods,
+            block_registry,
+        )
+    }
+}
+
+impl WorldGenerator {
+    /// Generator with the assets of the [`ASSET_DIRECTORY`]. Panics if they can't be loaded.
+    pub fn new(seed: u64) -> Self {
+        Self::load(ASSET_DIRECTORY, seed).unwrap()
+    }
+
+    /// Generator with the registries, noise graphs and structure models of the asset directory.
+    pub fn load(asset_directory: impl AsRef<Path>, seed: u64) -> Result<Self, String> {
+        let generation_options = GenerationOptions::load(asset_directory, seed, false)?;
+
+        Ok(Self::from_options(Arc::new(generation_options)))
+    }
+
+    pub fn from_options(generation_options: Arc<GenerationOptions>) -> Self {
+        Self {
+            generation_options,
+            country_caches: GenerationCache::new(COUNTRY_CACHE_MEMORY),
+        }
+    }
+
+    pub fn generation_options(&self) -> &Arc<GenerationOptions> {
+        &self.generation_options
+    }
+
+    pub fn get_country_cache(&self, chunk_pos: IVec2) -> Arc<CountryCache> {
+        let parent_pos = chunk_pos.div_euclid(IVec2::splat(max_lod().multiplier_i32()));
+        self.country_caches
+            .get_cache_entry(get_country_position(parent_pos), &self.generation_options)
+    }
+
+    pub fn generate_chunk(&self, chunk_pos: IVec3, chunk_lod: ChunkLod) -> GeneratedChunk {
+        self.generate_chunk_with_edits(chunk_pos, chunk_lod, &[])
+    }
+
+    pub fn generate_chunk_with_edits(
+        &self,
+        chunk_pos: IVec3,
+        chunk_lod: ChunkLod,
+        chunk_edits: &[Arc<ChunkEdits>],
+    ) -> GeneratedChunk {
+        let country_cache = self.get_country_cache(chunk_pos.xz());
+
+        let (mut voxel_data, min_height, generate_above) = generate_voxels(
+            chunk_pos.to_array(),
+            &self.generation_options,
+            chunk_lod,
+            &country_cache,
+        );
+
+        for edits in chunk_edits {
+            edits.apply(&mut voxel_data, chunk_pos, min_height, chunk_lod);
+        }
+
+        if chunk_lod == ChunkLod::Full {
+            light_chunk(&mut voxel_data, &self.generation_options.block_registry);
+        }
+
+        GeneratedChunk {
+            chunk_pos,
+            chunk_lod,
+            voxel_data,
+            min_height,
+            generate_above,
+        }
+    }
+
+    /// Generates the chunks of a column from the bottom up until the terrain ends.
+    pub fn generate_column(&self, chunk_pos: IVec2, chunk_lod: ChunkLod) -> Vec<GeneratedChunk> {
+        let mut chunks = vec![];
+
+        loop {
+            let chunk = self.generate_chunk(
+                IVec3::new(chunk_pos.x, chunks.len() as i32, chunk_pos.y),
+                chunk_lod,
+            );
+            let generate_above = chunk.generate_above;
+            chunks.push(chunk);
+
+            if !generate_above {
+                return chunks;
+            }
+        }
+    }
+}
@@ -0,0 +1,625 @@
+use crate::player::{Player, PlayerSpawnCallback};
+use crate::world_generation::chunk_generation::biome_registry::Biome;
+use crate::world_generation::chunk_generation::block_registry::BlockRegistry;
+use crate::world_generation::chunk_generation::chunk_edits::{BlockEdits, ChunkEdits};
+use crate::world_generation::chunk_generation::noise::noise_graph::NoiseGraphRegistry;
+use crate::world_generation::chunk_generation::BlockType;
+use crate::world_generation::generation_options::{
+    GenerationOptions, GenerationOptionsResource, TerrainSettings,
+};
+use bevy::app::AppExit;
+use bevy::prelude::*;
+use flate2::read::ZlibDecoder;
+use flate2::write::ZlibEncoder;
+use flate2::Compression;
+use std::collections::{HashMap, HashSet};
+use std::fs;
+use std::io::{self, Read, Write};
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+
+pub const SAVE_DIRECTORY: &str = "saves";
+pub const WORLD_SAVE_VERSION: u32 = 3;
+/// Oldest save version that can still be read.
+const MIN_WORLD_SAVE_VERSION: u32 = 1;
+
+const WORLD_FILE_MAGIC: &[u8; 4] = b"OTWD";
+const REGION_FILE_MAGIC: &[u8; 4] = b"OTRG";
+/// Width of a region file in quad tree root chunks.
+const REGION_SIZE: i32 = 16;
+
+pub struct WorldSavePlugin;
+
+impl Plugin for WorldSavePlugin {
+    fn build(&self, app: &mut App) {
+        app.add_systems(Update, (load_world_save, save_world_on_key))
+            .add_systems(Last, save_world_on_exit);
+    }
+}
+
+#[derive(Resource)]
+pub struct WorldSave {
+    pub directory: PathBuf,
+    pub seed: u64,
+    pub generate_paths: bool,
+    pub player_position: Option<Vec3>,
+    pub save_key: KeyCode,
+    /// Block names by the ids used in the region files, as block ids change with the registry.
+    block_names: Vec<String>,
+    /// Generation settings the world was saved with, which replace the ones of the assets. Saves
+    /// older than version 3 don't have them.
+    generation_settings: Option<GenerationSettings>,
+    loaded: bool,
+}
+
+/// Everything besides the seed the terrain depends on, so a retuned world is generated the same
+/// way after loading it.
+struct GenerationSettings {
+    generate_density: bool,
+    sea_level: f32,
+    terrain_settings: TerrainSettings,
+    /// Noise graphs in the format of the noise graph file.
+    noise_graphs: String,
+    biomes: Vec<BiomeSettings>,
+}
+
+/// Tunable values of a biome, restored into the biome of the same name.
+struct BiomeSettings {
+    name: String,
+    temperature: f32,
+    moisture: f32,
+    subsurface_depth: u32,
+    max_surface_steepness: f64,
+    height_multiplier: f32,
+    height_offset: f32,
+}
+
+impl GenerationSettings {
+    fn new(generation_options: &GenerationOptions) -> io::Result<Self> {
+        Ok(Self {
+            generate_density: generation_options.generate_density,
+            sea_level: generation_options.sea_level,
+            terrain_settings: generation_options.terrain_settings.clone(),
+            noise_graphs: generation_options
+                .noise_graphs
+                .to_ron()
+                .map_err(|error| invalid_data(&error))?,
+            biomes: generation_options
+                .biome_registry
+                .iter()
+                .map(|biome| BiomeSettings {
+                    name: biome.name.clone(),
+                    temperature: biome.temperature,
+                    moisture: biome.moisture,
+                    subsurface_depth: biome.subsurface_depth,
+                    max_surface_steepness: biome.max_surface_steepness,
+                    height_multiplier: biome.height_multiplier,
+                    height_offset: biome.height_offset,
+                })
+                .collect(),
+        })
+    }
+
+    fn write_to(&self, data: &mut Vec<u8>) {
+        data.push(self.generate_density as u8);
+        data.extend_from_slice(&self.sea_level.to_le_bytes());
+        data.extend_from_slice(&self.terrain_settings.snow_height.to_le_bytes());
+        data.extend_from_slice(&self.terrain_settings.path_width.to_le_bytes());
+        data.extend_from_slice(&self.terrain_settings.path_blend_distance.to_le_bytes());
+        data.extend_from_slice(&self.terrain_settings.structure_max_steepness.to_le_bytes());
+        data.extend_from_slice(&self.terrain_settings.overhang_steepness.to_le_bytes());
+        write_string(data, &self.noise_graphs);
+
+        data.extend_from_slice(&(self.biomes.len() as u32).to_le_bytes());
+        for biome in &self.biomes {
+            write_string(data, &biome.name);
+            data.extend_from_slice(&biome.temperature.to_le_bytes());
+            data.extend_from_slice(&biome.moisture.to_le_bytes());
+            data.extend_from_slice(&biome.subsurface_depth.to_le_bytes());
+            data.extend_from_slice(&biome.max_surface_steepness.to_le_bytes());
+            data.extend_from_slice(&biome.height_multiplier.to_le_bytes());
+            data.extend_from_slice(&biome.height_offset.to_le_bytes());
+        }
+    }
+
+    fn read_from(reader: &mut impl Read) -> io::Result<Self> {
+        Ok(Self {
+            generate_density: read_u8(reader)? != 0,
+            sea_level: read_f32(reader)?,
+            terrain_settings: TerrainSettings {
+                snow_height: read_f32(reader)?,
+                path_width: read_f32(reader)?,
+                path_blend_distance: read_f32(reader)?,
+                structure_max_steepness: read_f64(reader)?,
+                overhang_steepness: read_f64(reader)?,
+            },
+            noise_graphs: read_string(reader)?,
+            biomes: (0..read_u32(reader)?)
+                .map(|_| {
+                    Ok(BiomeSettings {
+                        name: read_string(reader)?,
+                        temperature: read_f32(reader)?,
+                        moisture: read_f32(reader)?,
+                        subsurface_depth: read_u32(reader)?,
+                        max_surface_steepness: read_f64(reader)?,
+                        height_multiplier: read_f32(reader)?,
+                        height_offset: read_f32(reader)?,
+                    })
+                })
+                .collect::<io::Result<_>>()?,
+        })
+    }
+
+    /// The generation options with these settings. Settings the assets can't take anymore, like
+    /// noise graphs of an older format or removed biomes, are reported and left as the assets have
+    /// them, so the terrain can differ from when the world was saved.
+    fn apply(&self, generation_options: &GenerationOptions) -> GenerationOptions {
+        let noise_graphs = match NoiseGraphRegistry::parse(&self.noise_graphs) {
+            Ok(noise_graphs) => Arc::new(noise_graphs),
+            Err(error) => {
+                warn!(
+                    "Failed to read the saved noise graphs, using the ones of the assets: {error}"
+                );
+                generation_options.noise_graphs.clone()
+            }
+        };
+
+        let mut biome_registry = (*generation_options.biome_registry).clone();
+        for biome in biome_registry.iter_mut() {
+            match self.biomes.iter().find(|saved| saved.name == biome.name) {
+                Some(saved) => saved.apply(biome),
+                None => warn!("Biome {:?} isn't part of the world save", biome.name),
+            }
+        }
+        for saved in &self.biomes {
+            if !biome_registry.iter().any(|biome| biome.name == saved.name) {
+                warn!("Saved biome {:?} doesn't exist anymore", saved.name);
+            }
+        }
+
+        let mut new_generation_options = generation_options.retune(
+            noise_graphs,
+            Arc::new(biome_registry),
+            self.terrain_settings.clone(),
+        );
+        new_generation_options.generate_density = self.generate_density;
+        new_generation_options.sea_level = self.sea_level;
+        new_generation_options
+    }
+}
+
+impl BiomeSettings {
+    fn apply(&self, biome: &mut Biome) {
+        biome.temperature = self.temperature;
+        biome.moisture = self.moisture;
+        biome.subsurface_depth = self.subsurface_depth;
+        biome.max_surface_steepness = self.max_surface_steepness;
+        biome.height_multiplier = self.height_multiplier;
+        biome.height_offset = self.height_offset;
+    }
+}
+
+impl WorldSave {
+    pub fn new(directory: impl Into<PathBuf>, seed: u64) -> Self {
+        Self {
+            directory: directory.into(),
+            seed,
+            generate_paths: false,
+            player_position: None,
+            save_key: KeyCode::F5,
+            block_names: vec![],
+            generation_settings: None,
+            loaded: false,
+        }
+    }
+
+    pub fn is_loaded(&self) -> bool {
+        self.loaded
+    }
+
+    fn world_file(&self) -> PathBuf {
+        self.directory.join("world.dat")
+    }
+
+    fn region_directory(&self) -> PathBuf {
+        self.directory.join("regions")
+    }
+
+    /// Reads the world file if the save exists, overriding the settings this save was created
+    /// with. Returns whether a save was found. Nothing is overridden if the file can't be read
+    /// completely.
+    pub fn read_world(&mut self) -> io::Result<bool> {
+        let Some((mut reader, version)) = open_file(&self.world_file(), WORLD_FILE_MAGIC)? else {
+            return Ok(false);
+        };
+
+        let seed = read_u64(&mut reader)?;
+        let generate_paths = read_u8(&mut reader)? != 0;
+        let player_position = if read_u8(&mut reader)? != 0 {
+            Some(Vec3::new(
+                read_f32(&mut reader)?,
+                read_f32(&mut reader)?,
+                read_f32(&mut reader)?,
+            ))
+        } else {
+            None
+        };
+
+        let block_names = if version >= 2 {
+            (0..read_u32(&mut reader)?)
+                .map(|_| read_string(&mut reader))
+                .collect::<io::Result<_>>()?
+        } else {
+            BlockType::BUILTIN
+                .iter()
+                .map(|(_, name)| name.to_string())
+                .collect()
+        };
+
+        let generation_settings = if version >= 3 {
+            Some(GenerationSettings::read_from(&mut reader)?)
+        } else {
+            None
+        };
+
+        self.seed = seed;
+        self.generate_paths = generate_paths;
+        self.player_position = player_position;
+        self.block_names = block_names;
+        self.generation_settings = generation_settings;
+
+        Ok(true)
+    }
+
+    pub fn read_block_edits(&self, block_registry: &BlockRegistry) -> io::Result<BlockEdits> {
+        let block_types: Vec<Option<BlockType>> = self
+            .block_names
+            .iter()
+            .map(|name| block_registry.get_by_name(name))
+            .collect();
+
+        let mut block_edits = BlockEdits::default();
+
+        let entries = match fs::read_dir(self.region_directory()) {
+            Ok(entries) => entries,
+            Err(error) if error.kind() == io::ErrorKind::NotFound => return Ok(block_edits),
+            Err(error) => return Err(error),
+        };
+
+        for entry in entries {
+            let path = entry?.path();
+
+            // Files of a save that failed halfway are left behind next to the regions.
+            if path.extension().is_none_or(|extension| extension != "dat") {
+                continue;
+            }
+
+            let Some((mut reader, _)) = open_file(&path, REGION_FILE_MAGIC)? else {
+                continue;
+            };
+
+            for _ in 0..read_u32(&mut reader)? {
+                // The root chunk the edits were saved in. The chunk settings can differ from the
+                // ones the save was written with, so the edits are sorted into the root chunks of
+                // the current settings instead.
+                let _parent_pos = [read_i32(&mut reader)?, read_i32(&mut reader)?];
+
+                for _ in 0..read_u32(&mut reader)? {
+                    let position = IVec3::new(
+                        read_i32(&mut reader)?,
+                        read_i32(&mut reader)?,
+                        read_i32(&mut reader)?,
+                    );
+                    let id = read_u8(&mut reader)? as usize;
+                    let block = block_types.get(id).copied().flatten().ok_or_else(|| {
+                        invalid_data(&format!(
+                            "Unknown block {:?}",
+                            self.block_names.get(id).map_or("", String::as_str)
+                        ))
+                    })?;
+                    block_edits.set_block(position, block);
+                }
+            }
+        }
+
+        Ok(block_edits)
+    }
+
+    /// Writes the world and its regions next to the previous save first and only moves them into
+    /// place once every file is written, so a failed save keeps the previous one readable.
+    pub fn write(
+        &self,
+        block_edits: &BlockEdits,
+        generation_options: &GenerationOptions,
+    ) -> io::Result<()> {
+        let block_registry = &generation_options.block_registry;
+        fs::create_dir_all(self.region_directory())?;
+
+        let mut world = Vec::new();
+        world.extend_from_slice(&self.seed.to_le_bytes());
+        world.push(self.generate_paths as u8);
+        match self.player_position {
+            None => world.push(0),
+            Some(position) => {
+                world.push(1);
+                for axis in position.to_array() {
+                    world.extend_from_slice(&axis.to_le_bytes());
+                }
+            }
+        }
+        world.extend_from_slice(&(block_registry.len() as u32).to_le_bytes());
+        for (_, block) in block_registry.iter() {
+            write_string(&mut world, &block.name);
+        }
+        GenerationSettings::new(generation_options)?.write_to(&mut world);
+
+        let mut written_files = vec![(
+            write_temporary_file(&self.world_file(), WORLD_FILE_MAGIC, &world)?,
+            self.world_file(),
+        )];
+
+        let mut regions: HashMap<[i32; 2], Vec<(&[i32; 2], &ChunkEdits)>> = HashMap::new();
+        for (parent_pos, chunk_edits) in block_edits.iter() {
+            regions
+                .entry([
+                    parent_pos[0].div_euclid(REGION_SIZE),
+                    parent_pos[1].div_euclid(REGION_SIZE),
+                ])
+                .or_default()
+                .push((parent_pos, chunk_edits));
+        }
+
+        let mut region_files = HashSet::new();
+
+        for (region_pos, chunks) in regions {
+            let mut region = Vec::new();
+            region.extend_from_slice(&(chunks.len() as u32).to_le_bytes());
+
+            for (parent_pos, chunk_edits) in chunks {
+                region.extend_from_slice(&parent_pos[0].to_le_bytes());
+                region.extend_from_slice(&parent_pos[1].to_le_bytes());
+                region.extend_from_slice(&(chunk_edits.blocks.len() as u32).to_le_bytes());
+
+                for (position, block) in &chunk_edits.blocks {
+                    for axis in position.to_array() {
+                        region.extend_from_slice(&axis.to_le_bytes());
+                    }
+                    region.push(block.id());
+                }
+            }
+
+            let region_file = self
+                .region_directory()
+                .join(format!("r.{}.{}.dat", region_pos[0], region_pos[1]));
+            written_files.push((
+                write_temporary_file(&region_file, REGION_FILE_MAGIC, &region)?,
+                region_file.clone(),
+            ));
+            region_files.insert(region_file);
+        }
+
+        for (temporary_file, file) in written_files {
+            fs::rename(temporary_file, file)?;
+        }
+
+        // Regions written with other chunk settings hold edits that were just written again into
+        // the regions of the current ones. Files of failed saves go as well.
+        for entry in fs::read_dir(self.region_directory())? {
+            let path = entry?.path();
+            if !region_files.contains(&path) {
+                fs::remove_file(path)?;
+            }
+        }
+
+        Ok(())
+    }
+}
+
+pub fn world_save_loaded(world_save: Option<Res<WorldSave>>) -> bool {
+    world_save.is_some_and(|world_save| world_save.is_loaded())
+}
+
+fn load_world_save(
+    mut commands: Commands,
+    world_save: Option<ResMut<WorldSave>>,
+    mut generation_options: ResMut<GenerationOptionsResource>,
+    mut block_edits: ResMut<BlockEdits>,
+    player_spawn_callback: Res<PlayerSpawnCallback>,
+) {
+    let Some(mut world_save) = world_save else {
+        return;
+    };
+
+    if world_save.is_loaded() {
+        return;
+    }
+
+    // Broken saves aren't loaded, as saving the world would overwrite them with what could be
+    // read of them.
+    match world_save.read_world() {
+        Ok(true) => info!("Loading world from {:?}", world_save.directory),
+        Ok(false) => info!("Creating new world at {:?}", world_save.directory),
+        Err(error) => {
+            error!(
+                "Failed to read world save {:?}: {error}",
+                world_save.directory
+            );
+            commands.remove_resource::<WorldSave>();
+            return;
+        }
+    }
+
+    let mut new_generation_options =
+        GenerationOptionsResource::new(world_save.seed, world_save.generate_paths);
+    if let Some(generation_settings) = &world_save.generation_settings {
+        new_generation_options.0 = Arc::new(generation_settings.apply(&new_generation_options.0));
+    }
+
+    match world_save.read_block_edits(&new_generation_options.0.block_registry) {
+        Ok(edits) => *block_edits = edits,
+        Err(error) => {
+            error!(
+                "Failed to read region files of {:?}: {error}",
+                world_save.directory
+            );
+            commands.remove_resource::<WorldSave>();
+            return;
+        }
+    }
+
+    *generation_options = new_generation_options;
+
+    world_save.loaded = true;
+    commands.run_system(player_spawn_callback.0);
+}
+
+fn save_world(
+    world_save: &mut WorldSave,
+    block_edits: &BlockEdits,
+    generation_options: &GenerationOptions,
+    players: &Query<&Transform, With<Player>>,
+) {
+    if let Ok(player) = players.single() {
+        world_save.player_position = Some(player.translation);
+    }
+
+    match world_save.write(block_edits, generation_options) {
+        Ok(()) => info!("Saved world to {:?}", world_save.directory),
+        Err(error) => error!("Failed to save world: {error}"),
+    }
+}
+
+fn save_world_on_key(
+    keyboard_input: Res<ButtonInput<KeyCode>>,
+    world_save: Option<ResMut<WorldSave>>,
+    block_edits: Res<BlockEdits>,
+    generation_options: Res<GenerationOptionsResource>,
+    players: Query<&Transform, With<Player>>,
+) {
+    let Some(mut world_save) = world_save else {
+        return;
+    };
+
+    if world_save.is_loaded() && keyboard_input.just_pressed(world_save.save_key) {
+        save_world(
+            &mut world_save,
+            &block_edits,
+            &generation_options.0,
+            &players,
+        );
+    }
+}
+
+fn save_world_on_exit(
+    mut exit_events: EventReader<AppExit>,
+    world_save: Option<ResMut<WorldSave>>,
+    block_edits: Res<BlockEdits>,
+    generation_options: Res<GenerationOptionsResource>,
+    players: Query<&Transform, With<Player>>,
+) {
+    if exit_events.read().last().is_none() {
+        return;
+    }
+
+    let Some(mut world_save) = world_save else {
+        return;
+    };
+
+    if world_save.is_loaded() {
+        save_world(
+            &mut world_save,
+            &block_edits,
+            &generation_options.0,
+            &players,
+        );
+    }
+}
+
+fn open_file(path: &Path, magic: &[u8; 4]) -> io::Result<Option<(ZlibDecoder<fs::File>, u32)>> {
+    let mut file = match fs::File::open(path) {
+        Ok(file) => file,
+        Err(error) if error.kind() == io::ErrorKind::NotFound => return Ok(None),
+        Err(error) => return Err(error),
+    };
+
+    let mut file_magic = [0u8; 4];
+    file.read_exact(&mut file_magic)?;
+    if &file_magic != magic {
+        return Err(invalid_data("Not a world save file"));
+    }
+
+    let version = read_u32(&mut file)?;
+    if !(MIN_WORLD_SAVE_VERSION..=WORLD_SAVE_VERSION).contains(&version) {
+        return Err(invalid_data(&format!(
+            "Unsupported save version {version}, expected {WORLD_SAVE_VERSION}"
+        )));
+    }
+
+    Ok(Some((ZlibDecoder::new(file), version)))
+}
+
+/// Writes the file next to the given path and returns where, to be renamed into place.
+fn write_temporary_file(path: &Path, magic: &[u8; 4], data: &[u8]) -> io::Result<PathBuf> {
+    let temporary_path = path.with_extension("tmp");
+
+    let mut file = fs::File::create(&temporary_path)?;
+    file.write_all(magic)?;
+    file.write_all(&WORLD_SAVE_VERSION.to_le_bytes())?;
+
+    let mut encoder = ZlibEncoder::new(file, Compression::default());
+    encoder.write_all(data)?;
+    encoder.finish()?.sync_all()?;
+
+    Ok(temporary_path)
+}
+
+fn write_string(data: &mut Vec<u8>, string: &str) {
+    data.extend_from_slice(&(string.len() as u32).to_le_bytes());
+    data.extend_from_slice(string.as_bytes());
+}
+
+pub fn invalid_data(message: &str) -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidData, message)
+}
+
+fn read_u8(reader: &mut impl Read) -> io::Result<u8> {
+    let mut bytes = [0u8; 1];
+    reader.read_exact(&mut bytes)?;
+    Ok(bytes[0])
+}
+
+pub fn read_u32(reader: &mut impl Read) -> io::Result<u32> {
+    let mut bytes = [0u8; 4];
+    reader.read_exact(&mut bytes)?;
+    Ok(u32::from_le_bytes(bytes))
+}
+
+pub fn read_i32(reader: &mut impl Read) -> io::Result<i32> {
+    let mut bytes = [0u8; 4];
+    reader.read_exact(&mut bytes)?;
+    Ok(i32::from_le_bytes(bytes))
+}
+
+pub fn read_u64(reader: &mut impl Read) -> io::Result<u64> {
+    let mut bytes = [0u8; 8];
+    reader.read_exact(&mut bytes)?;
+    Ok(u64::from_le_bytes(bytes))
+}
+
+fn read_string(reader: &mut impl Read) -> io::Result<String> {
+    let mut bytes = vec![0u8; read_u32(reader)? as usize];
+    reader.read_exact(&mut bytes)?;
+    String::from_utf8(bytes).map_err(|_| invalid_data("Invalid string"))
+}
+
+pub fn read_f32(reader: &mut impl Read) -> io::Result<f32> {
+    let mut bytes = [0u8; 4];
+    reader.read_exact(&mut bytes)?;
+    Ok(f32::from_le_bytes(bytes))
+}
+
+fn read_f64(reader: &mut impl Read) -> io::Result<f64> {
+    let mut bytes = [0u8; 8];
+    reader.read_exact(&mut bytes)?;
+    Ok(f64::from_le_bytes(bytes))
+}